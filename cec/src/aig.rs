@@ -0,0 +1,243 @@
+// A structurally hashed and-inverter graph.
+//
+// Both netlists of a check are added to the same graph with their inputs shared by name, so
+// equal logic in the two becomes the same node and the miter of an output pair is the xor of
+// two literals. Nodes are created after their fanins, so node order is a simulation order.
+use crate::netlist::{Expr, Netlist, NetlistError};
+use crate::sat::{Lit, Solver};
+use std::collections::{HashMap, HashSet};
+use std::ops::Not;
+
+// A node, complemented when the low bit is set. Node 0 is the constant false.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AigLit(u32);
+
+impl AigLit {
+    pub const FALSE: AigLit = AigLit(0);
+    pub const TRUE: AigLit = AigLit(1);
+
+    fn new(node: usize, complemented: bool) -> AigLit {
+        AigLit((node as u32) << 1 | complemented as u32)
+    }
+
+    pub fn node(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn is_complemented(self) -> bool {
+        self.0 & 1 == 1
+    }
+}
+
+impl Not for AigLit {
+    type Output = AigLit;
+    fn not(self) -> AigLit {
+        AigLit(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Const,
+    // index into the inputs
+    Input(usize),
+    And(AigLit, AigLit),
+}
+
+#[derive(Debug, Default)]
+pub struct Aig {
+    nodes: Vec<Node>,
+    inputs: Vec<String>,
+    input_lits: HashMap<String, AigLit>,
+    strash: HashMap<(AigLit, AigLit), AigLit>,
+}
+
+impl Aig {
+    pub fn new() -> Aig {
+        Aig { nodes: vec![Node::Const], ..Default::default() }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    // The input called `name`, created on first use
+    pub fn input(&mut self, name: &str) -> AigLit {
+        if let Some(lit) = self.input_lits.get(name) {
+            return *lit;
+        }
+        let lit = AigLit::new(self.nodes.len(), false);
+        self.nodes.push(Node::Input(self.inputs.len()));
+        self.inputs.push(name.to_string());
+        self.input_lits.insert(name.to_string(), lit);
+        lit
+    }
+
+    pub fn and(&mut self, a: AigLit, b: AigLit) -> AigLit {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        if a == AigLit::FALSE || a == !b {
+            return AigLit::FALSE;
+        }
+        if a == AigLit::TRUE || a == b {
+            return b;
+        }
+        if let Some(lit) = self.strash.get(&(a, b)) {
+            return *lit;
+        }
+        let lit = AigLit::new(self.nodes.len(), false);
+        self.nodes.push(Node::And(a, b));
+        self.strash.insert((a, b), lit);
+        lit
+    }
+
+    pub fn or(&mut self, a: AigLit, b: AigLit) -> AigLit {
+        !self.and(!a, !b)
+    }
+
+    pub fn xor(&mut self, a: AigLit, b: AigLit) -> AigLit {
+        let x = self.and(a, !b);
+        let y = self.and(!a, b);
+        self.or(x, y)
+    }
+
+    // `a` when `s` is true, `b` otherwise
+    pub fn mux(&mut self, s: AigLit, a: AigLit, b: AigLit) -> AigLit {
+        let x = self.and(s, a);
+        let y = self.and(!s, b);
+        self.or(x, y)
+    }
+
+    pub fn maj(&mut self, a: AigLit, b: AigLit, c: AigLit) -> AigLit {
+        let ab = self.and(a, b);
+        let a_or_b = self.or(a, b);
+        let rest = self.and(c, a_or_b);
+        self.or(ab, rest)
+    }
+
+    // Adds a netlist, its inputs are shared with the netlists already added. Returns its outputs.
+    pub fn add_netlist(&mut self, netlist: &Netlist) -> Result<Vec<(String, AigLit)>, NetlistError> {
+        for (name, _) in &netlist.inputs {
+            self.input(name);
+        }
+        let mut signals: HashMap<&str, AigLit> = HashMap::with_capacity(netlist.assigns.len());
+        for index in netlist.topo_order()? {
+            let assign = &netlist.assigns[index];
+            let lit = self.add_expr(&assign.expr, &signals);
+            signals.insert(assign.name.as_str(), lit);
+        }
+        Ok(netlist
+            .outputs
+            .iter()
+            .map(|(name, _)| (name.clone(), signals.get(name.as_str()).copied().unwrap_or_else(|| self.input_lits[name])))
+            .collect())
+    }
+
+    fn add_expr(&mut self, expr: &Expr, signals: &HashMap<&str, AigLit>) -> AigLit {
+        match expr {
+            Expr::Const(c) => if *c { AigLit::TRUE } else { AigLit::FALSE },
+            Expr::Var(name, _) => signals.get(name.as_str()).copied().unwrap_or_else(|| self.input_lits[name]),
+            Expr::Not(e) => !self.add_expr(e, signals),
+            Expr::And(es) => es.iter().fold(AigLit::TRUE, |acc, e| {
+                let lit = self.add_expr(e, signals);
+                self.and(acc, lit)
+            }),
+            Expr::Or(es) => es.iter().fold(AigLit::FALSE, |acc, e| {
+                let lit = self.add_expr(e, signals);
+                self.or(acc, lit)
+            }),
+            Expr::Xor(es) => es.iter().fold(AigLit::FALSE, |acc, e| {
+                let lit = self.add_expr(e, signals);
+                self.xor(acc, lit)
+            }),
+            Expr::Mux(es) => {
+                let [s, a, b] = es.as_ref();
+                let (s, a, b) = (self.add_expr(s, signals), self.add_expr(a, signals), self.add_expr(b, signals));
+                self.mux(s, a, b)
+            }
+            Expr::Maj(es) => {
+                let [a, b, c] = es.as_ref();
+                let (a, b, c) = (self.add_expr(a, signals), self.add_expr(b, signals), self.add_expr(c, signals));
+                self.maj(a, b, c)
+            }
+        }
+    }
+
+    // The words of every node, `inputs[i]` holds the `words` words of input `i`
+    pub fn simulate(&self, inputs: &[Vec<u64>], words: usize) -> Vec<u64> {
+        let mut values = vec![0u64; self.nodes.len() * words];
+        for (node, kind) in self.nodes.iter().enumerate() {
+            for word in 0..words {
+                values[node * words + word] = match kind {
+                    Node::Const => 0,
+                    Node::Input(input) => inputs[*input][word],
+                    Node::And(a, b) => lit_word(&values, *a, words, word) & lit_word(&values, *b, words, word),
+                };
+            }
+        }
+        values
+    }
+
+    // Encodes the cones of `lits` into `solver`, reusing the nodes encoded by earlier calls
+    pub fn encode(&self, solver: &mut Solver, encoded: &mut HashMap<usize, Lit>, lits: &[AigLit]) -> Vec<Lit> {
+        let mut stack: Vec<usize> = lits.iter().map(|lit| lit.node()).collect();
+        let mut cone = HashSet::new();
+        while let Some(node) = stack.pop() {
+            if encoded.contains_key(&node) || !cone.insert(node) {
+                continue;
+            }
+            if let Node::And(a, b) = self.nodes[node] {
+                stack.push(a.node());
+                stack.push(b.node());
+            }
+        }
+        // fanins have smaller indices
+        let mut cone: Vec<usize> = cone.into_iter().collect();
+        cone.sort_unstable();
+        for node in cone {
+            let lit = match self.nodes[node] {
+                Node::Const => solver.constant(false),
+                Node::Input(_) => solver.new_lit(),
+                Node::And(a, b) => {
+                    let (a, b) = (sat_lit(encoded, a), sat_lit(encoded, b));
+                    solver.and(a, b)
+                }
+            };
+            encoded.insert(node, lit);
+        }
+        lits.iter().map(|lit| sat_lit(encoded, *lit)).collect()
+    }
+
+    // The input assignment of a satisfying model, inputs that were not encoded are false
+    pub fn model(&self, solver: &Solver, encoded: &HashMap<usize, Lit>) -> Vec<bool> {
+        self.inputs
+            .iter()
+            .map(|name| {
+                let sat = encoded.get(&self.input_lits[name].node());
+                sat.is_some_and(|lit| solver.value(lit.var()) == Some(lit.is_positive()))
+            })
+            .collect()
+    }
+}
+
+// word `word` of `lit` in the values of `Aig::simulate`
+pub fn lit_word(values: &[u64], lit: AigLit, words: usize, word: usize) -> u64 {
+    let value = values[lit.node() * words + word];
+    if lit.is_complemented() {
+        !value
+    } else {
+        value
+    }
+}
+
+fn sat_lit(encoded: &HashMap<usize, Lit>, lit: AigLit) -> Lit {
+    let sat = encoded[&lit.node()];
+    if lit.is_complemented() {
+        !sat
+    } else {
+        sat
+    }
+}
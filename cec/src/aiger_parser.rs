@@ -0,0 +1,317 @@
+// Parser for AIGER and-inverter graphs, both the ASCII (`aag`) and the binary (`aig`) format.
+//
+//   header  := ("aag" | "aig") M I L O A [B C J F]
+//   inputs  := one literal per line (ASCII only, implicit in binary files)
+//   latches := [lit] next [init] per line (the latch literal is implicit in binary files)
+//   outputs := one literal per line, bad state properties are read as further outputs
+//   ands    := "lhs rhs0 rhs1" per line, or two delta-encoded varints per gate in binary files
+//   symbols := ("i" | "l" | "o" | "b") index name, up to an optional "c" comment section
+// Latches are cut like in BLIF: the latch becomes a pseudo primary input and its next
+// state function a pseudo primary output.
+use crate::netlist::*;
+use std::collections::{HashMap, HashSet};
+
+fn syntax<T>(pos: Pos, msg: impl Into<String>) -> Result<T, NetlistError> {
+    Err(NetlistError::Syntax { pos, msg: msg.into() })
+}
+
+// Reads the file line by line, except for the binary and-gate section
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    line: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn pos(&self) -> Pos {
+        Pos { line: self.line, col: 1 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+
+    fn next_line(&mut self) -> Result<(&'a str, Pos), NetlistError> {
+        if self.at_end() {
+            return syntax(self.pos(), "unexpected end of file");
+        }
+        let pos = self.pos();
+        let rest = &self.bytes[self.offset..];
+        let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        self.offset += (len + 1).min(rest.len());
+        self.line += 1;
+        match std::str::from_utf8(&rest[..len]) {
+            Ok(line) => Ok((line.trim_end_matches('\r'), pos)),
+            Err(_) => syntax(pos, "line is not valid text"),
+        }
+    }
+
+    // unsigned LEB128 as used for the deltas of binary and gates
+    fn varint(&mut self) -> Result<u32, NetlistError> {
+        let mut value: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let Some(&byte) = self.bytes.get(self.offset) else {
+                return syntax(self.pos(), "unexpected end of file in the and-gate section");
+            };
+            self.offset += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if value > u32::MAX as u64 {
+                return syntax(self.pos(), "delta in the and-gate section is too large");
+            }
+            if byte & 0x80 == 0 {
+                return Ok(value as u32);
+            }
+            shift += 7;
+        }
+    }
+}
+
+fn numbers(line: &str, pos: Pos, min: usize, max: usize) -> Result<Vec<u32>, NetlistError> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < min || fields.len() > max {
+        return syntax(pos, format!("expected {} numbers, found `{}`", if min == max { min.to_string() } else { format!("{} to {}", min, max) }, line));
+    }
+    fields
+        .iter()
+        .map(|field| field.parse::<u32>().or_else(|_| syntax(pos, format!("expected a number, found `{}`", field))))
+        .collect()
+}
+
+// Names every AIG variable and turns literals into expressions
+struct Names {
+    by_var: HashMap<u32, String>,
+}
+
+impl Names {
+    fn literal(&self, lit: u32, pos: Pos) -> Result<Expr, NetlistError> {
+        if lit < 2 {
+            return Ok(Expr::Const(lit == 1));
+        }
+        let Some(name) = self.by_var.get(&(lit / 2)) else {
+            return Err(NetlistError::Undefined {
+                pos,
+                name: format!("literal {}", lit),
+            });
+        };
+        let var = Expr::Var(name.clone(), pos);
+        Ok(if lit % 2 == 1 { Expr::Not(Box::new(var)) } else { var })
+    }
+}
+
+// `base`, or `base` followed by underscores if that name is already taken
+fn fresh(used: &mut HashSet<String>, base: String) -> String {
+    let mut name = base;
+    while used.contains(&name) {
+        name.push('_');
+    }
+    used.insert(name.clone());
+    name
+}
+
+// Parses an AIGER file (ASCII or binary) into a netlist
+pub fn parse_aiger(bytes: &[u8]) -> Result<Netlist, NetlistError> {
+    let mut reader = Reader { bytes, offset: 0, line: 1 };
+    let (header, header_pos) = reader.next_line()?;
+    let mut fields = header.split_whitespace();
+    let binary = match fields.next() {
+        Some("aag") => false,
+        Some("aig") => true,
+        _ => return syntax(header_pos, "expected an `aag` or `aig` header"),
+    };
+    let counts = numbers(&fields.collect::<Vec<_>>().join(" "), header_pos, 5, 9)?;
+    let (max_var, num_inputs, num_latches, num_outputs, num_ands) = (counts[0], counts[1], counts[2], counts[3], counts[4]);
+    let num_bad = counts.get(5).copied().unwrap_or(0);
+    if counts.iter().skip(6).any(|&count| count != 0) {
+        return syntax(header_pos, "invariant constraints, justice and fairness properties are not supported");
+    }
+    if binary && max_var != num_inputs + num_latches + num_ands {
+        return syntax(header_pos, "binary AIGER requires M = I + L + A");
+    }
+
+    // inputs and latches are the variables that are not driven by an and gate
+    let mut input_vars = Vec::with_capacity(num_inputs as usize);
+    for index in 0..num_inputs {
+        if binary {
+            input_vars.push((index + 1, header_pos));
+        } else {
+            let (line, pos) = reader.next_line()?;
+            let lit = numbers(line, pos, 1, 1)?[0];
+            if lit < 2 || lit % 2 == 1 || lit / 2 > max_var {
+                return syntax(pos, format!("invalid input literal {}", lit));
+            }
+            input_vars.push((lit / 2, pos));
+        }
+    }
+
+    // (variable, next state literal, initial value, position)
+    let mut latches = Vec::with_capacity(num_latches as usize);
+    for index in 0..num_latches {
+        let (line, pos) = reader.next_line()?;
+        let (var, rest) = if binary {
+            (num_inputs + index + 1, numbers(line, pos, 1, 2)?)
+        } else {
+            let fields = numbers(line, pos, 2, 3)?;
+            if fields[0] < 2 || fields[0] % 2 == 1 || fields[0] / 2 > max_var {
+                return syntax(pos, format!("invalid latch literal {}", fields[0]));
+            }
+            (fields[0] / 2, fields[1..].to_vec())
+        };
+        let init = match rest.get(1).copied() {
+            None | Some(0) => Some(false),
+            Some(1) => Some(true),
+            Some(lit) if lit == var * 2 => None,
+            Some(lit) => return syntax(pos, format!("invalid latch initial value {}", lit)),
+        };
+        latches.push((var, rest[0], init, pos));
+    }
+
+    let mut outputs = Vec::with_capacity((num_outputs + num_bad) as usize);
+    for _ in 0..num_outputs + num_bad {
+        let (line, pos) = reader.next_line()?;
+        outputs.push((numbers(line, pos, 1, 1)?[0], pos));
+    }
+
+    // (lhs variable, rhs0, rhs1, position)
+    let mut ands = Vec::with_capacity(num_ands as usize);
+    for index in 0..num_ands {
+        if binary {
+            let pos = reader.pos();
+            let lhs = 2 * (num_inputs + num_latches + index + 1);
+            let delta0 = reader.varint()?;
+            let delta1 = reader.varint()?;
+            if delta0 == 0 || delta0 > lhs || delta1 > lhs - delta0 {
+                return syntax(pos, format!("invalid deltas for and gate {}", lhs));
+            }
+            let rhs0 = lhs - delta0;
+            ands.push((lhs / 2, rhs0, rhs0 - delta1, pos));
+        } else {
+            let (line, pos) = reader.next_line()?;
+            let fields = numbers(line, pos, 3, 3)?;
+            if fields[0] < 2 || fields[0] % 2 == 1 || fields[0] / 2 > max_var {
+                return syntax(pos, format!("invalid and gate literal {}", fields[0]));
+            }
+            ands.push((fields[0] / 2, fields[1], fields[2], pos));
+        }
+    }
+    if binary {
+        // the symbol table starts on a fresh line after the binary section
+        let rest = &reader.bytes[reader.offset..];
+        reader.line += rest.iter().take_while(|&&b| b == b'\n').count();
+        reader.offset += rest.iter().take_while(|&&b| b == b'\n').count();
+    }
+
+    // symbol table: `i0 name`, `l0 name`, `o0 name`, `b0 name`
+    let mut symbols: HashMap<(char, usize), String> = HashMap::new();
+    while !reader.at_end() {
+        let (line, pos) = reader.next_line()?;
+        if line == "c" {
+            break;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (key, name) = line.split_once(' ').unwrap_or((line, ""));
+        let kind = key.chars().next().unwrap();
+        match (kind, key[1..].parse::<usize>()) {
+            ('i' | 'l' | 'o' | 'b', Ok(index)) if !name.is_empty() => {
+                symbols.insert((kind, index), name.to_string());
+            }
+            _ => return syntax(pos, format!("invalid symbol table entry `{}`", line)),
+        }
+    }
+
+    let mut netlist = Netlist::default();
+    let mut used: HashSet<String> = symbols.values().cloned().collect();
+    let mut by_var: HashMap<u32, String> = HashMap::new();
+    let mut define = |var: u32, name: String, pos: Pos| -> Result<(), NetlistError> {
+        match by_var.insert(var, name.clone()) {
+            Some(_) => Err(NetlistError::Redefined { pos, name }),
+            None => Ok(()),
+        }
+    };
+
+    for (index, (var, pos)) in input_vars.iter().enumerate() {
+        let name = symbols.get(&('i', index)).cloned().unwrap_or_else(|| fresh(&mut used, format!("i{}", index)));
+        define(*var, name.clone(), *pos)?;
+        netlist.inputs.push((name, *pos));
+    }
+    let mut latch_names = Vec::with_capacity(latches.len());
+    for (index, (var, _, _, pos)) in latches.iter().enumerate() {
+        let name = symbols.get(&('l', index)).cloned().unwrap_or_else(|| fresh(&mut used, format!("l{}", index)));
+        define(*var, name.clone(), *pos)?;
+        netlist.inputs.push((name.clone(), *pos));
+        latch_names.push(name);
+    }
+    for (var, _, _, pos) in &ands {
+        define(*var, fresh(&mut used, format!("n{}", var)), *pos)?;
+    }
+    let names = Names { by_var };
+
+    for (var, rhs0, rhs1, pos) in &ands {
+        netlist.assigns.push(Assign {
+            name: names.by_var[var].clone(),
+            pos: *pos,
+            expr: Expr::And(vec![names.literal(*rhs0, *pos)?, names.literal(*rhs1, *pos)?]),
+        });
+    }
+    for (index, (lit, pos)) in outputs.iter().enumerate() {
+        let name = if index < num_outputs as usize {
+            symbols.get(&('o', index)).cloned().unwrap_or_else(|| fresh(&mut used, format!("o{}", index)))
+        } else {
+            let bad = index - num_outputs as usize;
+            symbols.get(&('b', bad)).cloned().unwrap_or_else(|| fresh(&mut used, format!("b{}", bad)))
+        };
+        netlist.assigns.push(Assign {
+            name: name.clone(),
+            pos: *pos,
+            expr: names.literal(*lit, *pos)?,
+        });
+        netlist.outputs.push((name, *pos));
+    }
+    for ((_, next, init, pos), output) in latches.iter().zip(latch_names) {
+        let input = fresh(&mut used, format!("{}_next", output));
+        netlist.assigns.push(Assign {
+            name: input.clone(),
+            pos: *pos,
+            expr: names.literal(*next, *pos)?,
+        });
+        netlist.outputs.push((input.clone(), *pos));
+        netlist.latches.push(Latch { input, output, init: *init });
+    }
+    Ok(netlist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(src: &[u8]) -> NetlistError {
+        match parse_aiger(src).and_then(|netlist| netlist.topo_order()) {
+            Err(err) => err,
+            Ok(_) => panic!("expected an error in {:?}", String::from_utf8_lossy(src)),
+        }
+    }
+
+    #[test]
+    fn latches_are_cut_and_symbols_name_the_signals() {
+        // q' = a * q, f = !q
+        let netlist = parse_aiger(b"aag 3 1 1 1 1\n2\n4 6 1\n5\n6 2 4\ni0 a\nl0 q\no0 f\nc\ncomment\n").unwrap();
+        let names = |list: &[(String, Pos)]| list.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&netlist.inputs), ["a", "q"]);
+        assert_eq!(netlist.latches.len(), 1);
+        assert_eq!(netlist.latches[0].output, "q");
+        assert_eq!(netlist.latches[0].init, Some(true));
+        assert_eq!(names(&netlist.outputs)[0], "f");
+        assert_eq!(netlist.outputs.len(), 2);
+    }
+
+    #[test]
+    fn errors_report_their_line() {
+        assert!(matches!(error(b"aag 3 2 0 1\n"), NetlistError::Syntax { pos: Pos { line: 1, col: 1 }, .. }));
+        assert!(matches!(error(b"aag 3 2 0 1 1\n2\n4\n6\n6 2 x\n"), NetlistError::Syntax { pos: Pos { line: 5, col: 1 }, .. }));
+        assert!(matches!(error(b"aag 3 2 0 1 1\n2\n4\n6\n"), NetlistError::Syntax { pos: Pos { line: 5, col: 1 }, .. }));
+        assert!(matches!(error(b"aig 3 2 0 1 1\n6\n"), NetlistError::Syntax { pos: Pos { line: 3, col: 1 }, .. }));
+        assert!(matches!(error(b"aag 3 2 0 1 1\n2\n4\n9\n6 2 4\n"), NetlistError::Undefined { pos: Pos { line: 4, col: 1 }, name } if name == "literal 9"));
+    }
+}
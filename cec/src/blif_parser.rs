@@ -0,0 +1,310 @@
+// Parser for combinational and sequential BLIF netlists.
+//
+// Supported constructs (a trailing `\` continues a line, `#` starts a comment):
+//   .model name
+//   .inputs name*            .outputs name*
+//   .names in* out           followed by the rows of a single-output cover
+//   .latch in out [type control] [init]
+//   .end
+// Each `.names` cover becomes an OR of AND cubes over the (possibly negated) inputs,
+// an off-set cover (rows ending in `0`) is complemented. Two- and three-input covers
+// that implement an XOR, XNOR, MUX or MAJ gate become that gate instead.
+// Latches are cut: the latch output becomes a pseudo primary input and the latch input
+// a pseudo primary output, so the rewriting only ever sees combinational logic.
+use crate::netlist::*;
+use std::collections::HashSet;
+
+// a BLIF line after joining continuations, split into words
+struct Line {
+    words: Vec<(String, Pos)>,
+}
+
+fn split_lines(src: &str) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut words = Vec::new();
+    for (line_index, raw) in src.lines().enumerate() {
+        let code = raw.split('#').next().unwrap_or("");
+        let trimmed = code.trim_end();
+        let (code, continued) = match trimmed.strip_suffix('\\') {
+            Some(code) => (code, true),
+            None => (trimmed, false),
+        };
+        let mut start = None;
+        for (byte, c) in code.char_indices().chain(std::iter::once((code.len(), ' '))) {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(byte),
+                (true, Some(begin)) => {
+                    let pos = Pos {
+                        line: line_index + 1,
+                        col: code[..begin].chars().count() + 1,
+                    };
+                    words.push((code[begin..byte].to_string(), pos));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if !continued && !words.is_empty() {
+            lines.push(Line {
+                words: std::mem::take(&mut words),
+            });
+        }
+    }
+    if !words.is_empty() {
+        lines.push(Line { words });
+    }
+    lines
+}
+
+fn syntax<T>(pos: Pos, msg: impl Into<String>) -> Result<T, NetlistError> {
+    Err(NetlistError::Syntax { pos, msg: msg.into() })
+}
+
+// The cover of a `.names` block: one entry per row, the input plane and the output bit
+struct Cover {
+    inputs: Vec<(String, Pos)>,
+    output: String,
+    pos: Pos,
+    rows: Vec<(String, bool, Pos)>,
+}
+
+impl Cover {
+    fn to_expr(&self) -> Result<Expr, NetlistError> {
+        let on_set = match self.rows.first() {
+            Some((_, value, _)) => *value,
+            // an empty cover is the constant 0
+            None => return Ok(Expr::Const(false)),
+        };
+        let mut cubes = Vec::with_capacity(self.rows.len());
+        for (plane, value, pos) in &self.rows {
+            if *value != on_set {
+                return syntax(*pos, format!("cover of `{}` mixes on-set and off-set rows", self.output));
+            }
+            let mut literals = Vec::new();
+            for (c, (input, input_pos)) in plane.chars().zip(&self.inputs) {
+                let var = Expr::Var(input.clone(), *input_pos);
+                match c {
+                    '1' => literals.push(var),
+                    '0' => literals.push(Expr::Not(Box::new(var))),
+                    '-' => {}
+                    c => return syntax(*pos, format!("unexpected character `{}` in cover", c)),
+                }
+            }
+            cubes.push(match literals.len() {
+                0 => Expr::Const(true),
+                1 => literals.pop().unwrap(),
+                _ => Expr::And(literals),
+            });
+        }
+        if let Some(gate) = self.gate(on_set) {
+            return Ok(gate);
+        }
+        let cover = if cubes.len() == 1 { cubes.pop().unwrap() } else { Expr::Or(cubes) };
+        Ok(if on_set { cover } else { Expr::Not(Box::new(cover)) })
+    }
+
+    // truth table of the cover, bit `m` is the value for the input assignment `m`
+    // (input `i` is bit `i` of `m`)
+    fn truth_table(&self, on_set: bool) -> u8 {
+        let mut table = 0u8;
+        for m in 0..1u8 << self.inputs.len() {
+            let covered = self.rows.iter().any(|(plane, _, _)| {
+                plane.chars().enumerate().all(|(i, c)| match c {
+                    '1' => m >> i & 1 == 1,
+                    '0' => m >> i & 1 == 0,
+                    _ => true,
+                })
+            });
+            if covered == on_set {
+                table |= 1 << m;
+            }
+        }
+        table
+    }
+
+    // the cover as a single XOR/XNOR/MUX/MAJ gate, if it is one
+    fn gate(&self, on_set: bool) -> Option<Expr> {
+        if !(2..=3).contains(&self.inputs.len()) {
+            return None;
+        }
+        let table = self.truth_table(on_set);
+        let var = |i: usize| Expr::Var(self.inputs[i].0.clone(), self.inputs[i].1);
+        let xor = Expr::Xor((0..self.inputs.len()).map(var).collect());
+        match (self.inputs.len(), table) {
+            (2, 0x6) | (3, 0x96) => return Some(xor),
+            (2, 0x9) | (3, 0x69) => return Some(Expr::Not(Box::new(xor))),
+            (3, 0xe8) => return Some(Expr::Maj(Box::new([var(0), var(1), var(2)]))),
+            (2, _) => return None,
+            _ => {}
+        }
+        for (s, a, b) in [(0, 1, 2), (0, 2, 1), (1, 0, 2), (1, 2, 0), (2, 0, 1), (2, 1, 0)] {
+            let mux = (0..8u8).filter(|m| if m >> s & 1 == 1 { m >> a & 1 == 1 } else { m >> b & 1 == 1 });
+            if mux.fold(0u8, |table, m| table | 1 << m) == table {
+                return Some(Expr::Mux(Box::new([var(s), var(a), var(b)])));
+            }
+        }
+        None
+    }
+}
+
+// Parses the first model of a BLIF file into a netlist
+pub fn parse_blif(src: &str) -> Result<Netlist, NetlistError> {
+    let mut netlist = Netlist::default();
+    let mut latch_inputs: Vec<(String, Pos)> = Vec::new();
+    let mut cover: Option<Cover> = None;
+
+    let lines = split_lines(src);
+    for line in &lines {
+        let (keyword, pos) = &line.words[0];
+        if !keyword.starts_with('.') {
+            // a row of the current cover
+            let Some(cover) = cover.as_mut() else {
+                return syntax(*pos, format!("expected a `.` command, found `{}`", keyword));
+            };
+            let (plane, value) = match (line.words.len(), cover.inputs.len()) {
+                (1, 0) => ("", &line.words[0]),
+                (2, n) if n > 0 => (line.words[0].0.as_str(), &line.words[1]),
+                _ => return syntax(*pos, format!("malformed row in cover of `{}`", cover.output)),
+            };
+            if plane.chars().count() != cover.inputs.len() {
+                return syntax(
+                    *pos,
+                    format!("row has {} literals but `{}` has {} inputs", plane.chars().count(), cover.output, cover.inputs.len()),
+                );
+            }
+            let value = match value.0.as_str() {
+                "1" => true,
+                "0" => false,
+                other => return syntax(value.1, format!("expected `0` or `1`, found `{}`", other)),
+            };
+            cover.rows.push((plane.to_string(), value, *pos));
+            continue;
+        }
+
+        if let Some(done) = cover.take() {
+            netlist.assigns.push(Assign {
+                name: done.output.clone(),
+                pos: done.pos,
+                expr: done.to_expr()?,
+            });
+        }
+        let args = &line.words[1..];
+        match keyword.as_str() {
+            ".model" => {}
+            ".inputs" => netlist.inputs.extend(args.iter().cloned()),
+            ".outputs" => netlist.outputs.extend(args.iter().cloned()),
+            ".names" => {
+                let Some(((output, output_pos), inputs)) = args.split_last() else {
+                    return syntax(*pos, "`.names` needs an output signal");
+                };
+                cover = Some(Cover {
+                    inputs: inputs.to_vec(),
+                    output: output.clone(),
+                    pos: *output_pos,
+                    rows: Vec::new(),
+                });
+            }
+            ".latch" => {
+                if args.len() < 2 {
+                    return syntax(*pos, "`.latch` needs an input and an output signal");
+                }
+                let init = match args.len() {
+                    2 | 4 => None,
+                    3 | 5 => match args[args.len() - 1].0.as_str() {
+                        "0" => Some(false),
+                        "1" => Some(true),
+                        "2" | "3" => None,
+                        other => return syntax(args[args.len() - 1].1, format!("invalid latch initial value `{}`", other)),
+                    },
+                    _ => return syntax(*pos, "too many arguments to `.latch`"),
+                };
+                netlist.latches.push(Latch {
+                    input: args[0].0.clone(),
+                    output: args[1].0.clone(),
+                    init,
+                });
+                latch_inputs.push(args[0].clone());
+                netlist.inputs.push(args[1].clone());
+            }
+            ".end" => break,
+            // timing and wire-load annotations do not change the logic
+            ".default_input_arrival" | ".default_output_required" | ".default_input_drive" | ".default_output_load"
+            | ".input_arrival" | ".output_required" | ".input_drive" | ".output_load" | ".wire_load_slope" | ".wire"
+            | ".area" | ".delay" | ".exdc" => {}
+            other => return syntax(*pos, format!("unsupported BLIF construct `{}`", other)),
+        }
+    }
+    if let Some(done) = cover.take() {
+        netlist.assigns.push(Assign {
+            name: done.output.clone(),
+            pos: done.pos,
+            expr: done.to_expr()?,
+        });
+    }
+
+    cut_latches(&mut netlist, latch_inputs);
+    Ok(netlist)
+}
+
+// Every latch input becomes a pseudo primary output. A latch input that is already a
+// primary output (or feeds an earlier latch) is driven through a fresh buffer signal,
+// so pseudo outputs never share a name with a real one.
+fn cut_latches(netlist: &mut Netlist, latch_inputs: Vec<(String, Pos)>) {
+    let mut used: HashSet<String> = netlist.inputs.iter().map(|(name, _)| name.clone()).collect();
+    used.extend(netlist.outputs.iter().map(|(name, _)| name.clone()));
+    used.extend(netlist.assigns.iter().map(|assign| assign.name.clone()));
+    let outputs: HashSet<String> = netlist.outputs.iter().map(|(name, _)| name.clone()).collect();
+
+    let mut cut: HashSet<String> = HashSet::new();
+    for (latch, (input, pos)) in netlist.latches.iter_mut().zip(latch_inputs) {
+        if outputs.contains(&input) || !cut.insert(input.clone()) {
+            let mut name = format!("{}_next", latch.output);
+            while used.contains(&name) {
+                name.push('_');
+            }
+            used.insert(name.clone());
+            netlist.assigns.push(Assign {
+                name: name.clone(),
+                pos,
+                expr: Expr::Var(input, pos),
+            });
+            latch.input = name.clone();
+            netlist.outputs.push((name, pos));
+        } else {
+            netlist.outputs.push((input, pos));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the error of parsing `src` and ordering its assignments
+    fn error(src: &str) -> NetlistError {
+        match parse_blif(src).and_then(|netlist| netlist.topo_order()) {
+            Err(err) => err,
+            Ok(_) => panic!("expected an error in {:?}", src),
+        }
+    }
+
+    #[test]
+    fn covers_latches_and_continuations_are_read() {
+        let netlist = parse_blif(".model m\n.inputs a \\\n  b\n.outputs f\n.latch f q 0\n.names a b q f\n1-1 1\n-11 1\n.end\n").unwrap();
+        let names = |list: &[(String, Pos)]| list.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&netlist.inputs), ["a", "b", "q"]);
+        // `f` is already a primary output, so the latch input gets a buffer
+        assert_eq!(names(&netlist.outputs), ["f", "q_next"]);
+        assert_eq!(netlist.latches, vec![Latch { input: "q_next".to_string(), output: "q".to_string(), init: Some(false) }]);
+        assert_eq!(netlist.topo_order().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn errors_report_line_and_column() {
+        let header = ".model m\n.inputs a b\n.outputs f\n";
+        assert!(matches!(error(&format!("{}.names a b f\n1x 1\n.end\n", header)), NetlistError::Syntax { pos: Pos { line: 5, col: 1 }, .. }));
+        assert!(matches!(error(&format!("{}.frob a b f\n.end\n", header)), NetlistError::Syntax { pos: Pos { line: 4, col: 1 }, .. }));
+        assert!(matches!(error(&format!("{}.names a x f\n11 1\n.end\n", header)), NetlistError::Undefined { pos: Pos { line: 4, col: 10 }, name } if name == "x"));
+        assert!(matches!(error(&format!("{}.names a b f\n11 1\n.names a f\n1 1\n.end\n", header)), NetlistError::Redefined { pos: Pos { line: 6, col: 10 }, name } if name == "f"));
+    }
+}
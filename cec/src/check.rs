@@ -0,0 +1,324 @@
+// SAT-based equivalence check of two netlists.
+//
+// Both netlists are added to one structurally hashed AIG with their inputs shared by name, the
+// miter. Output pairs that hash to the same node are equivalent without further work. The rest
+// are simulated together, on every input assignment when there are few inputs, which decides
+// every pair, and on random patterns otherwise, which tells most different pairs apart. The
+// pairs left are checked one at a time with the SAT solver. Their cones are encoded into one
+// incremental solver and every proven pair is added as a clause, so later checks reuse the
+// work of earlier ones. A care set is added to the miter too, and a pair only differs where
+// its output is 1.
+use crate::aig::{lit_word, Aig, AigLit};
+use crate::netlist::Netlist;
+use crate::sat::{SatResult, Solver};
+use crate::sim::{assignment_word, care_set, CecError, Counterexample, Matching, SimConfig};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fmt;
+
+// patterns simulated at once, in words
+const CHUNK: usize = 16;
+
+pub struct CecConfig {
+    // the random or exhaustive simulation before SAT
+    pub sim: SimConfig,
+    // conflicts per output pair, the pair is undecided when it is reached
+    pub conflict_limit: Option<u64>,
+}
+
+impl Default for CecConfig {
+    fn default() -> Self {
+        CecConfig {
+            sim: SimConfig::default(),
+            conflict_limit: Some(100_000),
+        }
+    }
+}
+
+// How an output pair was proven equivalent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    // both outputs are the same node of the miter
+    Structural,
+    // on every input assignment
+    Simulation,
+    Sat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Equivalent(Method),
+    Different(Counterexample),
+    // the conflict limit was reached
+    Undecided,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Equivalent(Method::Structural) => write!(f, "equivalent (structural)"),
+            Verdict::Equivalent(Method::Simulation) => write!(f, "equivalent (exhaustive simulation)"),
+            Verdict::Equivalent(Method::Sat) => write!(f, "equivalent (SAT)"),
+            Verdict::Different(counterexample) => write!(f, "different: {}", counterexample),
+            Verdict::Undecided => write!(f, "undecided"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CecReport {
+    // the verdict of every output, in the order of the first netlist
+    pub outputs: Vec<(String, Verdict)>,
+    // inputs of one netlist only, the other netlist does not depend on them
+    pub only_in_first: Vec<String>,
+    pub only_in_second: Vec<String>,
+    pub miter_nodes: usize,
+    pub patterns: u64,
+    pub sat_checks: usize,
+    pub conflicts: u64,
+}
+
+impl CecReport {
+    // every output pair is proven equivalent
+    pub fn equivalent(&self) -> bool {
+        self.outputs.iter().all(|(_, verdict)| matches!(verdict, Verdict::Equivalent(_)))
+    }
+
+    pub fn counterexamples(&self) -> impl Iterator<Item = &Counterexample> {
+        self.outputs.iter().filter_map(|(_, verdict)| match verdict {
+            Verdict::Different(counterexample) => Some(counterexample),
+            _ => None,
+        })
+    }
+
+    pub fn count(&self, pred: impl Fn(&Verdict) -> bool) -> usize {
+        self.outputs.iter().filter(|(_, verdict)| pred(verdict)).count()
+    }
+}
+
+// The values of an output pair under an input assignment, `assignment` follows `aig.inputs()`
+fn counterexample(aig: &Aig, output: &str, a: AigLit, b: AigLit, assignment: Vec<bool>) -> Counterexample {
+    let inputs: Vec<Vec<u64>> = assignment.iter().map(|value| vec![0u64.wrapping_sub(*value as u64)]).collect();
+    let values = aig.simulate(&inputs, 1);
+    Counterexample {
+        output: output.to_string(),
+        inputs: aig.inputs().iter().cloned().zip(assignment).collect(),
+        first: lit_word(&values, a, 1, 0) & 1 == 1,
+        second: lit_word(&values, b, 1, 0) & 1 == 1,
+    }
+}
+
+// Checks every output pair of two netlists, matched by name
+pub fn check_equivalence(first: &Netlist, second: &Netlist, config: &CecConfig) -> Result<CecReport, CecError> {
+    let care = care_set(&config.sim)?;
+    let matching = Matching::new(first, second)?;
+    let mut aig = Aig::new();
+    for name in &matching.inputs {
+        aig.input(name);
+    }
+    let first_outputs = aig.add_netlist(first)?;
+    let second_outputs = aig.add_netlist(second)?;
+    let care = match care {
+        Some(care) => aig.add_netlist(care)?[0].1,
+        None => AigLit::TRUE,
+    };
+    let pairs: Vec<(AigLit, AigLit)> = first_outputs.iter().zip(&matching.second_outputs).map(|((_, a), index)| (*a, second_outputs[*index].1)).collect();
+    let mut verdicts: Vec<Option<Verdict>> = pairs.iter().map(|(a, b)| (a == b).then_some(Verdict::Equivalent(Method::Structural))).collect();
+    let mut report = CecReport {
+        outputs: Vec::new(),
+        only_in_first: matching.only_in_first,
+        only_in_second: matching.only_in_second,
+        miter_nodes: aig.num_nodes(),
+        patterns: 0,
+        sat_checks: 0,
+        conflicts: 0,
+    };
+
+    let num_inputs = aig.inputs().len();
+    let exhaustive = num_inputs <= config.sim.exhaustive_inputs;
+    let total_words = if exhaustive { (1usize << num_inputs).div_ceil(64) } else { config.sim.words };
+    // the patterns of the last word beyond the assignments are not simulated
+    let last_mask = if exhaustive && num_inputs < 6 { (1u64 << (1 << num_inputs)) - 1 } else { u64::MAX };
+    let mut rng = StdRng::seed_from_u64(config.sim.seed);
+    for start in (0..total_words).step_by(CHUNK) {
+        if verdicts.iter().all(Option::is_some) {
+            break;
+        }
+        let words = CHUNK.min(total_words - start);
+        let inputs: Vec<Vec<u64>> = (0..num_inputs)
+            .map(|var| (start..start + words).map(|word| if exhaustive { assignment_word(var, word) } else { rng.gen() }).collect())
+            .collect();
+        let values = aig.simulate(&inputs, words);
+        report.patterns += if exhaustive && num_inputs < 6 { 1 << num_inputs } else { 64 * words as u64 };
+        for (output, (a, b)) in pairs.iter().enumerate() {
+            if verdicts[output].is_some() {
+                continue;
+            }
+            let differ = (0..words).find_map(|word| {
+                let diff = (lit_word(&values, *a, words, word) ^ lit_word(&values, *b, words, word)) & lit_word(&values, care, words, word) & last_mask;
+                (diff != 0).then(|| (word, diff.trailing_zeros()))
+            });
+            if let Some((word, bit)) = differ {
+                let assignment = inputs.iter().map(|words| words[word] >> bit & 1 == 1).collect();
+                verdicts[output] = Some(Verdict::Different(counterexample(&aig, &first_outputs[output].0, *a, *b, assignment)));
+            }
+        }
+    }
+
+    let mut solver = Solver::new();
+    let mut encoded = HashMap::new();
+    for (output, (a, b)) in pairs.iter().enumerate() {
+        if verdicts[output].is_some() {
+            continue;
+        }
+        if exhaustive {
+            verdicts[output] = Some(Verdict::Equivalent(Method::Simulation));
+            continue;
+        }
+        let lits = aig.encode(&mut solver, &mut encoded, &[*a, *b, care]);
+        let miter = solver.xor(lits[0], lits[1]);
+        report.sat_checks += 1;
+        verdicts[output] = Some(match solver.solve(&[miter, lits[2]], config.conflict_limit) {
+            SatResult::Unsat => {
+                solver.add_clause(&[!lits[2], !lits[0], lits[1]]);
+                solver.add_clause(&[!lits[2], lits[0], !lits[1]]);
+                Verdict::Equivalent(Method::Sat)
+            }
+            SatResult::Sat => Verdict::Different(counterexample(&aig, &first_outputs[output].0, *a, *b, aig.model(&solver, &encoded))),
+            SatResult::Unknown => Verdict::Undecided,
+        });
+    }
+    report.conflicts = solver.conflicts;
+    report.outputs = first_outputs.into_iter().zip(verdicts).map(|((name, _), verdict)| (name, verdict.expect("every output is decided"))).collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlist::Expr;
+    use crate::{aiger_parser::parse_aiger, blif_parser::parse_blif, eqn_parser::parse_eqn, verilog_parser::parse_verilog};
+
+    // no simulation, every output pair that is not structurally equal goes to the solver
+    fn sat_only() -> CecConfig {
+        CecConfig {
+            sim: SimConfig { words: 0, exhaustive_inputs: 0, seed: 0, care: None },
+            ..Default::default()
+        }
+    }
+
+    fn eval(expr: &Expr, values: &HashMap<String, bool>) -> bool {
+        match expr {
+            Expr::Const(c) => *c,
+            Expr::Var(name, _) => values[name],
+            Expr::Not(e) => !eval(e, values),
+            Expr::And(es) => es.iter().all(|e| eval(e, values)),
+            Expr::Or(es) => es.iter().any(|e| eval(e, values)),
+            Expr::Xor(es) => es.iter().fold(false, |acc, e| acc ^ eval(e, values)),
+            Expr::Mux(es) => if eval(&es[0], values) { eval(&es[1], values) } else { eval(&es[2], values) },
+            Expr::Maj(es) => es.iter().filter(|e| eval(e, values)).count() >= 2,
+        }
+    }
+
+    // the value of `output` under the counterexample's input assignment
+    fn output_value(netlist: &Netlist, counterexample: &Counterexample) -> bool {
+        let mut values: HashMap<String, bool> = counterexample.inputs.iter().cloned().collect();
+        for index in netlist.topo_order().unwrap() {
+            let assign = &netlist.assigns[index];
+            let value = eval(&assign.expr, &values);
+            values.insert(assign.name.clone(), value);
+        }
+        values[&counterexample.output]
+    }
+
+    #[test]
+    fn equivalent_miter_is_unsat() {
+        let first = parse_eqn("INORDER = a b c; OUTORDER = f g; f = a * (b + c); g = !(a * b);").unwrap();
+        let second = parse_eqn("INORDER = a b c; OUTORDER = g f; f = a * b + a * c; g = !a + !b;").unwrap();
+        let report = check_equivalence(&first, &second, &sat_only()).unwrap();
+        assert!(report.equivalent());
+        assert_eq!(report.outputs[0], ("f".to_string(), Verdict::Equivalent(Method::Sat)));
+        // `!a + !b` is strashed to the same node as `!(a * b)`
+        assert_eq!(report.outputs[1], ("g".to_string(), Verdict::Equivalent(Method::Structural)));
+        assert_eq!(report.sat_checks, 1);
+        // exhaustive simulation decides the same without the solver
+        let report = check_equivalence(&first, &second, &CecConfig::default()).unwrap();
+        assert_eq!(report.count(|verdict| *verdict == Verdict::Equivalent(Method::Simulation)), 1);
+        assert_eq!(report.sat_checks, 0);
+    }
+
+    #[test]
+    fn differences_outside_the_care_set_are_ignored() {
+        // `f` differs for `a = 0, b = 1` only, outside of the care set `a`, `g` for `a = b = 1`
+        let first = parse_eqn("INORDER = a b; OUTORDER = f g; f = a * b; g = a;").unwrap();
+        let second = parse_eqn("INORDER = a b; OUTORDER = f g; f = b; g = a * !b;").unwrap();
+        let care = parse_eqn("INORDER = a; OUTORDER = care; care = a;").unwrap();
+        for config in [sat_only(), CecConfig::default()] {
+            let report = check_equivalence(&first, &second, &config).unwrap();
+            assert_eq!(report.counterexamples().count(), 2);
+
+            let config = CecConfig { sim: SimConfig { care: Some(care.clone()), ..config.sim }, ..config };
+            let report = check_equivalence(&first, &second, &config).unwrap();
+            assert!(matches!(report.outputs[0].1, Verdict::Equivalent(_)), "{}", report.outputs[0].1);
+            let counterexample = report.counterexamples().next().expect("`g` differs on the care set");
+            assert_eq!(counterexample.output, "g");
+            assert_eq!(counterexample.inputs, [("a".to_string(), true), ("b".to_string(), true)]);
+        }
+        let two_outputs = parse_eqn("INORDER = a b; OUTORDER = x y; x = a; y = b;").unwrap();
+        let config = CecConfig { sim: SimConfig { care: Some(two_outputs), ..Default::default() }, ..Default::default() };
+        assert!(matches!(check_equivalence(&first, &second, &config), Err(CecError::CareOutputs(2))));
+    }
+
+    #[test]
+    fn counterexamples_tell_the_netlists_apart() {
+        // `f` differs on one assignment of 20 inputs only, `g` on half of them
+        let names: Vec<String> = (0..20).map(|i| format!("x{}", i)).collect();
+        let first = parse_eqn(&format!("INORDER = {}; OUTORDER = f g; f = {}; g = x0 * x1;", names.join(" "), names.join(" * "))).unwrap();
+        let second = parse_eqn(&format!("INORDER = {}; OUTORDER = f g; f = 0; g = x0;", names.join(" "))).unwrap();
+        for config in [sat_only(), CecConfig::default()] {
+            let report = check_equivalence(&first, &second, &config).unwrap();
+            assert!(!report.equivalent());
+            assert_eq!(report.counterexamples().count(), 2);
+            for counterexample in report.counterexamples() {
+                assert_eq!(output_value(&first, counterexample), counterexample.first, "{}", counterexample);
+                assert_eq!(output_value(&second, counterexample), counterexample.second, "{}", counterexample);
+                assert_ne!(counterexample.first, counterexample.second);
+            }
+        }
+    }
+
+    #[test]
+    fn outputs_of_one_netlist_only_are_errors() {
+        let first = parse_eqn("INORDER = a b; OUTORDER = f g; f = a * b; g = a + b;").unwrap();
+        let second = parse_eqn("INORDER = a b; OUTORDER = f; f = a * b;").unwrap();
+        assert!(matches!(check_equivalence(&first, &second, &CecConfig::default()), Err(CecError::MissingInSecond(name)) if name == "g"));
+        assert!(matches!(check_equivalence(&second, &first, &CecConfig::default()), Err(CecError::MissingInFirst(name)) if name == "g"));
+        // inputs of one netlist only are reported, not errors
+        let third = parse_eqn("INORDER = a b c; OUTORDER = f; f = a * b + c * !c;").unwrap();
+        let report = check_equivalence(&second, &third, &CecConfig::default()).unwrap();
+        assert!(report.equivalent());
+        assert_eq!(report.only_in_second, vec!["c".to_string()]);
+    }
+
+    #[test]
+    fn every_format_parses_to_the_same_circuit() {
+        let eqn = parse_eqn("INORDER = a b c; OUTORDER = f g; f = a * b + !c; g = a * !c + !a * c;").unwrap();
+        let blif = parse_blif(".model m\n.inputs a b c\n.outputs f g\n.names a b c f\n11- 1\n--0 1\n.names a c g\n10 1\n01 1\n.end\n").unwrap();
+        let verilog = parse_verilog("module m(a, b, c, f, g);\n  input a, b, c;\n  output f, g;\n  assign f = (a & b) | ~c;\n  xor x0 (g, a, c);\nendmodule\n").unwrap();
+        // f = !(!(a * b) * c), g = !(!(a * !c) * !(!a * c))
+        let symbols = "i0 a\ni1 b\ni2 c\no0 f\no1 g\n";
+        let aag = parse_aiger(format!("aag 8 3 0 2 5\n2\n4\n6\n11\n17\n8 4 2\n10 9 6\n12 7 2\n14 6 3\n16 15 13\n{}", symbols).as_bytes()).unwrap();
+        // the same gates delta-encoded
+        let mut aig = b"aig 8 3 0 2 5\n11\n17\n".to_vec();
+        aig.extend_from_slice(&[4, 2, 1, 3, 5, 5, 8, 3, 1, 2]);
+        aig.extend_from_slice(symbols.as_bytes());
+        let aig = parse_aiger(&aig).unwrap();
+        for (format, netlist) in [("blif", blif), ("verilog", verilog), ("aag", aag), ("aig", aig)] {
+            let names = |netlist: &Netlist| (netlist.inputs.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>(), netlist.outputs.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>());
+            assert_eq!(names(&netlist), names(&eqn), "{}", format);
+            let report = check_equivalence(&eqn, &netlist, &sat_only()).unwrap();
+            assert!(report.equivalent(), "{}: {:?}", format, report.outputs);
+        }
+    }
+}
@@ -0,0 +1,363 @@
+// Parser for ABC/SIS eqn netlists.
+//
+// Grammar (statements end with `;` and may span several lines, `#` starts a comment):
+//   stmt    := ("INORDER" | "OUTORDER") "=" name* ";" | name "=" expr ";"
+//   expr    := xor (("+" | "|") xor)*
+//   xor     := and ("^" and)*
+//   and     := unary (("*" | "&") unary)*
+//   unary   := "!" unary | primary "'"*
+//   primary := "(" expr ")" | "0" | "1" | name
+use crate::netlist::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Eq,
+    Semi,
+    LParen,
+    RParen,
+    Not,
+    Quote,
+    And,
+    Or,
+    Xor,
+    Eof,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Name(name) => format!("`{}`", name),
+            Token::Eq => "`=`".to_string(),
+            Token::Semi => "`;`".to_string(),
+            Token::LParen => "`(`".to_string(),
+            Token::RParen => "`)`".to_string(),
+            Token::Not => "`!`".to_string(),
+            Token::Quote => "`'`".to_string(),
+            Token::And => "`*`".to_string(),
+            Token::Or => "`+`".to_string(),
+            Token::Xor => "`^`".to_string(),
+            Token::Eof => "end of file".to_string(),
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '[' | ']' | '.' | '$' | ':' | '<' | '>' | '/' | '\\' | '-')
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token, Pos)>, NetlistError> {
+    let mut tokens = Vec::new();
+    for (line_index, line) in src.lines().enumerate() {
+        let mut chars = line.char_indices().peekable();
+        while let Some(&(byte, c)) = chars.peek() {
+            // columns count characters, not bytes
+            let pos = Pos {
+                line: line_index + 1,
+                col: line[..byte].chars().count() + 1,
+            };
+            let token = match c {
+                '#' => break,
+                c if c.is_whitespace() => {
+                    chars.next();
+                    continue;
+                }
+                '=' => Token::Eq,
+                ';' => Token::Semi,
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                '!' => Token::Not,
+                '\'' => Token::Quote,
+                '*' | '&' => Token::And,
+                '+' | '|' => Token::Or,
+                '^' => Token::Xor,
+                c if is_name_char(c) => {
+                    let mut name = String::new();
+                    while let Some(&(_, c)) = chars.peek() {
+                        if !is_name_char(c) {
+                            break;
+                        }
+                        name.push(c);
+                        chars.next();
+                    }
+                    tokens.push((Token::Name(name), pos));
+                    continue;
+                }
+                c => {
+                    return Err(NetlistError::Syntax {
+                        pos,
+                        msg: format!("unexpected character `{}`", c),
+                    })
+                }
+            };
+            chars.next();
+            tokens.push((token, pos));
+        }
+    }
+    let eof = Pos {
+        line: src.lines().count() + 1,
+        col: 1,
+    };
+    tokens.push((Token::Eof, eof));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, Pos)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn pos(&self) -> Pos {
+        self.tokens[self.next].1
+    }
+
+    fn bump(&mut self) -> (Token, Pos) {
+        let token = self.tokens[self.next].clone();
+        if token.0 != Token::Eof {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, NetlistError> {
+        Err(NetlistError::Syntax {
+            pos: self.pos(),
+            msg: format!("expected {}, found {}", expected, self.peek().describe()),
+        })
+    }
+
+    fn expect(&mut self, token: Token) -> Result<Pos, NetlistError> {
+        if *self.peek() == token {
+            Ok(self.bump().1)
+        } else {
+            self.error(&token.describe())
+        }
+    }
+
+    // the final `;` of a file is optional
+    fn end_statement(&mut self) -> Result<(), NetlistError> {
+        match self.peek() {
+            Token::Semi => {
+                self.bump();
+                Ok(())
+            }
+            Token::Eof => Ok(()),
+            _ => self.error("`;`"),
+        }
+    }
+
+    fn netlist(&mut self) -> Result<Netlist, NetlistError> {
+        let mut netlist = Netlist::default();
+        loop {
+            let (token, pos) = self.bump();
+            let name = match token {
+                Token::Eof => return Ok(netlist),
+                Token::Semi => continue,
+                Token::Name(name) => name,
+                token => {
+                    return Err(NetlistError::Syntax {
+                        pos,
+                        msg: format!("expected a signal name, found {}", token.describe()),
+                    })
+                }
+            };
+            self.expect(Token::Eq)?;
+            match name.as_str() {
+                "INORDER" | "OUTORDER" => {
+                    let mut names = Vec::new();
+                    while let Token::Name(_) = self.peek() {
+                        let (token, pos) = self.bump();
+                        if let Token::Name(name) = token {
+                            names.push((name, pos));
+                        }
+                    }
+                    self.end_statement()?;
+                    if name == "INORDER" {
+                        netlist.inputs.extend(names);
+                    } else {
+                        netlist.outputs.extend(names);
+                    }
+                }
+                _ => {
+                    let expr = self.expr()?;
+                    self.end_statement()?;
+                    netlist.assigns.push(Assign { name, pos, expr });
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, NetlistError> {
+        let mut operands = vec![self.xor()?];
+        while *self.peek() == Token::Or {
+            self.bump();
+            operands.push(self.xor()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Expr::Or(operands)
+        })
+    }
+
+    fn xor(&mut self) -> Result<Expr, NetlistError> {
+        let mut operands = vec![self.and()?];
+        while *self.peek() == Token::Xor {
+            self.bump();
+            operands.push(self.and()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Expr::Xor(operands)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, NetlistError> {
+        let mut operands = vec![self.unary()?];
+        while *self.peek() == Token::And {
+            self.bump();
+            operands.push(self.unary()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Expr::And(operands)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr, NetlistError> {
+        if *self.peek() == Token::Not {
+            self.bump();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let mut expr = self.primary()?;
+        while *self.peek() == Token::Quote {
+            self.bump();
+            expr = Expr::Not(Box::new(expr));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, NetlistError> {
+        match self.peek().clone() {
+            Token::LParen => {
+                self.bump();
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Token::Name(name) => {
+                let pos = self.bump().1;
+                Ok(match name.as_str() {
+                    "0" => Expr::Const(false),
+                    "1" => Expr::Const(true),
+                    _ => Expr::Var(name, pos),
+                })
+            }
+            _ => self.error("an expression"),
+        }
+    }
+}
+
+// Parses the content of an eqn file into a netlist
+pub fn parse_eqn(src: &str) -> Result<Netlist, NetlistError> {
+    let tokens = tokenize(src)?;
+    Parser { tokens, next: 0 }.netlist()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Expr {
+        Expr::Var(name.to_string(), Pos::default())
+    }
+
+    // the expression without positions, so that it can be compared with one built by hand
+    fn strip(expr: &Expr) -> Expr {
+        match expr {
+            Expr::Const(c) => Expr::Const(*c),
+            Expr::Var(name, _) => var(name),
+            Expr::Not(e) => Expr::Not(Box::new(strip(e))),
+            Expr::And(es) => Expr::And(es.iter().map(strip).collect()),
+            Expr::Or(es) => Expr::Or(es.iter().map(strip).collect()),
+            Expr::Xor(es) => Expr::Xor(es.iter().map(strip).collect()),
+            Expr::Mux(es) => Expr::Mux(Box::new(es.clone().map(|e| strip(&e)))),
+            Expr::Maj(es) => Expr::Maj(Box::new(es.clone().map(|e| strip(&e)))),
+        }
+    }
+
+    fn parse_expr(src: &str) -> Expr {
+        let netlist = parse_eqn(&format!("INORDER = a b c d; OUTORDER = f; f = {};", src)).unwrap();
+        strip(&netlist.assigns[0].expr)
+    }
+
+    fn syntax_pos(src: &str) -> Pos {
+        match parse_eqn(src) {
+            Err(NetlistError::Syntax { pos, .. }) => pos,
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn or_binds_weaker_than_xor_and_xor_weaker_than_and() {
+        let expected = Expr::Or(vec![var("a"), Expr::Xor(vec![var("b"), Expr::And(vec![var("c"), var("d")])])]);
+        assert_eq!(parse_expr("a + b ^ c * d"), expected);
+        assert_eq!(parse_expr("a | b ^ c & d"), expected);
+        assert_eq!(parse_expr("d * c ^ b + a"), Expr::Or(vec![Expr::Xor(vec![Expr::And(vec![var("d"), var("c")]), var("b")]), var("a")]));
+    }
+
+    #[test]
+    fn negation_binds_tightest() {
+        let not = |e: Expr| Expr::Not(Box::new(e));
+        assert_eq!(parse_expr("!a * b"), Expr::And(vec![not(var("a")), var("b")]));
+        assert_eq!(parse_expr("a' * b"), Expr::And(vec![not(var("a")), var("b")]));
+        assert_eq!(parse_expr("!(a * b)'"), not(not(Expr::And(vec![var("a"), var("b")]))));
+        assert_eq!(parse_expr("(a + b) * c"), Expr::And(vec![Expr::Or(vec![var("a"), var("b")]), var("c")]));
+    }
+
+    #[test]
+    fn operators_are_n_ary_and_constants_are_read() {
+        assert_eq!(parse_expr("a * b * c"), Expr::And(vec![var("a"), var("b"), var("c")]));
+        assert_eq!(parse_expr("a + 0 + 1"), Expr::Or(vec![var("a"), Expr::Const(false), Expr::Const(true)]));
+    }
+
+    #[test]
+    fn syntax_errors_report_line_and_column() {
+        assert_eq!(syntax_pos("INORDER = a;\nOUTORDER = f;\nf = a * ;\n"), Pos { line: 3, col: 9 });
+        assert_eq!(syntax_pos("INORDER = a b;\nf = (a + b;"), Pos { line: 2, col: 11 });
+        assert_eq!(syntax_pos("f = a\n  ? b;"), Pos { line: 2, col: 3 });
+        // columns count characters, not bytes
+        assert_eq!(syntax_pos("# ü\nf = ü @ b;"), Pos { line: 2, col: 7 });
+    }
+
+    #[test]
+    fn undefined_signals_report_their_use() {
+        let netlist = parse_eqn("INORDER = a;\nOUTORDER = f;\nf = a * x;").unwrap();
+        match netlist.topo_order() {
+            Err(NetlistError::Undefined { pos, name }) => assert_eq!((pos, name.as_str()), (Pos { line: 3, col: 9 }, "x")),
+            other => panic!("expected an undefined signal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn repeated_declarations_are_concatenated() {
+        let netlist = parse_eqn("INORDER = a b;\nINORDER = c;\nOUTORDER = f;\nOUTORDER = g h;\nf = a;\ng = b;\nh = c;").unwrap();
+        let names = |list: &[(String, Pos)]| list.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&netlist.inputs), ["a", "b", "c"]);
+        assert_eq!(names(&netlist.outputs), ["f", "g", "h"]);
+        assert_eq!(netlist.outputs[2].1, Pos { line: 4, col: 14 });
+    }
+
+    #[test]
+    fn redefined_signals_are_rejected() {
+        let netlist = parse_eqn("INORDER = a;\nOUTORDER = f;\nf = a;\nf = !a;").unwrap();
+        assert!(matches!(netlist.topo_order(), Err(NetlistError::Redefined { pos: Pos { line: 4, col: 1 }, .. })));
+    }
+}
@@ -0,0 +1,25 @@
+// Netlist parsers and combinational equivalence checking, shared by e-rewriter and extraction-gym
+pub mod aig;
+pub mod aiger_parser;
+pub mod blif_parser;
+pub mod check;
+pub mod eqn_parser;
+pub mod netlist;
+pub mod sat;
+pub mod sim;
+pub mod verilog_parser;
+
+use netlist::{Netlist, NetlistError};
+use std::fs;
+use std::path::Path;
+
+// Parses a netlist file. The format is chosen by extension: `.blif` is read as BLIF,
+// `.aag`/`.aig` as AIGER, `.v` as structural Verilog and anything else as eqn.
+pub fn read_netlist(file_name: &str) -> Result<Netlist, NetlistError> {
+    match Path::new(file_name).extension().and_then(|ext| ext.to_str()) {
+        Some("aag") | Some("aig") => aiger_parser::parse_aiger(&fs::read(file_name)?),
+        Some("blif") => blif_parser::parse_blif(&fs::read_to_string(file_name)?),
+        Some("v") => verilog_parser::parse_verilog(&fs::read_to_string(file_name)?),
+        _ => eqn_parser::parse_eqn(&fs::read_to_string(file_name)?),
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use thiserror::Error;
+
+// Position of a token in the source netlist (1-based line and column)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum NetlistError {
+    #[error("{pos}: {msg}")]
+    Syntax { pos: Pos, msg: String },
+    #[error("{pos}: undefined signal `{name}`")]
+    Undefined { pos: Pos, name: String },
+    #[error("{pos}: signal `{name}` is defined more than once")]
+    Redefined { pos: Pos, name: String },
+    #[error("{pos}: combinational cycle through signal `{name}`")]
+    Cycle { pos: Pos, name: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+// Boolean expression on the right-hand side of an assignment.
+// And/Or/Xor are n-ary, they are folded into binary egraph nodes when the egraph is built.
+// A negated Xor becomes an xnor node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Const(bool),
+    Var(String, Pos),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Vec<Expr>),
+    // select, then, else
+    Mux(Box<[Expr; 3]>),
+    Maj(Box<[Expr; 3]>),
+}
+
+impl Expr {
+    // visit every signal referenced by the expression
+    pub fn for_each_var<'a>(&'a self, f: &mut impl FnMut(&'a str, Pos)) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Var(name, pos) => f(name, *pos),
+            Expr::Not(e) => e.for_each_var(f),
+            Expr::And(es) | Expr::Or(es) | Expr::Xor(es) => es.iter().for_each(|e| e.for_each_var(f)),
+            Expr::Mux(es) | Expr::Maj(es) => es.iter().for_each(|e| e.for_each_var(f)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Assign {
+    pub name: String,
+    pub pos: Pos,
+    pub expr: Expr,
+}
+
+// A latch cut out of a sequential netlist. Its `output` is listed as a pseudo primary
+// input and its `input` as a pseudo primary output of the combinational netlist.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Latch {
+    pub input: String,
+    pub output: String,
+    // `None` when the initial value is unknown or don't care
+    pub init: Option<bool>,
+}
+
+// A parsed combinational netlist, independent of the input format
+#[derive(Debug, Clone, Default)]
+pub struct Netlist {
+    pub inputs: Vec<(String, Pos)>,
+    pub outputs: Vec<(String, Pos)>,
+    pub assigns: Vec<Assign>,
+    pub latches: Vec<Latch>,
+}
+
+impl Netlist {
+    // Orders the assignments so that every signal is defined before it is used.
+    // Assignments that are already in topological order keep their file order.
+    pub fn topo_order(&self) -> Result<Vec<usize>, NetlistError> {
+        let mut defined: HashMap<&str, usize> = HashMap::new();
+        for (index, assign) in self.assigns.iter().enumerate() {
+            if defined.insert(assign.name.as_str(), index).is_some()
+                || self.inputs.iter().any(|(input, _)| *input == assign.name)
+            {
+                return Err(NetlistError::Redefined {
+                    pos: assign.pos,
+                    name: assign.name.clone(),
+                });
+            }
+        }
+        let is_input: HashMap<&str, ()> = self.inputs.iter().map(|(name, _)| (name.as_str(), ())).collect();
+
+        let mut deps: Vec<Vec<usize>> = Vec::with_capacity(self.assigns.len());
+        for assign in &self.assigns {
+            let mut assign_deps = Vec::new();
+            let mut undefined = None;
+            assign.expr.for_each_var(&mut |name, pos| {
+                if let Some(&index) = defined.get(name) {
+                    assign_deps.push(index);
+                } else if !is_input.contains_key(name) && undefined.is_none() {
+                    undefined = Some((name.to_string(), pos));
+                }
+            });
+            if let Some((name, pos)) = undefined {
+                return Err(NetlistError::Undefined { pos, name });
+            }
+            deps.push(assign_deps);
+        }
+
+        // iterative dfs, circuits can be far deeper than the call stack allows
+        const UNVISITED: u8 = 0;
+        const VISITING: u8 = 1;
+        const DONE: u8 = 2;
+        let mut state = vec![UNVISITED; self.assigns.len()];
+        let mut order = Vec::with_capacity(self.assigns.len());
+        for start in 0..self.assigns.len() {
+            if state[start] != UNVISITED {
+                continue;
+            }
+            let mut stack = vec![(start, 0)];
+            state[start] = VISITING;
+            while let Some((index, next)) = stack.pop() {
+                if next < deps[index].len() {
+                    stack.push((index, next + 1));
+                    let dep = deps[index][next];
+                    match state[dep] {
+                        UNVISITED => {
+                            state[dep] = VISITING;
+                            stack.push((dep, 0));
+                        }
+                        VISITING => {
+                            return Err(NetlistError::Cycle {
+                                pos: self.assigns[dep].pos,
+                                name: self.assigns[dep].name.clone(),
+                            })
+                        }
+                        _ => {}
+                    }
+                } else {
+                    state[index] = DONE;
+                    order.push(index);
+                }
+            }
+        }
+        Ok(order)
+    }
+}
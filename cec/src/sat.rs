@@ -0,0 +1,413 @@
+// A small CDCL SAT solver: two watched literals, first-UIP clause learning, VSIDS with phase
+// saving and geometric restarts. Learnt clauses are never deleted, calls are expected to be
+// bounded with a conflict limit. Gate helpers add the Tseitin clauses of a logic gate.
+use ordered_float::OrderedFloat;
+use std::collections::BinaryHeap;
+use std::ops::Not;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn new(var: usize, positive: bool) -> Lit {
+        Lit((var as u32) << 1 | !positive as u32)
+    }
+
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SatResult {
+    Sat,
+    Unsat,
+    // the conflict limit was reached
+    Unknown,
+}
+
+#[derive(Debug, Default)]
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    // clauses watching each literal, visited when the literal becomes false
+    watches: Vec<Vec<usize>>,
+    assigns: Vec<Option<bool>>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    qhead: usize,
+    activity: Vec<f64>,
+    bump: f64,
+    heap: BinaryHeap<(OrderedFloat<f64>, usize)>,
+    phase: Vec<bool>,
+    seen: Vec<bool>,
+    unsat: bool,
+    // a literal that is always true, made on first use
+    constant: Option<Lit>,
+    pub conflicts: u64,
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver { bump: 1.0, ..Default::default() }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.assigns.len()
+    }
+
+    pub fn new_var(&mut self) -> usize {
+        let var = self.assigns.len();
+        self.assigns.push(None);
+        self.level.push(0);
+        self.reason.push(None);
+        self.activity.push(0.0);
+        self.phase.push(false);
+        self.seen.push(false);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.heap.push((OrderedFloat(0.0), var));
+        var
+    }
+
+    pub fn new_lit(&mut self) -> Lit {
+        Lit::new(self.new_var(), true)
+    }
+
+    // the value of `var` in the model of the last satisfiable call
+    pub fn value(&self, var: usize) -> Option<bool> {
+        self.assigns[var]
+    }
+
+    fn lit_value(&self, lit: Lit) -> Option<bool> {
+        self.assigns[lit.var()].map(|value| value == lit.is_positive())
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.assigns[var] = Some(lit.is_positive());
+        self.level[var] = self.decision_level();
+        self.reason[var] = reason;
+        self.trail.push(lit);
+    }
+
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        if self.unsat {
+            return;
+        }
+        self.backtrack(0);
+        let mut clause: Vec<Lit> = Vec::with_capacity(lits.len());
+        for lit in lits {
+            match self.lit_value(*lit) {
+                Some(true) => return,
+                Some(false) => {}
+                None if clause.contains(&!*lit) => return,
+                None if !clause.contains(lit) => clause.push(*lit),
+                None => {}
+            }
+        }
+        match clause.len() {
+            0 => self.unsat = true,
+            1 => {
+                self.enqueue(clause[0], None);
+                if self.propagate().is_some() {
+                    self.unsat = true;
+                }
+            }
+            _ => {
+                self.watches[(!clause[0]).index()].push(self.clauses.len());
+                self.watches[(!clause[1]).index()].push(self.clauses.len());
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    // unit propagation, returns a conflicting clause
+    fn propagate(&mut self) -> Option<usize> {
+        while self.qhead < self.trail.len() {
+            let lit = self.trail[self.qhead];
+            self.qhead += 1;
+            let false_lit = !lit;
+            let mut watching = std::mem::take(&mut self.watches[lit.index()]);
+            let mut kept = 0;
+            let mut conflict = None;
+            for i in 0..watching.len() {
+                let index = watching[i];
+                if conflict.is_some() {
+                    watching[kept] = index;
+                    kept += 1;
+                    continue;
+                }
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if self.assigns[first.var()] == Some(first.is_positive()) {
+                    watching[kept] = index;
+                    kept += 1;
+                    continue;
+                }
+                let replacement = (2..clause.len()).find(|k| self.assigns[clause[*k].var()] != Some(!clause[*k].is_positive()));
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    let watch = (!clause[1]).index();
+                    self.watches[watch].push(index);
+                    continue;
+                }
+                watching[kept] = index;
+                kept += 1;
+                if self.lit_value(first) == Some(false) {
+                    conflict = Some(index);
+                } else {
+                    self.enqueue(first, Some(index));
+                }
+            }
+            watching.truncate(kept);
+            self.watches[lit.index()] = watching;
+            if conflict.is_some() {
+                self.qhead = self.trail.len();
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn bump_var(&mut self, var: usize) {
+        self.activity[var] += self.bump;
+        if self.activity[var] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.bump *= 1e-100;
+            self.heap = (0..self.num_vars()).filter(|v| self.assigns[*v].is_none()).map(|v| (OrderedFloat(self.activity[v]), v)).collect();
+        } else if self.assigns[var].is_none() {
+            self.heap.push((OrderedFloat(self.activity[var]), var));
+        }
+    }
+
+    // first-UIP learnt clause (asserting literal first) and the level to backtrack to
+    fn analyze(&mut self, mut conflict: usize) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut asserting = None;
+        loop {
+            let lits: Vec<Lit> = self.clauses[conflict].clone();
+            let skip = usize::from(asserting.is_some());
+            for lit in &lits[skip..] {
+                let var = lit.var();
+                if self.seen[var] || self.level[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump_var(var);
+                if self.level[var] == self.decision_level() {
+                    pending += 1;
+                } else {
+                    learnt.push(*lit);
+                }
+            }
+            // the next literal of the current level on the trail
+            let lit = loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break self.trail[index];
+                }
+            };
+            self.seen[lit.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !lit;
+                break;
+            }
+            asserting = Some(lit);
+            conflict = self.reason[lit.var()].expect("only decisions have no reason");
+            // the reason clause has the implied literal first
+            let clause = &mut self.clauses[conflict];
+            if clause[0] != lit {
+                let at = clause.iter().position(|l| *l == lit).unwrap();
+                clause.swap(0, at);
+            }
+        }
+        for lit in &learnt[1..] {
+            self.seen[lit.var()] = false;
+        }
+        // the literal of the highest level after the asserting one is watched too
+        let mut backtrack = 0;
+        if learnt.len() > 1 {
+            let max = (1..learnt.len()).max_by_key(|i| self.level[learnt[*i].var()]).unwrap();
+            learnt.swap(1, max);
+            backtrack = self.level[learnt[1].var()];
+        }
+        self.bump *= 1.0 / 0.95;
+        (learnt, backtrack)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        for lit in self.trail.drain(self.trail_lim[level]..).rev().collect::<Vec<_>>() {
+            let var = lit.var();
+            self.phase[var] = lit.is_positive();
+            self.assigns[var] = None;
+            self.reason[var] = None;
+            self.heap.push((OrderedFloat(self.activity[var]), var));
+        }
+        self.trail_lim.truncate(level);
+        self.qhead = self.trail.len();
+    }
+
+    fn pick_branch(&mut self) -> Option<Lit> {
+        while let Some((_, var)) = self.heap.pop() {
+            if self.assigns[var].is_none() {
+                return Some(Lit::new(var, self.phase[var]));
+            }
+        }
+        None
+    }
+
+    // Solves under the clauses added so far and the assumptions, gives up after `conflict_limit` conflicts
+    pub fn solve(&mut self, assumptions: &[Lit], conflict_limit: Option<u64>) -> SatResult {
+        if self.unsat {
+            return SatResult::Unsat;
+        }
+        self.backtrack(0);
+        let start = self.conflicts;
+        let mut restart = 100u64;
+        let mut since_restart = 0u64;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.conflicts += 1;
+                since_restart += 1;
+                if self.decision_level() == 0 {
+                    self.unsat = true;
+                    return SatResult::Unsat;
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let index = self.clauses.len();
+                    self.watches[(!learnt[0]).index()].push(index);
+                    self.watches[(!learnt[1]).index()].push(index);
+                    self.enqueue(learnt[0], Some(index));
+                    self.clauses.push(learnt);
+                }
+                continue;
+            }
+            if conflict_limit.is_some_and(|limit| self.conflicts - start >= limit) {
+                self.backtrack(0);
+                return SatResult::Unknown;
+            }
+            if since_restart >= restart {
+                since_restart = 0;
+                restart = restart * 3 / 2;
+                self.backtrack(0);
+                continue;
+            }
+            // assumptions are the first decisions
+            let level = self.decision_level();
+            let next = if level < assumptions.len() {
+                let lit = assumptions[level];
+                match self.lit_value(lit) {
+                    Some(true) => {
+                        self.trail_lim.push(self.trail.len());
+                        continue;
+                    }
+                    Some(false) => {
+                        self.backtrack(0);
+                        return SatResult::Unsat;
+                    }
+                    None => lit,
+                }
+            } else {
+                match self.pick_branch() {
+                    Some(lit) => lit,
+                    None => return SatResult::Sat,
+                }
+            };
+            self.trail_lim.push(self.trail.len());
+            self.enqueue(next, None);
+        }
+    }
+
+    pub fn constant(&mut self, value: bool) -> Lit {
+        let lit = match self.constant {
+            Some(lit) => lit,
+            None => {
+                let lit = self.new_lit();
+                self.add_clause(&[lit]);
+                self.constant = Some(lit);
+                lit
+            }
+        };
+        if value {
+            lit
+        } else {
+            !lit
+        }
+    }
+
+    pub fn and(&mut self, a: Lit, b: Lit) -> Lit {
+        let out = self.new_lit();
+        self.add_clause(&[!out, a]);
+        self.add_clause(&[!out, b]);
+        self.add_clause(&[out, !a, !b]);
+        out
+    }
+
+    pub fn or(&mut self, a: Lit, b: Lit) -> Lit {
+        !self.and(!a, !b)
+    }
+
+    pub fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        let out = self.new_lit();
+        self.add_clause(&[!out, a, b]);
+        self.add_clause(&[!out, !a, !b]);
+        self.add_clause(&[out, !a, b]);
+        self.add_clause(&[out, a, !b]);
+        out
+    }
+
+    // `a` when `s` is true, `b` otherwise
+    pub fn mux(&mut self, s: Lit, a: Lit, b: Lit) -> Lit {
+        let out = self.new_lit();
+        self.add_clause(&[!s, !a, out]);
+        self.add_clause(&[!s, a, !out]);
+        self.add_clause(&[s, !b, out]);
+        self.add_clause(&[s, b, !out]);
+        out
+    }
+
+    pub fn maj(&mut self, a: Lit, b: Lit, c: Lit) -> Lit {
+        let out = self.new_lit();
+        for (x, y) in [(a, b), (a, c), (b, c)] {
+            self.add_clause(&[!x, !y, out]);
+            self.add_clause(&[x, y, !out]);
+        }
+        out
+    }
+}
@@ -0,0 +1,290 @@
+// Simulation-based equivalence check of two netlists.
+//
+// Inputs and outputs are matched by name. Both netlists are simulated 64 patterns at a time on
+// every input assignment when there are few inputs, which proves equivalence, and on random
+// patterns otherwise, which can only find a difference. The first output (in the order of the
+// first netlist) that differs is reported with an input assignment that shows it. With a care
+// set, only the assignments where its single output is 1 count.
+use crate::netlist::{Expr, Netlist, NetlistError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use thiserror::Error;
+
+// patterns simulated at once, in words
+const CHUNK: usize = 16;
+
+// word `word` of input `var` when simulating every assignment, assignment `m` is bit `m % 64` of word `m / 64`
+pub fn assignment_word(var: usize, word: usize) -> u64 {
+    if var >= 6 {
+        0u64.wrapping_sub((word >> (var - 6) & 1) as u64)
+    } else {
+        (0..64).filter(|bit| bit >> var & 1 == 1).fold(0u64, |w, bit| w | 1 << bit)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CecError {
+    #[error("output `{0}` of the first netlist is not an output of the second")]
+    MissingInSecond(String),
+    #[error("output `{0}` of the second netlist is not an output of the first")]
+    MissingInFirst(String),
+    #[error("a care set has one output, it has {0}")]
+    CareOutputs(usize),
+    #[error(transparent)]
+    Netlist(#[from] NetlistError),
+}
+
+pub struct SimConfig {
+    // random patterns, in words of 64
+    pub words: usize,
+    // netlists with at most this many inputs are simulated on every assignment
+    pub exhaustive_inputs: usize,
+    pub seed: u64,
+    // outputs may differ where the single output of this netlist is 0
+    pub care: Option<Netlist>,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            words: 64,
+            exhaustive_inputs: 16,
+            seed: 0,
+            care: None,
+        }
+    }
+}
+
+// the care set of `config`, checked to have one output
+pub(crate) fn care_set(config: &SimConfig) -> Result<Option<&Netlist>, CecError> {
+    match &config.care {
+        Some(care) if care.outputs.len() != 1 => Err(CecError::CareOutputs(care.outputs.len())),
+        care => Ok(care.as_ref()),
+    }
+}
+
+// An input assignment where an output of the two netlists differs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub output: String,
+    // every input, those of the first netlist first and those of the care set last
+    pub inputs: Vec<(String, bool)>,
+    pub first: bool,
+    pub second: bool,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inputs: Vec<String> = self.inputs.iter().map(|(name, value)| format!("{}={}", name, *value as u8)).collect();
+        write!(f, "output `{}` is {} in the first netlist and {} in the second for {}", self.output, self.first as u8, self.second as u8, inputs.join(" "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimReport {
+    // `None` if no simulated pattern tells the netlists apart
+    pub counterexample: Option<Counterexample>,
+    // every input assignment was simulated, so `None` proves equivalence
+    pub exhaustive: bool,
+    pub patterns: u64,
+    pub outputs: usize,
+    // inputs of one netlist only, the other netlist does not depend on them
+    pub only_in_first: Vec<String>,
+    pub only_in_second: Vec<String>,
+}
+
+// A netlist in simulation order
+struct Simulator<'a> {
+    netlist: &'a Netlist,
+    order: Vec<usize>,
+}
+
+impl<'a> Simulator<'a> {
+    fn new(netlist: &'a Netlist) -> Result<Simulator<'a>, NetlistError> {
+        Ok(Simulator { netlist, order: netlist.topo_order()? })
+    }
+
+    // the words of every output, given the words of every input
+    fn run(&self, inputs: &HashMap<&str, Vec<u64>>, words: usize) -> Vec<Vec<u64>> {
+        let mut values: HashMap<&str, Vec<u64>> = HashMap::with_capacity(self.order.len());
+        for index in &self.order {
+            let assign = &self.netlist.assigns[*index];
+            let value = (0..words).map(|word| eval(&assign.expr, &|name| values.get(name).or_else(|| inputs.get(name)).map(|words| words[word]))).collect();
+            values.insert(assign.name.as_str(), value);
+        }
+        self.netlist
+            .outputs
+            .iter()
+            .map(|(name, _)| values.get(name.as_str()).or_else(|| inputs.get(name.as_str())).cloned().expect("outputs are defined"))
+            .collect()
+    }
+}
+
+fn eval(expr: &Expr, value: &impl Fn(&str) -> Option<u64>) -> u64 {
+    match expr {
+        Expr::Const(c) => 0u64.wrapping_sub(*c as u64),
+        Expr::Var(name, _) => value(name).expect("signals are defined before they are used"),
+        Expr::Not(e) => !eval(e, value),
+        Expr::And(es) => es.iter().fold(u64::MAX, |acc, e| acc & eval(e, value)),
+        Expr::Or(es) => es.iter().fold(0, |acc, e| acc | eval(e, value)),
+        Expr::Xor(es) => es.iter().fold(0, |acc, e| acc ^ eval(e, value)),
+        Expr::Mux(es) => {
+            let [s, a, b] = es.as_ref();
+            let s = eval(s, value);
+            (s & eval(a, value)) | (!s & eval(b, value))
+        }
+        Expr::Maj(es) => {
+            let [a, b, c] = es.as_ref();
+            let (a, b, c) = (eval(a, value), eval(b, value), eval(c, value));
+            (a & b) | (c & (a | b))
+        }
+    }
+}
+
+// How the inputs and outputs of two netlists correspond, by name
+pub(crate) struct Matching<'a> {
+    // the inputs of both netlists, those of the first netlist first
+    pub inputs: Vec<&'a str>,
+    // the index in the second netlist of every output of the first
+    pub second_outputs: Vec<usize>,
+    pub only_in_first: Vec<String>,
+    pub only_in_second: Vec<String>,
+}
+
+impl<'a> Matching<'a> {
+    pub fn new(first: &'a Netlist, second: &'a Netlist) -> Result<Matching<'a>, CecError> {
+        let first_outputs: HashMap<&str, usize> = first.outputs.iter().enumerate().map(|(i, (name, _))| (name.as_str(), i)).collect();
+        let second_outputs: HashMap<&str, usize> = second.outputs.iter().enumerate().map(|(i, (name, _))| (name.as_str(), i)).collect();
+        if let Some((name, _)) = first.outputs.iter().find(|(name, _)| !second_outputs.contains_key(name.as_str())) {
+            return Err(CecError::MissingInSecond(name.clone()));
+        }
+        if let Some((name, _)) = second.outputs.iter().find(|(name, _)| !first_outputs.contains_key(name.as_str())) {
+            return Err(CecError::MissingInFirst(name.clone()));
+        }
+
+        let first_inputs: HashSet<&str> = first.inputs.iter().map(|(name, _)| name.as_str()).collect();
+        let second_inputs: HashSet<&str> = second.inputs.iter().map(|(name, _)| name.as_str()).collect();
+        let mut inputs: Vec<&str> = first.inputs.iter().map(|(name, _)| name.as_str()).collect();
+        inputs.extend(second.inputs.iter().map(|(name, _)| name.as_str()).filter(|name| !first_inputs.contains(name)));
+        Ok(Matching {
+            second_outputs: first.outputs.iter().map(|(name, _)| second_outputs[name.as_str()]).collect(),
+            only_in_first: inputs.iter().filter(|name| !second_inputs.contains(*name)).map(|name| name.to_string()).collect(),
+            only_in_second: inputs.iter().filter(|name| !first_inputs.contains(*name)).map(|name| name.to_string()).collect(),
+            inputs,
+        })
+    }
+}
+
+// Simulates both netlists on the same patterns until an output differs
+pub fn simulate_equivalence(first: &Netlist, second: &Netlist, config: &SimConfig) -> Result<SimReport, CecError> {
+    let care = care_set(config)?;
+    let Matching { inputs: mut names, second_outputs, only_in_first, only_in_second } = Matching::new(first, second)?;
+    for (name, _) in care.iter().flat_map(|care| &care.inputs) {
+        if !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+    let mut report = SimReport {
+        counterexample: None,
+        exhaustive: names.len() <= config.exhaustive_inputs,
+        patterns: 0,
+        outputs: first.outputs.len(),
+        only_in_first,
+        only_in_second,
+    };
+    let total_words = if report.exhaustive { (1usize << names.len()).div_ceil(64) } else { config.words.max(1) };
+    // the patterns of the last word beyond the assignments are not simulated
+    let last_mask = if report.exhaustive && names.len() < 6 { (1u64 << (1 << names.len())) - 1 } else { u64::MAX };
+
+    let first_sim = Simulator::new(first)?;
+    let second_sim = Simulator::new(second)?;
+    let care_sim = care.map(Simulator::new).transpose()?;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    for start in (0..total_words).step_by(CHUNK) {
+        let words = CHUNK.min(total_words - start);
+        let inputs: HashMap<&str, Vec<u64>> = names
+            .iter()
+            .enumerate()
+            .map(|(var, name)| {
+                let words = (start..start + words).map(|word| if report.exhaustive { assignment_word(var, word) } else { rng.gen() }).collect();
+                (*name, words)
+            })
+            .collect();
+        let first_values = first_sim.run(&inputs, words);
+        let second_values = second_sim.run(&inputs, words);
+        let care_values = care_sim.as_ref().map(|care| care.run(&inputs, words).remove(0));
+        report.patterns += if report.exhaustive && names.len() < 6 { 1 << names.len() } else { 64 * words as u64 };
+        for (output, (name, _)) in first.outputs.iter().enumerate() {
+            let (a, b) = (&first_values[output], &second_values[second_outputs[output]]);
+            let differ = (0..words).find_map(|word| {
+                let care = care_values.as_ref().map_or(u64::MAX, |care| care[word]);
+                let diff = (a[word] ^ b[word]) & care & last_mask;
+                (diff != 0).then(|| (word, diff.trailing_zeros()))
+            });
+            if let Some((word, bit)) = differ {
+                let value = |words: &[u64]| words[word] >> bit & 1 == 1;
+                report.counterexample = Some(Counterexample {
+                    output: name.clone(),
+                    inputs: names.iter().map(|name| (name.to_string(), value(&inputs[name]))).collect(),
+                    first: value(a),
+                    second: value(b),
+                });
+                return Ok(report);
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eqn_parser::parse_eqn;
+
+    fn simulate(first: &str, second: &str, config: &SimConfig) -> Result<SimReport, CecError> {
+        simulate_equivalence(&parse_eqn(first).unwrap(), &parse_eqn(second).unwrap(), config)
+    }
+
+    #[test]
+    fn the_counterexample_shows_the_difference() {
+        // differs only for a = b = c = 1
+        let report = simulate("INORDER = a b c;\nOUTORDER = f g;\nf = a * b;\ng = a;\n", "INORDER = c b a;\nOUTORDER = g f;\nf = a * b * !c;\ng = a;\n", &SimConfig::default()).unwrap();
+        assert!(report.exhaustive);
+        assert_eq!((report.outputs, report.patterns), (2, 8));
+        let counterexample = report.counterexample.expect("f differs");
+        assert_eq!(counterexample.output, "f");
+        assert_eq!((counterexample.first, counterexample.second), (true, false));
+        assert!(counterexample.inputs.iter().all(|(_, value)| *value), "{}", counterexample);
+    }
+
+    #[test]
+    fn inputs_of_one_netlist_only_are_reported() {
+        let report = simulate("INORDER = a b;\nOUTORDER = f;\nf = a + a * b;\n", "INORDER = a c;\nOUTORDER = f;\nf = a;\n", &SimConfig::default()).unwrap();
+        assert!(report.counterexample.is_none());
+        assert_eq!((report.only_in_first.as_slice(), report.only_in_second.as_slice()), (&["b".to_string()][..], &["c".to_string()][..]));
+        // random patterns are not a proof
+        let report = simulate("INORDER = a;\nOUTORDER = f;\nf = a;\n", "INORDER = a;\nOUTORDER = f;\nf = !(!a);\n", &SimConfig { exhaustive_inputs: 0, words: 4, seed: 1, care: None }).unwrap();
+        assert!(!report.exhaustive && report.counterexample.is_none());
+        assert_eq!(report.patterns, 256);
+        assert!(matches!(simulate("INORDER = a;\nOUTORDER = f;\nf = a;\n", "INORDER = a;\nOUTORDER = g;\ng = a;\n", &SimConfig::default()), Err(CecError::MissingInSecond(name)) if name == "f"));
+    }
+
+    #[test]
+    fn differences_outside_the_care_set_are_ignored() {
+        // `f` differs for `a = 0, b = 1` only, outside of the care set `a * c`
+        let first = "INORDER = a b; OUTORDER = f g; f = a * b; g = a;";
+        let care = parse_eqn("INORDER = a c; OUTORDER = care; care = a * c;").unwrap();
+        let config = SimConfig { care: Some(care), ..Default::default() };
+        let report = simulate(first, "INORDER = a b; OUTORDER = f g; f = b; g = a;", &config).unwrap();
+        assert!(report.exhaustive && report.counterexample.is_none());
+        assert_eq!(report.patterns, 8);
+
+        // `g` differs for `a = b = 1`, and `c` = 1 puts it in the care set
+        let report = simulate(first, "INORDER = a b; OUTORDER = f g; f = b; g = a * !b;", &config).unwrap();
+        let counterexample = report.counterexample.expect("`g` differs on the care set");
+        assert_eq!(counterexample.output, "g");
+        assert_eq!(counterexample.inputs, [("a".to_string(), true), ("b".to_string(), true), ("c".to_string(), true)]);
+    }
+}
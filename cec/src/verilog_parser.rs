@@ -0,0 +1,536 @@
+// Parser for gate-level structural Verilog.
+//
+// Only the first module of the file is read. Supported inside the module:
+//   input/output/wire declarations, scalar or with a `[msb:lsb]` range (expanded to one
+//   signal per bit, named `x[i]`), `wire w = expr;` declarations,
+//   `assign lhs = expr;` with the operators `~ ! & | ^ ~^ ^~ ?:`, parentheses and the
+//   constants `0`, `1`, `1'b0`, `1'b1`,
+//   gate primitives `and or nand nor xor xnor` (output first) and `not buf`
+//   (outputs first, input last), with or without an instance name.
+// Line (`//`) and block (`/* */`) comments are skipped, escaped identifiers (`\a[0] `)
+// keep their name without the backslash.
+use crate::netlist::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Punct(&'static str),
+    Eof,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("`{}`", name),
+            Token::Number(number) => format!("`{}`", number),
+            Token::Punct(punct) => format!("`{}`", punct),
+            Token::Eof => "end of file".to_string(),
+        }
+    }
+}
+
+const PUNCTS: [&str; 18] = ["~^", "^~", "(", ")", ",", ";", "[", "]", ":", "=", "~", "!", "&", "|", "^", ".", "#", "?"];
+
+fn tokenize(src: &str) -> Result<Vec<(Token, Pos)>, NetlistError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = src.chars().collect();
+    let (mut i, mut line, mut col) = (0, 1, 1);
+    // advance over `n` characters, keeping track of the position
+    let advance = |i: &mut usize, line: &mut usize, col: &mut usize, n: usize| {
+        for _ in 0..n {
+            if chars[*i] == '\n' {
+                *line += 1;
+                *col = 1;
+            } else {
+                *col += 1;
+            }
+            *i += 1;
+        }
+    };
+    while i < chars.len() {
+        let pos = Pos { line, col };
+        let c = chars[i];
+        let rest: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        if c.is_whitespace() {
+            advance(&mut i, &mut line, &mut col, 1);
+        } else if rest == "//" {
+            while i < chars.len() && chars[i] != '\n' {
+                advance(&mut i, &mut line, &mut col, 1);
+            }
+        } else if rest == "/*" {
+            advance(&mut i, &mut line, &mut col, 2);
+            loop {
+                if i + 1 >= chars.len() {
+                    return Err(NetlistError::Syntax {
+                        pos,
+                        msg: "unterminated comment".to_string(),
+                    });
+                }
+                if chars[i] == '*' && chars[i + 1] == '/' {
+                    advance(&mut i, &mut line, &mut col, 2);
+                    break;
+                }
+                advance(&mut i, &mut line, &mut col, 1);
+            }
+        } else if c == '\\' {
+            let start = i + 1;
+            let mut end = start;
+            while end < chars.len() && !chars[end].is_whitespace() {
+                end += 1;
+            }
+            tokens.push((Token::Ident(chars[start..end].iter().collect()), pos));
+            let n = end - i;
+            advance(&mut i, &mut line, &mut col, n);
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = i;
+            while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_' || chars[end] == '$') {
+                end += 1;
+            }
+            tokens.push((Token::Ident(chars[i..end].iter().collect()), pos));
+            let n = end - i;
+            advance(&mut i, &mut line, &mut col, n);
+        } else if c.is_ascii_digit() || c == '\'' {
+            // plain and sized numbers such as `3`, `1'b0` or `'h1`
+            let mut end = i;
+            while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '\'' || chars[end] == '_') {
+                end += 1;
+            }
+            tokens.push((Token::Number(chars[i..end].iter().collect()), pos));
+            let n = end - i;
+            advance(&mut i, &mut line, &mut col, n);
+        } else if let Some(punct) = PUNCTS.iter().find(|punct| chars[i..].starts_with(&punct.chars().collect::<Vec<_>>())) {
+            tokens.push((Token::Punct(punct), pos));
+            advance(&mut i, &mut line, &mut col, punct.len());
+        } else {
+            return Err(NetlistError::Syntax {
+                pos,
+                msg: format!("unexpected character `{}`", c),
+            });
+        }
+    }
+    tokens.push((Token::Eof, Pos { line, col }));
+    Ok(tokens)
+}
+
+// value of a one-bit constant, `None` for anything wider or not a constant
+fn constant_value(number: &str) -> Option<bool> {
+    let digits = match number.split_once('\'') {
+        Some((width, value)) => {
+            if !(width.is_empty() || width == "1") {
+                return None;
+            }
+            value.trim_start_matches(['b', 'B', 'd', 'D', 'h', 'H', 'o', 'O'])
+        }
+        None => number,
+    };
+    match digits {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, Pos)>,
+    next: usize,
+    // declared vectors: name -> (msb, lsb)
+    vectors: HashMap<String, (i64, i64)>,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn pos(&self) -> Pos {
+        self.tokens[self.next].1
+    }
+
+    fn bump(&mut self) -> (Token, Pos) {
+        let token = self.tokens[self.next].clone();
+        if token.0 != Token::Eof {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, NetlistError> {
+        Err(NetlistError::Syntax {
+            pos: self.pos(),
+            msg: format!("expected {}, found {}", expected, self.peek().describe()),
+        })
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Token::Punct(p) if *p == punct)
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<Pos, NetlistError> {
+        if self.is_punct(punct) {
+            Ok(self.bump().1)
+        } else {
+            self.error(&format!("`{}`", punct))
+        }
+    }
+
+    fn ident(&mut self) -> Result<(String, Pos), NetlistError> {
+        match self.peek().clone() {
+            Token::Ident(name) => {
+                let pos = self.bump().1;
+                Ok((name, pos))
+            }
+            _ => self.error("an identifier"),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64, NetlistError> {
+        match self.peek().clone() {
+            Token::Number(number) => match number.parse() {
+                Ok(value) => {
+                    self.bump();
+                    Ok(value)
+                }
+                Err(_) => self.error("an integer"),
+            },
+            _ => self.error("an integer"),
+        }
+    }
+
+    // optional `[msb:lsb]` in a declaration
+    fn range(&mut self) -> Result<Option<(i64, i64)>, NetlistError> {
+        if !self.is_punct("[") {
+            return Ok(None);
+        }
+        self.bump();
+        let msb = self.integer()?;
+        self.expect(":")?;
+        let lsb = self.integer()?;
+        self.expect("]")?;
+        Ok(Some((msb, lsb)))
+    }
+
+    // a scalar signal or a single bit of a vector, as a flat signal name
+    fn signal(&mut self) -> Result<(String, Pos), NetlistError> {
+        let (name, pos) = self.ident()?;
+        if self.is_punct("[") {
+            self.bump();
+            let bit = self.integer()?;
+            self.expect("]")?;
+            return Ok((format!("{}[{}]", name, bit), pos));
+        }
+        if self.vectors.contains_key(&name) {
+            return Err(NetlistError::Syntax {
+                pos,
+                msg: format!("vector `{}` must be used one bit at a time", name),
+            });
+        }
+        Ok((name, pos))
+    }
+
+    // names declared by one `input`/`output`/`wire` statement, vectors expanded msb first
+    fn declared_names(&mut self, names: &mut Vec<(String, Pos)>, range: Option<(i64, i64)>) -> Result<(), NetlistError> {
+        let (name, pos) = self.ident()?;
+        match range {
+            None => names.push((name, pos)),
+            Some((msb, lsb)) => {
+                let bits: Vec<i64> = if msb >= lsb { (lsb..=msb).rev().collect() } else { (msb..=lsb).collect() };
+                names.extend(bits.into_iter().map(|bit| (format!("{}[{}]", name, bit), pos)));
+                self.vectors.insert(name, (msb, lsb));
+            }
+        }
+        Ok(())
+    }
+
+    fn module(&mut self) -> Result<Netlist, NetlistError> {
+        let mut netlist = Netlist::default();
+        match self.ident()? {
+            (keyword, _) if keyword == "module" => {}
+            (_, pos) => {
+                return Err(NetlistError::Syntax {
+                    pos,
+                    msg: "expected `module`".to_string(),
+                })
+            }
+        }
+        self.ident()?;
+        // the port list only repeats names; the directions come from the declarations.
+        // ANSI-style headers (`input a, output y`) declare the ports right here.
+        if self.is_punct("(") {
+            self.bump();
+            let mut direction: Option<String> = None;
+            let mut range = None;
+            while !self.is_punct(")") {
+                if let Token::Ident(word) = self.peek().clone() {
+                    if matches!(word.as_str(), "input" | "output") {
+                        self.bump();
+                        if let Token::Ident(kind) = self.peek() {
+                            if kind == "wire" {
+                                self.bump();
+                            }
+                        }
+                        direction = Some(word);
+                        range = self.range()?;
+                    }
+                }
+                match direction.as_deref() {
+                    Some("input") => self.declared_names(&mut netlist.inputs, range)?,
+                    Some(_) => self.declared_names(&mut netlist.outputs, range)?,
+                    None => {
+                        self.ident()?;
+                    }
+                }
+                if !self.is_punct(",") {
+                    break;
+                }
+                self.bump();
+            }
+            self.expect(")")?;
+        }
+        self.expect(";")?;
+
+        loop {
+            let (keyword, pos) = match self.peek().clone() {
+                Token::Ident(keyword) => (keyword, self.pos()),
+                Token::Eof => return self.error("`endmodule`"),
+                _ => return self.error("a declaration, `assign` or a gate"),
+            };
+            self.bump();
+            match keyword.as_str() {
+                "endmodule" => return Ok(netlist),
+                "input" | "output" | "wire" => {
+                    let range = self.range()?;
+                    loop {
+                        let mut names = Vec::new();
+                        self.declared_names(&mut names, range)?;
+                        if keyword == "wire" && self.is_punct("=") {
+                            self.bump();
+                            let (name, pos) = names.pop().unwrap();
+                            let expr = self.expr()?;
+                            netlist.assigns.push(Assign { name, pos, expr });
+                        }
+                        match keyword.as_str() {
+                            "input" => netlist.inputs.extend(names),
+                            "output" => netlist.outputs.extend(names),
+                            _ => {}
+                        }
+                        if !self.is_punct(",") {
+                            break;
+                        }
+                        self.bump();
+                    }
+                    self.expect(";")?;
+                }
+                "assign" => {
+                    loop {
+                        let (name, pos) = self.signal()?;
+                        self.expect("=")?;
+                        let expr = self.expr()?;
+                        netlist.assigns.push(Assign { name, pos, expr });
+                        if !self.is_punct(",") {
+                            break;
+                        }
+                        self.bump();
+                    }
+                    self.expect(";")?;
+                }
+                "and" | "or" | "nand" | "nor" | "xor" | "xnor" | "not" | "buf" => {
+                    loop {
+                        self.gate(&keyword, &mut netlist)?;
+                        if !self.is_punct(",") {
+                            break;
+                        }
+                        self.bump();
+                    }
+                    self.expect(";")?;
+                }
+                _ => {
+                    return Err(NetlistError::Syntax {
+                        pos,
+                        msg: format!("unsupported construct or module instance `{}`", keyword),
+                    })
+                }
+            }
+        }
+    }
+
+    // one gate instance: `[name] (out, in, ...)`
+    fn gate(&mut self, kind: &str, netlist: &mut Netlist) -> Result<(), NetlistError> {
+        if let Token::Ident(_) = self.peek() {
+            self.bump();
+        }
+        let open = self.expect("(")?;
+        let mut terminals = vec![self.signal_or_constant()?];
+        while self.is_punct(",") {
+            self.bump();
+            terminals.push(self.signal_or_constant()?);
+        }
+        self.expect(")")?;
+        if terminals.len() < 2 {
+            return Err(NetlistError::Syntax {
+                pos: open,
+                msg: format!("`{}` gate needs an output and at least one input", kind),
+            });
+        }
+
+        let output_name = |terminal: &Expr| match terminal {
+            Expr::Var(name, pos) => Ok((name.clone(), *pos)),
+            _ => Err(NetlistError::Syntax {
+                pos: open,
+                msg: "gate output must be a signal".to_string(),
+            }),
+        };
+        if kind == "not" || kind == "buf" {
+            let input = terminals.pop().unwrap();
+            for terminal in &terminals {
+                let (name, pos) = output_name(terminal)?;
+                let expr = if kind == "not" { Expr::Not(Box::new(input.clone())) } else { input.clone() };
+                netlist.assigns.push(Assign { name, pos, expr });
+            }
+            return Ok(());
+        }
+
+        let (name, pos) = output_name(&terminals[0])?;
+        let inputs = terminals[1..].to_vec();
+        let expr = match kind {
+            "and" => Expr::And(inputs),
+            "or" => Expr::Or(inputs),
+            "nand" => Expr::Not(Box::new(Expr::And(inputs))),
+            "nor" => Expr::Not(Box::new(Expr::Or(inputs))),
+            "xor" => xor(inputs),
+            _ => Expr::Not(Box::new(xor(inputs))),
+        };
+        netlist.assigns.push(Assign { name, pos, expr });
+        Ok(())
+    }
+
+    fn signal_or_constant(&mut self) -> Result<Expr, NetlistError> {
+        if let Token::Number(number) = self.peek().clone() {
+            return match constant_value(&number) {
+                Some(value) => {
+                    self.bump();
+                    Ok(Expr::Const(value))
+                }
+                None => self.error("a one-bit constant"),
+            };
+        }
+        let (name, pos) = self.signal()?;
+        Ok(Expr::Var(name, pos))
+    }
+
+    // precedence from low to high: ?: | ^ & unary
+    fn expr(&mut self) -> Result<Expr, NetlistError> {
+        let select = self.or_expr()?;
+        if !self.is_punct("?") {
+            return Ok(select);
+        }
+        self.bump();
+        let then = self.expr()?;
+        self.expect(":")?;
+        let otherwise = self.expr()?;
+        Ok(Expr::Mux(Box::new([select, then, otherwise])))
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, NetlistError> {
+        let mut operands = vec![self.xor_expr()?];
+        while self.is_punct("|") {
+            self.bump();
+            operands.push(self.xor_expr()?);
+        }
+        Ok(if operands.len() == 1 { operands.pop().unwrap() } else { Expr::Or(operands) })
+    }
+
+    fn xor_expr(&mut self) -> Result<Expr, NetlistError> {
+        let mut expr = self.and_expr()?;
+        loop {
+            let negate = if self.is_punct("^") {
+                false
+            } else if self.is_punct("~^") || self.is_punct("^~") {
+                true
+            } else {
+                return Ok(expr);
+            };
+            self.bump();
+            let rhs = self.and_expr()?;
+            expr = xor(vec![expr, rhs]);
+            if negate {
+                expr = Expr::Not(Box::new(expr));
+            }
+        }
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, NetlistError> {
+        let mut operands = vec![self.unary()?];
+        while self.is_punct("&") {
+            self.bump();
+            operands.push(self.unary()?);
+        }
+        Ok(if operands.len() == 1 { operands.pop().unwrap() } else { Expr::And(operands) })
+    }
+
+    fn unary(&mut self) -> Result<Expr, NetlistError> {
+        if self.is_punct("~") || self.is_punct("!") {
+            self.bump();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.is_punct("(") {
+            self.bump();
+            let expr = self.expr()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        self.signal_or_constant()
+    }
+}
+
+// exclusive or of several operands (xor of two or more operands is n-ary, like and/or)
+fn xor(mut operands: Vec<Expr>) -> Expr {
+    if operands.len() == 1 {
+        operands.pop().unwrap()
+    } else {
+        Expr::Xor(operands)
+    }
+}
+
+// Parses the first module of a structural Verilog file into a netlist
+pub fn parse_verilog(src: &str) -> Result<Netlist, NetlistError> {
+    let tokens = tokenize(src)?;
+    Parser {
+        tokens,
+        next: 0,
+        vectors: HashMap::new(),
+    }
+    .module()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(src: &str) -> NetlistError {
+        match parse_verilog(src).and_then(|netlist| netlist.topo_order()) {
+            Err(err) => err,
+            Ok(_) => panic!("expected an error in {:?}", src),
+        }
+    }
+
+    #[test]
+    fn buses_wires_and_gate_primitives_are_read() {
+        let netlist = parse_verilog("module m(a, b, s, f, g);\n  input [1:0] a;\n  input b, s;\n  output f, g;\n  wire w = s ? a[1] : b; // mux\n  nand n0 (g, a[0], w);\n  assign f = a[0] ~^ b;\nendmodule\n").unwrap();
+        let names = |list: &[(String, Pos)]| list.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&netlist.inputs), ["a[1]", "a[0]", "b", "s"]);
+        assert_eq!(names(&netlist.outputs), ["f", "g"]);
+        assert_eq!(netlist.topo_order().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn errors_report_line_and_column() {
+        let module = |body: &str| format!("module m(a, f);\n  input a;\n  output f;\n{}endmodule\n", body);
+        assert!(matches!(error(&module("  assign f = a & ;\n")), NetlistError::Syntax { pos: Pos { line: 4, col: 18 }, .. }));
+        assert!(matches!(error(&module("  assign f = a @ a;\n")), NetlistError::Syntax { pos: Pos { line: 4, col: 16 }, .. }));
+        assert!(matches!(error(&module("  /* never closed\n")), NetlistError::Syntax { pos: Pos { line: 4, col: 3 }, .. }));
+        assert!(matches!(error(&module("  assign f = a & q;\n")), NetlistError::Undefined { pos: Pos { line: 4, col: 18 }, name } if name == "q"));
+        assert!(matches!(error(&module("  assign f = a;\n  assign f = ~a;\n")), NetlistError::Redefined { pos: Pos { line: 5, col: 10 }, name } if name == "f"));
+    }
+}
@@ -1,3 +1,4 @@
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use egg::*;
 use egraph_serialize::EGraph as SerializedEGraph;
 use rand::random;
@@ -15,15 +16,18 @@ use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
 use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
+use std::sync::Arc;
 use std::time::Instant;
 mod utils;
 use crate::utils::cost::*;
 use crate::utils::random_gen;
-use crate::utils::runner_modified;
+use crate::utils::runner_modified::{Runner, SimpleScheduler};
 use serde_json::Value;
 use std::collections::BTreeMap;
 use std::path::PathBuf;
-use utils::{extract_new::*, language::*, preprocess::*};
+use cec::check::{check_equivalence, CecConfig, Method, Verdict as CecVerdict};
+use cec::sim::{simulate_equivalence, SimConfig};
+use utils::{extract_new::*, cuts::{cut_rewrite, CutConfig}, dont_care::{dont_care_rewrite, CareSet, DontCareConfig}, fraig::{fraig, FraigConfig}, greedy::{greedy_rewrite, GreedyCost}, qor::{Abc, CostModel, DepthCost, SizeCost}, language::*, netlist::Latch, partition::{partition, rewrite_partitioned, Partition, WindowCost}, preprocess::*, proof::*, rule_file::{build_rewrites, load_rules}, schedule::*, soundness::*, synthesis::*};
 
 use log::LevelFilter;
 
@@ -34,25 +38,27 @@ use log::LevelFilter;
 //     );
 // }
 
-fn save_egraph_to_json(egraph: &EGraph<Prop, ()>, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+fn save_egraph_to_json(egraph: &PropEGraph, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
     let json_rep = serde_json::to_string_pretty(&egraph).unwrap();
     fs::write(&file_path, json_rep)?;
     Ok(())
 }
 
-fn save_serialized_egraph_to_json(serialized_egraph: &SerializedEGraph, file_path: &PathBuf, root_ids: &[usize]) -> Result<(), Box<dyn std::error::Error>> {
-    let file = File::create(&file_path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &serialized_egraph)?;
-
-    let root_eclasses_value: serde_json::Value = root_ids
+// Record the primary outputs, inputs and cut latches of the circuit next to the egraph:
+// `root_eclasses[i]` is the class of output `root_names[i]`
+fn set_circuit_metadata(json_data: &mut serde_json::Value, root_ids: &[usize], root_names: &[String], input_names: &[String], latches: &[Latch]) {
+    json_data["root_eclasses"] = root_ids
         .iter()
         .map(|id| serde_json::Value::String(id.to_string()))
         .collect();
+    json_data["root_names"] = json!(root_names);
+    json_data["input_names"] = json!(input_names);
+    json_data["latches"] = json!(latches);
+}
 
-    let json_string = std::fs::read_to_string(&file_path)?;
-    let mut json_data: serde_json::Value = serde_json::from_str(&json_string)?;
-    json_data["root_eclasses"] = root_eclasses_value;
+fn save_serialized_egraph_to_json(serialized_egraph: &SerializedEGraph, file_path: &PathBuf, root_ids: &[usize], root_names: &[String], input_names: &[String], latches: &[Latch]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut json_data = serde_json::to_value(serialized_egraph)?;
+    set_circuit_metadata(&mut json_data, root_ids, root_names, input_names, latches);
 
     let file = File::create(&file_path)?;
     let writer = BufWriter::new(file);
@@ -61,131 +67,915 @@ fn save_serialized_egraph_to_json(serialized_egraph: &SerializedEGraph, file_pat
     Ok(())
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Ruleset {
+    /// make_rules
+    Full,
+    /// make_rules_simplify
+    Simplify,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Scheduler {
+    /// every rule is applied to all of its matches in every iteration
+    Simple,
+    /// rules with too many matches are banned for a while
+    Backoff,
+    /// backoff, and rules of a higher tier only run once the lower tiers stop changing the egraph
+    Tiered,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Evaluator {
+    /// the local cost model, wight_depth for delay and wight_size for area
+    CostModel,
+    /// ABC on the extracted circuit, stime with --abc-lib or the AIG levels and nodes
+    Abc,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum GreedyMetric {
+    /// wight_size op costs of the rewritten nodes
+    Size,
+    /// wight_depth arrival time of the rewritten node
+    Depth,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum PlateauMetric {
+    /// area of the DAG extracted with wight_size
+    Size,
+    /// slowest output extracted with wight_depth
+    Depth,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum WindowMetric {
+    /// tree size with wight_size op costs
+    Size,
+    /// wight_depth arrival time
+    Depth,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Artifact {
+    /// the input egraph in egg's format (eqn2egraph.json)
+    InputEgraph,
+    /// the serialized input egraph (egraph2egraph_serd.json)
+    InputSerd,
+    /// the rewritten egraph in egg's format (rewritten_egraph_internal.json)
+    RewrittenEgraph,
+    /// the serialized rewritten egraph (rewritten_egraph_internal_serd.json)
+    RewrittenSerd,
+    /// the serialized rewritten egraph with operator costs, the extraction-gym input (rewritten_egraph_with_weight_cost_serd.json)
+    Weighted,
+    /// the runner report with per-iteration and per-rule statistics (runner_report.json)
+    Report,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "e-rewriter")]
+#[command(version)]
+#[command(about = "Rewrite a circuit with equality saturation", long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(value_name = "FILE", required = true, help = "Input circuit (eqn, BLIF with .blif, AIGER with .aag/.aig, structural Verilog with .v)")]
+    input: Option<String>,
+    #[arg(short, long, value_name = "DIR", default_value = "rewritten_circuit", help = "Directory the egraph files are written to")]
+    output_dir: PathBuf,
+    #[arg(short = 'i', long, default_value_t = 10, help = "Maximum number of runner iterations")]
+    iter_limit: usize,
+    #[arg(short, long, value_name = "SECONDS", default_value_t = 10.0, help = "Runner time limit in seconds")]
+    time_limit: f64,
+    #[arg(short, long, default_value_t = 200000000, help = "Maximum number of e-nodes")]
+    node_limit: usize,
+    #[arg(short, long, value_enum, default_value_t = Ruleset::Full, help = "Rewrite rules to run")]
+    ruleset: Ruleset,
+    #[arg(long, value_name = "FILE", conflicts_with = "ruleset", help = "Rule file (text, or JSON with a .json extension) to run instead of a built-in rule set")]
+    rules: Option<String>,
+    #[arg(short, long, value_enum, default_value_t = Scheduler::Backoff, help = "Rewrite scheduler")]
+    scheduler: Scheduler,
+    #[arg(long, help = "Initial match limit of the backoff scheduler (default: 1000)")]
+    match_limit: Option<usize>,
+    #[arg(long, help = "Initial ban length (in iterations) of the backoff scheduler (default: 5)")]
+    ban_length: Option<usize>,
+    #[arg(long, value_name = "FILE", help = "JSON file with per-rule match limits, ban lengths and tiers")]
+    schedule: Option<String>,
+    #[arg(long, value_name = "RULE=N", help = "Initial match limit of a rule, `name*` for every rule starting with name")]
+    rule_match_limit: Vec<String>,
+    #[arg(long, value_name = "RULE=N", help = "Initial ban length of a rule")]
+    rule_ban_length: Vec<String>,
+    #[arg(long, value_name = "RULE=N", help = "Tier of a rule for the tiered scheduler, lower tiers run first")]
+    rule_tier: Vec<String>,
+    #[arg(long, value_enum, value_name = "COST", help = "Before saturating, greedily apply only the rules that reduce this cost (with -i 0 only this)")]
+    greedy: Option<GreedyMetric>,
+    #[arg(long, value_name = "PASSES", default_value_t = 100, help = "Maximum number of greedy rewriting passes")]
+    greedy_passes: usize,
+    #[arg(long, value_name = "DELAY", help = "Stop once the evaluated delay of the best circuit is at most this")]
+    target_delay: Option<f64>,
+    #[arg(long, value_name = "ITERATIONS", default_value_t = 1, help = "Iterations between two delay evaluations")]
+    check_interval: usize,
+    #[arg(long, value_enum, default_value_t = Evaluator::CostModel, help = "How the delay of the best circuit is evaluated")]
+    evaluator: Evaluator,
+    #[arg(long, value_name = "PATH", default_value = "abc", help = "ABC binary used by the abc evaluator")]
+    abc: PathBuf,
+    #[arg(long, value_name = "FILE", help = "Liberty library for the abc evaluator (default: AIG levels and nodes)")]
+    abc_lib: Option<PathBuf>,
+    #[arg(long, value_name = "ITERATIONS", help = "Stop once the best extraction cost has not improved for this many iterations")]
+    plateau: Option<usize>,
+    #[arg(long, value_enum, default_value_t = PlateauMetric::Size, help = "Extraction cost tracked by --plateau")]
+    plateau_cost: PlateauMetric,
+    #[arg(long, value_name = "N", conflicts_with_all = ["partition_nodes", "target_delay", "plateau"], help = "Rewrite the cones of every N primary outputs as a window of their own, in parallel")]
+    partition_outputs: Option<usize>,
+    #[arg(long, value_name = "NODES", conflicts_with_all = ["target_delay", "plateau"], help = "Rewrite windows of at most this many nodes in parallel")]
+    partition_nodes: Option<usize>,
+    #[arg(long, value_enum, default_value_t = WindowMetric::Size, help = "Cost the windows are extracted with before they are stitched back")]
+    window_cost: WindowMetric,
+    #[arg(long, help = "Before rewriting, add NPN library implementations of the k-feasible cuts that need fewer gates")]
+    npn: bool,
+    #[arg(long, value_name = "K", default_value_t = 4, value_parser = clap::value_parser!(u8).range(2..=6), help = "Leaves per cut for --npn")]
+    cut_size: u8,
+    #[arg(long, value_name = "N", default_value_t = 8, help = "Cuts kept per class for --npn")]
+    cuts_per_class: usize,
+    #[arg(long, help = "Also add --npn implementations with as many gates as the cut they replace")]
+    npn_zero_gain: bool,
+    #[arg(long, help = "Before rewriting, add cheaper functions of cuts that agree with them on the simulated care set, once proven")]
+    dont_cares: bool,
+    #[arg(long, requires = "dont_cares", help = "Let --dont-cares also ignore the patterns where a class is not observed by its users")]
+    odc: bool,
+    #[arg(long, value_name = "FILE", help = "Circuit with one output that is 1 on the input assignments that can occur, for --dont-cares and --fraig")]
+    care: Option<PathBuf>,
+    #[arg(long, help = "After rewriting, union classes proven equivalent by simulation and SAT (FRAIGing)")]
+    fraig: bool,
+    #[arg(long, value_name = "WORDS", default_value_t = 4, help = "Random simulation words (64 patterns each) per class for --fraig and --dont-cares")]
+    fraig_words: usize,
+    #[arg(long, value_name = "INPUTS", default_value_t = 16, help = "Candidates with at most this many inputs are proven by exhaustive simulation, others with SAT")]
+    fraig_support: usize,
+    #[arg(long, value_name = "CONFLICTS", default_value_t = 1000, help = "Conflicts before a --fraig or --dont-cares SAT check gives up")]
+    fraig_conflicts: u64,
+    #[arg(long, default_value_t = true, action = ArgAction::Set, help = "Record explanations while rewriting")]
+    explanations: bool,
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Artifact::InputEgraph, Artifact::InputSerd, Artifact::RewrittenEgraph, Artifact::RewrittenSerd, Artifact::Weighted, Artifact::Report],
+        help = "Files to write to the output directory"
+    )]
+    emit: Vec<Artifact>,
+    #[arg(long, help = "Root the outputs in the order they are defined instead of the declared order")]
+    definition_order: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check that rewrite rules are sound by enumerating all assignments of their pattern variables
+    CheckRules {
+        #[arg(value_name = "FILE", help = "Rule files to check (default: all built-in rule sets)")]
+        rules: Vec<String>,
+    },
+    /// Synthesize candidate rewrite rules by enumerating terms and grouping them by truth table
+    SynthRules {
+        #[arg(long, default_value_t = 3, help = "Number of variables (at most 6)")]
+        vars: usize,
+        #[arg(long, default_value_t = 5, help = "Largest term size (operators, variables and constants)")]
+        size: usize,
+        #[arg(long, value_delimiter = ',', default_value = "!,*,+", help = "Operators to enumerate: ! * + ^ xnor mux maj")]
+        ops: Vec<String>,
+        #[arg(long, default_value_t = true, action = ArgAction::Set, help = "Use the constants 0 and 1 as leaves")]
+        constants: bool,
+        #[arg(short, long, value_name = "FILE", help = "Write the rules to a rule file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+    /// Check that two netlists compute the same outputs, matching inputs and outputs by name
+    Cec {
+        #[arg(value_name = "FIRST", help = "First netlist (eqn, BLIF, AIGER or Verilog)")]
+        first: String,
+        #[arg(value_name = "SECOND", help = "Second netlist")]
+        second: String,
+        #[arg(long, default_value_t = 64, help = "Random simulation words (64 patterns each)")]
+        words: usize,
+        #[arg(long, value_name = "INPUTS", default_value_t = 16, help = "Simulate every assignment when there are at most this many inputs")]
+        exhaustive_inputs: usize,
+        #[arg(long, default_value_t = 0, help = "Seed of the random patterns")]
+        seed: u64,
+        #[arg(long, default_value_t = 100000, help = "SAT conflicts per output before it is left undecided (0 for no limit)")]
+        conflicts: u64,
+        #[arg(long, help = "Only simulate, without SAT random patterns can find a difference but prove nothing")]
+        simulate_only: bool,
+        #[arg(long, value_name = "FILE", help = "Netlist with one output, outputs may only differ where it is 0")]
+        care: Option<String>,
+    },
+    /// Write a rewrite proof that every output of an extracted netlist equals the same output of the input
+    Prove {
+        #[arg(value_name = "INPUT", help = "Input netlist (eqn, BLIF, AIGER or Verilog)")]
+        input: String,
+        #[arg(value_name = "EXTRACTED", help = "Extracted netlist")]
+        extracted: String,
+        #[arg(short, long, value_name = "FILE", default_value = "proof.txt", help = "Proof file to write")]
+        output: PathBuf,
+        #[arg(short, long, value_enum, default_value_t = Ruleset::Full, help = "Rewrite rules to prove with")]
+        ruleset: Ruleset,
+        #[arg(long, value_name = "FILE", conflicts_with = "ruleset", help = "Rule file to prove with instead of a built-in rule set")]
+        rules: Option<String>,
+        #[arg(short, long, default_value_t = 30, help = "Iteration limit")]
+        iter_limit: usize,
+        #[arg(short, long, default_value_t = 60, help = "Time limit in seconds")]
+        time_limit: u64,
+        #[arg(short, long, default_value_t = 1000000, help = "Node limit")]
+        node_limit: usize,
+        #[arg(long, default_value_t = 1000, help = "Outputs whose terms have more nodes as trees are left unproven, every step prints the whole term")]
+        max_term_size: u64,
+        #[arg(long, help = "Check the written proof without the egraph")]
+        check: bool,
+    },
+    /// Check a proof written by `prove` step by step against the rules
+    CheckProof {
+        #[arg(value_name = "PROOF", help = "Proof file")]
+        proof: PathBuf,
+        #[arg(short, long, value_enum, default_value_t = Ruleset::Full, help = "Rewrite rules the proof uses")]
+        ruleset: Ruleset,
+        #[arg(long, value_name = "FILE", conflicts_with = "ruleset", help = "Rule file the proof uses instead of a built-in rule set")]
+        rules: Option<String>,
+        #[arg(long, value_name = "FILE", requires = "extracted", help = "Also check that every proof starts at this input netlist")]
+        input: Option<String>,
+        #[arg(long, value_name = "FILE", requires = "input", help = "Also check that every proof ends at this extracted netlist")]
+        extracted: Option<String>,
+    },
+}
+
+// The rules of a rule file, or of a built-in rule set
+fn select_rules(file: Option<&str>, ruleset: Ruleset) -> Result<Vec<Rewrite<Prop, ConstantFold>>, String> {
+    match (file, ruleset) {
+        (Some(path), _) => load_rules(path).map_err(|err| format!("{}: {}", path, err)),
+        (None, Ruleset::Full) => Ok(make_rules()),
+        (None, Ruleset::Simplify) => Ok(make_rules_simplify()),
+    }
+}
+
+// Checks every rule of the given rule files (or of the built-in rule sets) and
+// exits with an error if any rule is unsound
+fn check_rules_command(files: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let rule_sets = if files.is_empty() {
+        vec![("default".to_string(), make_rules()), ("simplify".to_string(), make_rules_simplify())]
+    } else {
+        let mut rule_sets = Vec::new();
+        for file in files {
+            let rules = load_rules(file).map_err(|err| format!("{}: {}", file, err))?;
+            rule_sets.push((file.clone(), rules));
+        }
+        rule_sets
+    };
+
+    let mut unsound = 0;
+    for (set, rules) in &rule_sets {
+        let checks = check_rules(rules);
+        let failed: Vec<_> = checks.iter().filter(|check| check.verdict != Verdict::Sound).collect();
+        println!("{}: {} rules, {} sound", set, checks.len(), checks.len() - failed.len());
+        for check in failed {
+            println!("  {}", check);
+            if matches!(check.verdict, Verdict::Unsound { .. }) {
+                unsound += 1;
+            }
+        }
+    }
+    if unsound > 0 {
+        return Err(format!("{} unsound rule(s)", unsound).into());
+    }
+    Ok(())
+}
+
+// Synthesizes rules and writes them in the text rule-file format
+fn synth_rules_command(config: &SynthConfig, output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let specs = synthesize(config)?;
+    // the synthesized rules must load and be sound like any hand-written rule file
+    let failed = check_rules(&build_rewrites::<ConstantFold>(&specs)?).into_iter().filter(|check| check.verdict != Verdict::Sound).count();
+    if failed > 0 {
+        return Err(format!("{} synthesized rule(s) are not sound", failed).into());
+    }
+
+    let mut text = format!(
+        "# synthesized over {} variable(s), terms up to size {}, operators {}{}\n",
+        config.vars,
+        config.max_size,
+        config.ops.join(" "),
+        if config.constants { ", constants 0 1" } else { "" }
+    );
+    for spec in &specs {
+        text.push_str(&format!("{}\n", spec));
+    }
+    match output {
+        Some(path) => fs::write(path, text)?,
+        None => print!("{}", text),
+    }
+    eprintln!("synthesized {} rules in {:.2}s", specs.len(), start.elapsed().as_secs_f64());
+    Ok(())
+}
+
+// Per-rule scheduling from --schedule and the --rule-* overrides, the global flags win over the file
+fn load_schedule(args: &Args) -> Result<ScheduleConfig, ScheduleError> {
+    let schedule = match &args.schedule {
+        Some(path) => read_schedule(path)?,
+        None => ScheduleConfig::default(),
+    };
+    let mut schedule = schedule.with_overrides(&args.rule_match_limit, &args.rule_ban_length, &args.rule_tier)?;
+    schedule.match_limit = args.match_limit.or(schedule.match_limit);
+    schedule.ban_length = args.ban_length.or(schedule.ban_length);
+    if schedule.has_tiers() && args.scheduler != Scheduler::Tiered {
+        return Err(ScheduleError::TiersNeedTiered);
+    }
+    Ok(schedule)
+}
+
+// A runner with the limits, scheduler and rules of the command line, without stop conditions on the circuit
+fn base_runner(args: &Args, schedule: &ScheduleConfig, rules: &[Rewrite<Prop, ConstantFold>]) -> Result<Runner<Prop, ConstantFold>, ScheduleError> {
+    let mut runner = Runner::default();
+    if args.explanations {
+        runner = runner.with_explanations_enabled();
+    }
+    runner = match args.scheduler {
+        Scheduler::Simple => runner.with_scheduler(SimpleScheduler),
+        Scheduler::Backoff => runner.with_scheduler(schedule.backoff(rules)?),
+        Scheduler::Tiered => runner.with_scheduler(schedule.tiered(rules)?),
+    };
+    Ok(runner
+        .with_time_limit(std::time::Duration::from_secs_f64(args.time_limit))
+        .with_iter_limit(args.iter_limit)
+        .with_node_limit(args.node_limit))
+}
+
+// Simulation and proof settings shared by --fraig and --dont-cares
+fn fraig_config(args: &Args, care: &Option<Arc<CareSet>>) -> FraigConfig {
+    FraigConfig {
+        words: args.fraig_words,
+        exhaustive_support: args.fraig_support,
+        conflict_limit: args.fraig_conflicts,
+        care: care.clone(),
+        ..FraigConfig::default()
+    }
+}
+
+// Don't-care-aware resynthesis of the input of a runner, returns its report entry
+fn dont_care_pass(args: &Args, egraph: &mut PropEGraph, roots: &[Id], care: &Option<Arc<CareSet>>) -> serde_json::Value {
+    let start = Instant::now();
+    let config = DontCareConfig {
+        cut_size: (args.cut_size as usize).min(4),
+        cuts_per_class: args.cuts_per_class,
+        observability: args.odc,
+        check: fraig_config(args, care),
+    };
+    let stats = dont_care_rewrite(egraph, roots, &config);
+    println!(
+        "don't care rewriting: {} cuts with don't cares, {} candidates, {} proven, {} rejected ({} SAT checks), {} inserted in {:?}",
+        stats.cuts,
+        stats.candidates,
+        stats.proven,
+        stats.rejected,
+        stats.sat_checks,
+        stats.inserted,
+        start.elapsed()
+    );
+    json!({
+        "cuts": stats.cuts,
+        "candidates": stats.candidates,
+        "proven": stats.proven,
+        "rejected": stats.rejected,
+        "sat_checks": stats.sat_checks,
+        "inserted": stats.inserted,
+    })
+}
+
+// Cut-based NPN resynthesis of the input of a runner, returns its report entry
+fn npn_rewrite(args: &Args, egraph: &mut PropEGraph) -> serde_json::Value {
+    let start = Instant::now();
+    let config = CutConfig {
+        cut_size: args.cut_size as usize,
+        cuts_per_class: args.cuts_per_class,
+        zero_gain: args.npn_zero_gain,
+    };
+    let stats = cut_rewrite(egraph, &config);
+    println!("npn rewriting: {} cuts, {} NPN classes, {} inserted in {:?}", stats.cuts, stats.npn_classes, stats.inserted, start.elapsed());
+    json!({
+        "cuts": stats.cuts,
+        "npn_classes": stats.npn_classes,
+        "inserted": stats.inserted,
+    })
+}
+
+// Checks every output of two netlists and exits with an error unless all are proven equivalent
+fn cec_command(first: &str, second: &str, config: &CecConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let parse = |file: &str| preprocess_file(file, OutputOrder::Declared).map_err(|err| format!("{}: {}", file, err));
+    let report = check_equivalence(&parse(first)?, &parse(second)?, config).map_err(|err| err.to_string())?;
+    for (file, names) in [(first, &report.only_in_first), (second, &report.only_in_second)] {
+        if !names.is_empty() {
+            println!("inputs only in {}: {}", file, names.join(" "));
+        }
+    }
+    // the input assignment is printed for the first difference only
+    let mut first_difference = true;
+    for (output, verdict) in &report.outputs {
+        match verdict {
+            CecVerdict::Different(counterexample) if !first_difference => {
+                println!("{}: different, {} in the first netlist and {} in the second", output, counterexample.first as u8, counterexample.second as u8)
+            }
+            CecVerdict::Equivalent(_) => {}
+            _ => println!("{}: {}", output, verdict),
+        }
+        first_difference &= !matches!(verdict, CecVerdict::Different(_));
+    }
+    let method = |method: Method| report.count(|verdict| *verdict == CecVerdict::Equivalent(method));
+    println!(
+        "{} outputs: {} structurally equal, {} equal on every assignment, {} proven by SAT, {} different, {} undecided",
+        report.outputs.len(),
+        method(Method::Structural),
+        method(Method::Simulation),
+        method(Method::Sat),
+        report.count(|verdict| matches!(verdict, CecVerdict::Different(_))),
+        report.count(|verdict| *verdict == CecVerdict::Undecided),
+    );
+    println!("miter of {} nodes, {} patterns, {} SAT checks, {} conflicts", report.miter_nodes, report.patterns, report.sat_checks, report.conflicts);
+    if !report.equivalent() {
+        return Err(format!("{} and {} are not proven equivalent", first, second).into());
+    }
+    Ok(())
+}
+
+// Simulates two netlists against each other and exits with an error on the first mismatch
+fn simulate_command(first: &str, second: &str, config: &SimConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let parse = |file: &str| preprocess_file(file, OutputOrder::Declared).map_err(|err| format!("{}: {}", file, err));
+    let report = simulate_equivalence(&parse(first)?, &parse(second)?, config).map_err(|err| err.to_string())?;
+    for (file, names) in [(first, &report.only_in_first), (second, &report.only_in_second)] {
+        if !names.is_empty() {
+            println!("inputs only in {}: {}", file, names.join(" "));
+        }
+    }
+    if let Some(counterexample) = &report.counterexample {
+        println!("{}", counterexample);
+        return Err(format!("{} and {} are not equivalent", first, second).into());
+    }
+    if report.exhaustive {
+        println!("{} outputs equivalent on all {} input assignments", report.outputs, report.patterns);
+    } else {
+        println!("{} outputs equal on {} random patterns (not a proof)", report.outputs, report.patterns);
+    }
+    Ok(())
+}
+
+// The output expressions of a netlist file
+fn read_output_exprs(file: &str) -> Result<Vec<(String, RecExpr<Prop>)>, String> {
+    let netlist = preprocess_file(file, OutputOrder::Declared).map_err(|err| format!("{}: {}", file, err))?;
+    utils::netlist::output_exprs(&netlist).map_err(|err| format!("{}: {}", file, err))
+}
+
+// Checks every proven output of a proof. With the netlists, also checks that each proof starts at
+// the input output and ends at the extracted one, and that no output is missing.
+fn check_proofs(proofs: &[OutputProof], rules: &RuleTable, ends: Option<(&OutputExprs, &OutputExprs)>) -> Result<CheckStats, String> {
+    let mut stats = CheckStats::default();
+    let mut failed = 0;
+    for proof in proofs {
+        let OutputProof::Proven { output, steps } = proof else {
+            continue;
+        };
+        let mut result = check_steps(steps, rules, &mut stats);
+        if let (Ok(()), Some((input, extracted))) = (&result, ends) {
+            let find = |exprs: &OutputExprs| exprs.iter().position(|(name, _)| name == output);
+            result = match (find(input), find(extracted)) {
+                (Some(first), _) if !same_expr(&input[first].1, &steps[0].term) => Err("the first term is not the input output".to_string()),
+                (_, Some(last)) if !same_expr(&extracted[last].1, &steps[steps.len() - 1].term) => Err("the last term is not the extracted output".to_string()),
+                (Some(_), Some(_)) => Ok(()),
+                _ => Err("not an output of both netlists".to_string()),
+            };
+        }
+        if let Err(err) = result {
+            println!("{}: {}", output, err);
+            failed += 1;
+        }
+    }
+    if let Some((input, _)) = ends {
+        for (name, _) in input {
+            if !proofs.iter().any(|proof| proof.output() == name) {
+                println!("{}: no proof", name);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} output proof(s) failed", failed));
+    }
+    Ok(stats)
+}
+
+// Proves the extracted netlist equal to the input output by output and writes the proof
+fn prove_command(input: &str, extracted: &str, output: &Path, rule_set: &str, rules: &[Rewrite<Prop, ConstantFold>], config: &ProofConfig, check: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let input_exprs = read_output_exprs(input)?;
+    let extracted_exprs = read_output_exprs(extracted)?;
+    let start = Instant::now();
+    let proofs = prove(&input_exprs, &extracted_exprs, rules, config).map_err(|err| err.to_string())?;
+    let header = [format!("input: {}", input), format!("extracted: {}", extracted), format!("rules: {}", rule_set)];
+    fs::write(output, write_proof(&proofs, &header))?;
+    let proven = proofs.iter().filter(|proof| matches!(proof, OutputProof::Proven { .. })).count();
+    let steps: usize = proofs.iter().map(|proof| if let OutputProof::Proven { steps, .. } = proof { steps.len() - 1 } else { 0 }).sum();
+    for proof in &proofs {
+        if let OutputProof::Unproven { output, reason } = proof {
+            println!("{}: {}", output, reason);
+        }
+    }
+    println!("{} of {} outputs proven in {} steps, {:?}, written to {}", proven, proofs.len(), steps, start.elapsed(), output.display());
+    if check {
+        // the proof is read back from the file, so the check does not trust anything in memory
+        let proofs = parse_proof(&fs::read_to_string(output)?).map_err(|err| format!("{}: {}", output.display(), err))?;
+        let stats = check_proofs(&proofs, &RuleTable::new(rules), Some((&input_exprs, &extracted_exprs)))?;
+        println!("proof checked: {} rule steps, {} constant folds by evaluation", stats.rule_steps, stats.evaluated_steps);
+    }
+    if proven < proofs.len() {
+        return Err(format!("{} output(s) are not proven", proofs.len() - proven).into());
+    }
+    Ok(())
+}
+
+// Checks a proof file and exits with an error if a step does not follow or an output is unproven
+fn check_proof_command(proof: &Path, rules: &[Rewrite<Prop, ConstantFold>], netlists: Option<(&str, &str)>) -> Result<(), Box<dyn std::error::Error>> {
+    let proofs = parse_proof(&fs::read_to_string(proof)?).map_err(|err| format!("{}: {}", proof.display(), err))?;
+    let exprs = match netlists {
+        Some((input, extracted)) => Some((read_output_exprs(input)?, read_output_exprs(extracted)?)),
+        None => None,
+    };
+    let ends = exprs.as_ref().map(|(input, extracted)| (input.as_slice(), extracted.as_slice()));
+    let stats = check_proofs(&proofs, &RuleTable::new(rules), ends)?;
+    let unproven = proofs.iter().filter(|proof| matches!(proof, OutputProof::Unproven { .. })).count();
+    println!("{} outputs, {} rule steps, {} constant folds by evaluation, {} unproven", proofs.len(), stats.rule_steps, stats.evaluated_steps, unproven);
+    if unproven > 0 {
+        return Err(format!("{} output(s) are not proven", unproven).into());
+    }
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    let input_path = &args[1];
+    let args = Args::parse();
+    match &args.command {
+        Some(Command::CheckRules { rules }) => return check_rules_command(rules),
+        Some(Command::Cec { first, second, words, exhaustive_inputs, seed, conflicts, simulate_only, care }) => {
+            let care = match care {
+                Some(file) => Some(preprocess_file(file, OutputOrder::Declared).map_err(|err| format!("{}: {}", file, err))?),
+                None => None,
+            };
+            let sim = SimConfig {
+                words: *words,
+                exhaustive_inputs: *exhaustive_inputs,
+                seed: *seed,
+                care,
+            };
+            if *simulate_only {
+                return simulate_command(first, second, &sim);
+            }
+            let config = CecConfig {
+                sim,
+                conflict_limit: (*conflicts > 0).then_some(*conflicts),
+            };
+            return cec_command(first, second, &config);
+        }
+        Some(Command::Prove { input, extracted, output, ruleset, rules, iter_limit, time_limit, node_limit, max_term_size, check }) => {
+            let rule_set = rules.clone().unwrap_or_else(|| format!("{:?}", ruleset).to_lowercase());
+            let config = ProofConfig {
+                iter_limit: *iter_limit,
+                node_limit: *node_limit,
+                time_limit: std::time::Duration::from_secs(*time_limit),
+                max_term_size: *max_term_size,
+            };
+            return prove_command(input, extracted, output, &rule_set, &select_rules(rules.as_deref(), *ruleset)?, &config, *check);
+        }
+        Some(Command::CheckProof { proof, ruleset, rules, input, extracted }) => {
+            let netlists = input.as_deref().zip(extracted.as_deref());
+            return check_proof_command(proof, &select_rules(rules.as_deref(), *ruleset)?, netlists);
+        }
+        Some(Command::SynthRules { vars, size, ops, constants, output }) => {
+            let config = SynthConfig {
+                vars: *vars,
+                max_size: *size,
+                ops: ops.clone(),
+                constants: *constants,
+                ..SynthConfig::default()
+            };
+            return synth_rules_command(&config, output.as_deref());
+        }
+        None => {}
+    }
+    let input_path = args.input.as_deref().expect("clap requires an input file");
+    let emit = |artifact: Artifact| args.emit.contains(&artifact);
+    fs::create_dir_all(&args.output_dir)?;
+
+    // rule files are checked before any work is done
+    let rules = select_rules(args.rules.as_deref(), args.ruleset)?;
+    let schedule = load_schedule(&args).and_then(|schedule| schedule.check_names(&rules).map(|_| schedule)).map_err(|err| format!("schedule: {}", err))?;
 
     // Set up timer to measure time for eqn2egraph
     let start = Instant::now();
 
-    // Preprocess input file
-    preprocess_file_concat(&input_path)?;
-    preprocess_file_order(&input_path)?;
-    println!("Finished preprocessing input file");
-
-    // Transfer eqn file into egraph format in egg
-    let (root_id, input_vec_id, input_vec_symbol) = process_file(input_path);
-
-    println!("root: {:?}", root_id);
-
-    let duration_eqn2egraph_initial = start.elapsed();
-    println!("eqn2egraph initially finished in: {:?}.", duration_eqn2egraph_initial);
-
-    
-    let mut root_ids: Vec<usize> = vec![root_id.into()];
-    
-    let json_file = format!("{}.json", input_path);
-
-    // format with base name+json
-    // let path = Path::new(input_path);
-    // let Some(stem) = path.file_stem() else {
-    //     panic!("Invalid input file path")
-    // };
-    // let json_file =  format!("{}.json", stem.to_string_lossy());
-    // println!("json_file: {}", json_file);
-
-    // Transfer egg::egraph symbol language's json into my defined language's json
-    let modified_json_file = process_json_prop(&json_file);
-    //let modified_json_file = process_json_prop_prallel(&json_file);
-    let duration_eqn2egraph_handling_base_language = start.elapsed();
-    let converted_json_data = fs::read_to_string(&modified_json_file).expect("Unable to read the JSON file");
-    let mut input_egraph: egg::EGraph<Prop, ()> = serde_json::from_str(&converted_json_data).unwrap();
+    // Outputs keep their declared order unless the definition order is explicitly requested
+    let output_order = if args.definition_order {
+        OutputOrder::Definition
+    } else {
+        OutputOrder::Declared
+    };
+
+    // Transfer the eqn/BLIF/AIGER/Verilog file into egraph format in egg (the input file is only read)
+    let Circuit { egraph: mut input_egraph, roots, inputs, latches } = process_file(input_path, output_order).map_err(|err| format!("{}: {}", input_path, err))?;
     input_egraph.rebuild();//eqn2egraph finished
+    let care = match &args.care {
+        Some(file) => {
+            let file = file.to_string_lossy();
+            Some(CareSet::from_file(&file).map(Arc::new).map_err(|err| format!("{}: {}", file, err))?)
+        }
+        None => None,
+    };
 
     // print the time taken for eqn2egraph
     let eqn2egraph_all_duration = start.elapsed();
-    println!("eqn2egraph with handling of base language finished in: {:?}.", duration_eqn2egraph_handling_base_language - duration_eqn2egraph_initial);
     println!("eqn2egraph finished in: {:?}.", eqn2egraph_all_duration);
 
+    // every primary output is a root of its own
+    let root_ids: Vec<usize> = roots.iter().map(|(_, id)| usize::from(*id)).collect();
+    let root_names: Vec<String> = roots.iter().map(|(name, _)| name.clone()).collect();
+    let input_names: Vec<String> = inputs.iter().map(|(name, _)| name.clone()).collect();
+    println!("roots: {:?}", root_ids);
+
     // Save input_egraph into json file
-    let input_egraph_json_path = env::current_dir().unwrap().join("rewritten_circuit/eqn2egraph.json"); 
-    save_egraph_to_json(&input_egraph, &input_egraph_json_path)?;
+    if emit(Artifact::InputEgraph) {
+        save_egraph_to_json(&input_egraph, &args.output_dir.join("eqn2egraph.json"))?;
+    }
 
     // Read from json file and print info
     // let json_contents = fs::read_to_string(&input_egraph_json_path).expect("Failed to read JSON file");
     // let mut converted_egg: egg::EGraph<Prop, ()> = serde_json::from_str(&json_contents).unwrap();
     // converted_egg.rebuild();
 
-    let converted_egg = input_egraph.clone();
+    let mut converted_egg = input_egraph.clone();
+
+    // Greedy destructive rewriting, the rewritten DAG replaces the input of the runner
+    let mut root_ids = root_ids;
+    if let Some(metric) = args.greedy {
+        let start = Instant::now();
+        let cost = match metric {
+            GreedyMetric::Size => GreedyCost::Size,
+            GreedyMetric::Depth => GreedyCost::Depth,
+        };
+        let roots: Vec<Id> = root_ids.iter().map(|id| Id::from(*id)).collect();
+        let (dag, roots, stats) = greedy_rewrite(&converted_egg, &roots, &rules, cost, args.greedy_passes)?;
+        println!(
+            "greedy rewriting: {} passes, {} rewrites, {:?} cost {} -> {} in {:?}",
+            stats.passes,
+            stats.rewrites,
+            metric,
+            stats.cost_before,
+            stats.cost_after,
+            start.elapsed()
+        );
+        converted_egg = dag;
+        root_ids = roots.iter().map(|id| usize::from(*id)).collect();
+    }
 
     println!("total");
     println!("input node: {}", converted_egg.total_size());
     println!("input class: {}", converted_egg.number_of_classes());
 
     // Transfer egg::egraph to serialized_egraph and save it into json file
-    let serialized_input_egraph = egg_to_serialized_egraph(&converted_egg);
-    let serialized_input_egraph_json_path = env::current_dir().unwrap().join("rewritten_circuit/egraph2egraph_serd.json"); // egraph to serialized_egraph finished
-    save_serialized_egraph_to_json(&serialized_input_egraph, &serialized_input_egraph_json_path, &root_ids)?;
+    if emit(Artifact::InputSerd) {
+        let serialized_input_egraph = egg_to_serialized_egraph(&converted_egg);
+        let serialized_input_egraph_json_path = args.output_dir.join("egraph2egraph_serd.json"); // egraph to serialized_egraph finished
+        save_serialized_egraph_to_json(&serialized_input_egraph, &serialized_input_egraph_json_path, &root_ids, &root_names, &input_names, &latches)?;
+    }
 
 
     // Rewrite time!
     {
-        let runner_iteration_limit = env::args()
-            .nth(2)
-            .unwrap_or("10".to_string())
-            .parse()
-            .unwrap_or(20);
-        let egraph_node_limit = 200000000;
         let start = Instant::now();
-        let mut runner = Runner::default()
-            .with_explanations_enabled()
-            .with_egraph(converted_egg.clone())
-            .with_time_limit(std::time::Duration::from_secs(10))
-            .with_iter_limit(runner_iteration_limit)
-            .with_node_limit(egraph_node_limit);
-
-        runner.roots = root_ids.iter().cloned().map(Id::from).collect();
-        let runner_result = runner.run(&make_rules());
-
-        let duration = start.elapsed();
-        println!(
-            "Runner stopped: {:?}. Time taken for runner: {:?}, Classes: {}, Nodes: {}, Size: {} \n\n",
-            runner_result.stop_reason,
-            duration,
-            runner_result.egraph.number_of_classes(),
-            runner_result.egraph.total_number_of_nodes(),
-            runner_result.egraph.total_size()
-        );
-        println!("root{:?}", runner_result.roots);
-        runner_result.print_report();
-        let root = runner_result.roots[0];
+        let partition_mode = match (args.partition_outputs, args.partition_nodes) {
+            (Some(outputs), _) => Some(Partition::Outputs(outputs)),
+            (None, Some(nodes)) => Some(Partition::Nodes(nodes)),
+            (None, None) => None,
+        };
+        let (mut output_egraph, output_roots, mut report) = if let Some(mode) = partition_mode {
+            // every window is rewritten and extracted on its own, the stitched DAG is the output egraph
+            let roots: Vec<Id> = root_ids.iter().cloned().map(Id::from).collect();
+            let windows = partition(&converted_egg, &roots, mode);
+            println!("partitioned into {} windows", windows.len());
+            let cost = match args.window_cost {
+                WindowMetric::Size => WindowCost::Size,
+                WindowMetric::Depth => WindowCost::Depth,
+            };
+            let (dag, roots, reports) = rewrite_partitioned(&converted_egg, &roots, &windows, cost, |index, egraph, roots| {
+                let window_start = Instant::now();
+                let mut egraph = egraph;
+                let npn = args.npn.then(|| npn_rewrite(&args, &mut egraph));
+                let dont_cares = args.dont_cares.then(|| dont_care_pass(&args, &mut egraph, &roots, &care));
+                let mut runner = base_runner(&args, &schedule, &rules)?.with_egraph(egraph);
+                runner.roots = roots;
+                let runner_result = runner.run(&rules);
+                println!(
+                    "window {}: {} roots, stopped: {:?}, Classes: {}, Nodes: {} in {:?}",
+                    index,
+                    runner_result.roots.len(),
+                    runner_result.stop_reason,
+                    runner_result.egraph.number_of_classes(),
+                    runner_result.egraph.total_number_of_nodes(),
+                    window_start.elapsed()
+                );
+                let mut report = runner_result.report_json();
+                if let Some(npn) = npn {
+                    report["npn"] = npn;
+                }
+                if let Some(dont_cares) = dont_cares {
+                    report["dont_cares"] = dont_cares;
+                }
+                let roots = runner_result.roots.iter().map(|id| runner_result.egraph.find(*id)).collect();
+                Ok::<_, ScheduleError>((runner_result.egraph, roots, report))
+            })?;
+            println!("Partitioned rewriting finished in {:?}", start.elapsed());
+            (dag, roots, json!({ "windows": reports }))
+        } else {
+            let mut input = converted_egg.clone();
+            let npn = args.npn.then(|| npn_rewrite(&args, &mut input));
+            let roots: Vec<Id> = root_ids.iter().cloned().map(Id::from).collect();
+            let dont_cares = args.dont_cares.then(|| dont_care_pass(&args, &mut input, &roots, &care));
+            let mut runner = base_runner(&args, &schedule, &rules)?.with_egraph(input);
+            if let Some(target_delay) = args.target_delay {
+                runner = runner.with_target_delay(target_delay).with_check_interval(args.check_interval);
+                runner = match args.evaluator {
+                    Evaluator::CostModel => runner.with_evaluator(CostModel),
+                    Evaluator::Abc => runner.with_evaluator(Abc {
+                        abc: args.abc.clone(),
+                        lib: args.abc_lib.clone(),
+                        work_dir: args.output_dir.clone(),
+                        root_names: root_names.clone(),
+                    }),
+                };
+            }
+            if let Some(patience) = args.plateau {
+                runner = match args.plateau_cost {
+                    PlateauMetric::Size => runner.with_plateau(patience, SizeCost),
+                    PlateauMetric::Depth => runner.with_plateau(patience, DepthCost),
+                };
+            }
+
+            runner.roots = roots;
+            let runner_result = runner.run(&rules);
+
+            let duration = start.elapsed();
+            println!(
+                "Runner stopped: {:?}. Time taken for runner: {:?}, Classes: {}, Nodes: {}, Size: {} \n\n",
+                runner_result.stop_reason,
+                duration,
+                runner_result.egraph.number_of_classes(),
+                runner_result.egraph.total_number_of_nodes(),
+                runner_result.egraph.total_size()
+            );
+            println!("root{:?}", runner_result.roots);
+            runner_result.print_report();
+            for (i, iteration) in runner_result.iterations.iter().enumerate() {
+                if let Some(qor) = iteration.qor {
+                    println!("iteration {}: delay {}, area {}", i + 1, qor.delay, qor.area);
+                }
+            }
+            let mut report = runner_result.report_json();
+            if let Some(npn) = npn {
+                report["npn"] = npn;
+            }
+            if let Some(dont_cares) = dont_cares {
+                report["dont_cares"] = dont_cares;
+            }
+            (runner_result.egraph, runner_result.roots, report)
+        };
+        if args.fraig {
+            let start = Instant::now();
+            let stats = fraig(&mut output_egraph, &fraig_config(&args, &care));
+            println!(
+                "fraig: {} classes, {} candidates, {} proven, {} disproven, {} undecided ({} SAT checks) in {:?}",
+                stats.classes,
+                stats.candidates,
+                stats.proven,
+                stats.disproven,
+                stats.undecided,
+                stats.sat_checks,
+                start.elapsed()
+            );
+            report["fraig"] = json!({
+                "classes": stats.classes,
+                "candidates": stats.candidates,
+                "proven": stats.proven,
+                "disproven": stats.disproven,
+                "undecided": stats.undecided,
+                "sat_checks": stats.sat_checks,
+            });
+        }
+        if emit(Artifact::Report) {
+            // the input and rule set make reports of different runs comparable
+            report["input"] = json!(input_path);
+            report["rule_set"] = match &args.rules {
+                Some(file) => json!(file),
+                None => json!(format!("{:?}", args.ruleset).to_lowercase()),
+            };
+            fs::write(args.output_dir.join("runner_report.json"), serde_json::to_string_pretty(&report)?)?;
+        }
+        // roots may have been merged into other classes during rewriting
+        let root_ids: Vec<usize> = output_roots.iter().map(|id| usize::from(output_egraph.find(*id))).collect();
 
         // Save output egraph from runner (input for extraction gym)
-        let output_egraph_json_path = env::current_dir().unwrap().join("rewritten_circuit/rewritten_egraph_internal.json");
-        save_egraph_to_json(&runner_result.egraph, &output_egraph_json_path)?;
+        if emit(Artifact::RewrittenEgraph) {
+            save_egraph_to_json(&output_egraph, &args.output_dir.join("rewritten_egraph_internal.json"))?;
+        }
 
         println!("egraph after runner");
-        println!("egraph node: {}", runner_result.egraph.total_size());
-        println!("egraph class: {}", runner_result.egraph.number_of_classes());
+        println!("egraph node: {}", output_egraph.total_size());
+        println!("egraph class: {}", output_egraph.number_of_classes());
 
         // Save serialized output egraph to json with root nodes
-        let serialized_output_egraph = egg_to_serialized_egraph(&runner_result.egraph);
-        let serialized_output_egraph_json_path = env::current_dir().unwrap().join("rewritten_circuit/rewritten_egraph_internal_serd.json");
-        save_serialized_egraph_to_json(&serialized_output_egraph, &serialized_output_egraph_json_path, &root_ids)?;
-
-        println!("------------------assign cost of enode-----------------");
-        let json_string = serde_json::to_string(&serialized_output_egraph).unwrap();
-        let cost_string = process_json_prop_cost(&json_string);
-
-        let output_egraph_cost_json_path = env::current_dir().unwrap().join("rewritten_circuit/rewritten_egraph_with_weight_cost_serd.json");
-        let mut json_data: serde_json::Value = serde_json::from_str(&cost_string)?;
-        json_data["root_eclasses"] = serde_json::Value::Array(root_ids.iter().map(|id| serde_json::Value::String(id.to_string())).collect());
-        let file = File::create(&output_egraph_cost_json_path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &json_data)?;
+        let serialized_output_egraph = egg_to_serialized_egraph(&output_egraph);
+        if emit(Artifact::RewrittenSerd) {
+            let serialized_output_egraph_json_path = args.output_dir.join("rewritten_egraph_internal_serd.json");
+            save_serialized_egraph_to_json(&serialized_output_egraph, &serialized_output_egraph_json_path, &root_ids, &root_names, &input_names, &latches)?;
+        }
+
+        if emit(Artifact::Weighted) {
+            println!("------------------assign cost of enode-----------------");
+            let json_string = serde_json::to_string(&serialized_output_egraph).unwrap();
+            let cost_string = process_json_prop_cost(&json_string);
+
+            let output_egraph_cost_json_path = args.output_dir.join("rewritten_egraph_with_weight_cost_serd.json");
+            let mut json_data: serde_json::Value = serde_json::from_str(&cost_string)?;
+            set_circuit_metadata(&mut json_data, &root_ids, &root_names, &input_names, &latches);
+            let file = File::create(&output_egraph_cost_json_path)?;
+            let writer = BufWriter::new(file);
+            serde_json::to_writer_pretty(writer, &json_data)?;
+        }
 
         println!("done");
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes `text` to a file of the temporary directory, named after the test
+    fn write_temp(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("e-rewriter-{}-{}", std::process::id(), name));
+        fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    const CIRCUIT: &str = "INORDER = a b c;\nOUTORDER = f g;\nf = a * b + a * c;\ng = !(!a) + a * b;\n";
+    // `CIRCUIT` with fewer gates, as BLIF
+    const REWRITTEN: &str = ".model rewritten\n.inputs a b c\n.outputs f g\n.names b c t\n1- 1\n-1 1\n.names a t f\n11 1\n.names a g\n1 1\n.end\n";
+
+    #[test]
+    fn cec_command_proves_a_smaller_circuit_in_another_format() {
+        let first = write_temp("proves.eqn", CIRCUIT);
+        let second = write_temp("proves.blif", REWRITTEN);
+        assert!(cec_command(&first, &second, &CecConfig::default()).is_ok());
+        assert!(simulate_command(&first, &second, &SimConfig::default()).is_ok());
+        // without exhaustive simulation the outputs are proven by SAT
+        let sat = CecConfig { sim: SimConfig { exhaustive_inputs: 0, ..Default::default() }, ..Default::default() };
+        assert!(cec_command(&first, &second, &sat).is_ok());
+        let report = check_equivalence(&preprocess_file(&first, OutputOrder::Declared).unwrap(), &preprocess_file(&second, OutputOrder::Declared).unwrap(), &sat).unwrap();
+        assert!(report.sat_checks > 0);
+        for file in [first, second] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn cec_command_ignores_differences_outside_the_care_set() {
+        // `f` differs from `CIRCUIT` only when `a` is 0
+        let first = write_temp("care.eqn", CIRCUIT);
+        let second = write_temp("care-second.eqn", "INORDER = a b c;\nOUTORDER = f g;\nf = b + c;\ng = a;\n");
+        let care_set = write_temp("care-set.eqn", "INORDER = a;\nOUTORDER = care;\ncare = a;\n");
+        let care = preprocess_file(&care_set, OutputOrder::Declared).unwrap();
+        assert!(cec_command(&first, &second, &CecConfig::default()).is_err());
+        assert!(simulate_command(&first, &second, &SimConfig::default()).is_err());
+        let sim = || SimConfig { care: Some(care.clone()), ..Default::default() };
+        assert!(cec_command(&first, &second, &CecConfig { sim: sim(), ..Default::default() }).is_ok());
+        assert!(cec_command(&first, &second, &CecConfig { sim: SimConfig { exhaustive_inputs: 0, ..sim() }, ..Default::default() }).is_ok());
+        assert!(simulate_command(&first, &second, &sim()).is_ok());
+        for file in [first, second, care_set] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn cec_command_rejects_different_circuits() {
+        let first = write_temp("rejects.eqn", CIRCUIT);
+        let different = write_temp("rejects-different.eqn", "INORDER = a b c;\nOUTORDER = f g;\nf = a * b + c;\ng = a;\n");
+        assert!(cec_command(&first, &different, &CecConfig::default()).is_err());
+        assert!(simulate_command(&first, &different, &SimConfig::default()).is_err());
+        let missing = write_temp("rejects-missing.eqn", "INORDER = a b c;\nOUTORDER = f;\nf = a * (b + c);\n");
+        assert!(cec_command(&first, &missing, &CecConfig::default()).is_err());
+        assert!(cec_command(&first, "no-such-file.eqn", &CecConfig::default()).is_err());
+        for file in [first, different, missing] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+}
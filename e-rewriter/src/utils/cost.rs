@@ -66,6 +66,9 @@ impl CostFunction<Prop> for wight_size{
             "!" => 1 ,
             "+" => 3,
             "*"=> 5,
+            "^" | "xnor" => 8,
+            "mux" => 9,
+            "maj" => 10,
             //"&" => 0.0 as  f32,
             _=> 1 
         };
@@ -104,6 +107,8 @@ impl CostFunction<Prop> for wight_depth{
             Not(a) => 2 + enode.fold(0, |max, id| max.max(costs(id))),  // 将常量改为浮点数
             And([a,b]) => 4  + enode.fold(0, |max, id| max.max(costs(id))),  // 将常量改为浮点数
             Or([a,b]) =>  6 + enode.fold(0, |max, id| max.max(costs(id))),  // 将常量改为浮点数
+            Xor(_) | Xnor(_) | Mux(_) => 8 + enode.fold(0, |max, id| max.max(costs(id))),
+            Maj(_) => 10 + enode.fold(0, |max, id| max.max(costs(id))),
             _=>0  + enode.fold(0, |max, id| max.max(costs(id))),  
 
 
@@ -0,0 +1,374 @@
+// Cut-based resynthesis inside the egraph, like ABC's `rewrite`/`refactor`.
+//
+// A k-feasible cut of a class is a set of at most k classes (its leaves) such that the class is a
+// function of them. Cuts are enumerated bottom-up, in evaluation order, through every node of a
+// class whose children come before it: the cuts of a node merge one cut of each child, and the
+// truth table of the cut is computed from the children's tables. For every cut, an implementation
+// from the NPN library (Shannon-expanded down to 4-input cofactors for larger cuts) is added as new
+// nodes and unioned with the class when it has fewer gates than the cone it replaces.
+use crate::utils::fraig::evaluation_order;
+use crate::utils::language::{Prop, PropEGraph};
+use crate::utils::npn::{Step, LIBRARY};
+use egg::{Id, Language};
+use std::collections::{HashMap, HashSet};
+
+pub struct CutConfig {
+    // at most 6 leaves, so a truth table fits in a u64
+    pub cut_size: usize,
+    // cuts kept per class, the smallest first
+    pub cuts_per_class: usize,
+    // also insert implementations with as many gates as the cone they replace
+    pub zero_gain: bool,
+}
+
+impl Default for CutConfig {
+    fn default() -> Self {
+        CutConfig {
+            cut_size: 4,
+            cuts_per_class: 8,
+            zero_gain: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CutStats {
+    pub cuts: usize,
+    // distinct NPN classes of the cuts with at most 4 leaves
+    pub npn_classes: usize,
+    pub inserted: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Cut {
+    pub leaves: Vec<Id>,
+    // bit `m` is the value for the assignment `m` of the leaves, leaf `i` is bit `i` of `m`
+    pub table: u64,
+    // classes of the and/or/xor/mux/maj nodes of the cone, inverters are free
+    pub cone: Vec<Id>,
+}
+
+pub(crate) fn mask(vars: usize) -> u64 {
+    if vars == 6 {
+        u64::MAX
+    } else {
+        (1u64 << (1 << vars)) - 1
+    }
+}
+
+// the table of a function of `from` as a function of `to`, a superset
+fn stretch(table: u64, from: &[Id], to: &[Id]) -> u64 {
+    let positions: Vec<usize> = from.iter().map(|leaf| to.binary_search(leaf).expect("cuts merge into a superset")).collect();
+    let mut stretched = 0u64;
+    for m in 0..1usize << to.len() {
+        let index = positions.iter().enumerate().fold(0, |index, (i, position)| index | (m >> position & 1) << i);
+        stretched |= (table >> index & 1) << m;
+    }
+    stretched
+}
+
+// the table as a function of 4 variables, the ones beyond `vars` do not matter
+pub(crate) fn replicate(table: u64, vars: usize) -> u16 {
+    let mut table = table & mask(vars);
+    let mut width = 1 << vars;
+    while width < 16 {
+        table |= table << width;
+        width *= 2;
+    }
+    table as u16
+}
+
+// the table of `f` with variable `var` fixed to `value`, over the other variables
+fn cofactor(table: u64, vars: usize, var: usize, value: bool) -> u64 {
+    let mut cofactor = 0u64;
+    for m in 0..1usize << (vars - 1) {
+        let low = m & ((1 << var) - 1);
+        let full = low | (m >> var) << (var + 1) | (value as usize) << var;
+        cofactor |= (table >> full & 1) << m;
+    }
+    cofactor
+}
+
+// Cuts of every class of `order`, the trivial cut (the class itself) first
+pub(crate) fn enumerate_cuts(egraph: &PropEGraph, order: &[Id], config: &CutConfig) -> HashMap<Id, Vec<Cut>> {
+    let position: HashMap<Id, usize> = order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let mut cuts: HashMap<Id, Vec<Cut>> = HashMap::with_capacity(order.len());
+    for id in order {
+        let class = &egraph[*id];
+        if let Some(value) = class.data {
+            cuts.insert(*id, vec![Cut { leaves: Vec::new(), table: value as u64, cone: Vec::new() }]);
+            continue;
+        }
+        let mut found: Vec<Cut> = Vec::new();
+        for node in &class.nodes {
+            let children: Vec<Id> = node.children().iter().map(|child| egraph.find(*child)).collect();
+            if children.is_empty() || matches!(node, Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_)) {
+                continue;
+            }
+            if !children.iter().all(|child| position.get(child).is_some_and(|p| *p < position[id])) {
+                continue;
+            }
+            // the node with its i-th child renamed to i, to evaluate it on the children's tables
+            let mut index = 0;
+            let local = node.clone().map_children(|_| {
+                index += 1;
+                Id::from(index - 1)
+            });
+            let mut combination = vec![0usize; children.len()];
+            'combinations: loop {
+                let picked: Vec<&Cut> = children.iter().zip(&combination).map(|(child, i)| &cuts[child][*i]).collect();
+                let mut leaves: Vec<Id> = picked.iter().flat_map(|cut| cut.leaves.iter().copied()).collect();
+                leaves.sort_unstable();
+                leaves.dedup();
+                if leaves.len() <= config.cut_size {
+                    let tables: Vec<u64> = picked.iter().map(|cut| stretch(cut.table, &cut.leaves, &leaves)).collect();
+                    let table = local.eval_bits(|child| tables[usize::from(child)]).expect("operators evaluate") & mask(leaves.len());
+                    // shared logic of the children's cones is only counted once
+                    let mut cone: Vec<Id> = picked.iter().flat_map(|cut| cut.cone.iter().copied()).collect();
+                    if !matches!(node, Prop::Not(_)) {
+                        cone.push(*id);
+                    }
+                    cone.sort_unstable();
+                    cone.dedup();
+                    found.push(Cut { leaves, table, cone });
+                }
+                // next combination, like counting with one digit per child
+                for (digit, child) in combination.iter_mut().zip(&children) {
+                    *digit += 1;
+                    if *digit < cuts[child].len() {
+                        continue 'combinations;
+                    }
+                    *digit = 0;
+                }
+                break;
+            }
+        }
+        found.sort_by(|a, b| (a.leaves.len(), a.cone.len()).cmp(&(b.leaves.len(), b.cone.len())).then_with(|| a.leaves.cmp(&b.leaves)));
+        let mut seen = HashSet::new();
+        found.retain(|cut| cut.leaves != [*id] && seen.insert(cut.leaves.clone()));
+        found.truncate(config.cuts_per_class);
+        found.insert(0, Cut { leaves: vec![*id], table: 0b10, cone: Vec::new() });
+        cuts.insert(*id, found);
+    }
+    cuts
+}
+
+// appends `sub` to `steps`, variable `i` of `sub` becomes the step `vars[i]`
+fn splice(steps: &mut Vec<Step>, sub: &[Step], vars: &[usize]) -> usize {
+    let mut at: Vec<usize> = Vec::with_capacity(sub.len());
+    for step in sub {
+        let step = match *step {
+            Step::Var(var) => {
+                at.push(vars[var]);
+                continue;
+            }
+            Step::Const(value) => Step::Const(value),
+            Step::Not(a) => Step::Not(at[a]),
+            Step::And(a, b) => Step::And(at[a], at[b]),
+            Step::Or(a, b) => Step::Or(at[a], at[b]),
+            Step::Xor(a, b) => Step::Xor(at[a], at[b]),
+            Step::Xnor(a, b) => Step::Xnor(at[a], at[b]),
+            Step::Mux(s, a, b) => Step::Mux(at[s], at[a], at[b]),
+        };
+        steps.push(step);
+        at.push(steps.len() - 1);
+    }
+    *at.last().expect("implementations are not empty")
+}
+
+// the steps `root` depends on, renumbered, `Var(i)` for the variables
+fn compact(steps: &[Step], root: usize) -> Vec<Step> {
+    let mut renumbered: HashMap<usize, usize> = HashMap::new();
+    let mut compacted = Vec::new();
+    let mut stack = vec![(root, false)];
+    while let Some((step, expanded)) = stack.pop() {
+        if renumbered.contains_key(&step) {
+            continue;
+        }
+        let args: Vec<usize> = match steps[step] {
+            Step::Var(_) | Step::Const(_) => vec![],
+            Step::Not(a) => vec![a],
+            Step::And(a, b) | Step::Or(a, b) | Step::Xor(a, b) | Step::Xnor(a, b) => vec![a, b],
+            Step::Mux(s, a, b) => vec![s, a, b],
+        };
+        if !expanded {
+            stack.push((step, true));
+            stack.extend(args.into_iter().map(|arg| (arg, false)));
+            continue;
+        }
+        let at = |arg: usize| renumbered[&arg];
+        compacted.push(match steps[step] {
+            Step::Not(a) => Step::Not(at(a)),
+            Step::And(a, b) => Step::And(at(a), at(b)),
+            Step::Or(a, b) => Step::Or(at(a), at(b)),
+            Step::Xor(a, b) => Step::Xor(at(a), at(b)),
+            Step::Xnor(a, b) => Step::Xnor(at(a), at(b)),
+            Step::Mux(s, a, b) => Step::Mux(at(s), at(a), at(b)),
+            leaf => leaf,
+        });
+        renumbered.insert(step, compacted.len() - 1);
+    }
+    compacted
+}
+
+// An implementation of `table` over `vars` variables, the root is the last step
+pub(crate) fn implementation(table: u64, vars: usize) -> Vec<Step> {
+    let mut steps: Vec<Step> = (0..4).map(|i| if i < vars { Step::Var(i) } else { Step::Const(false) }).collect();
+    if vars <= 4 {
+        let root = LIBRARY.implement(replicate(table, vars), &[0, 1, 2, 3], &mut steps);
+        return compact(&steps, root);
+    }
+    // Shannon expansion on the variable that gives the fewest gates
+    let mut best: Option<Vec<Step>> = None;
+    for var in 0..vars {
+        let (low, high) = (cofactor(table, vars, var, false), cofactor(table, vars, var, true));
+        let others: Vec<usize> = (0..vars).filter(|v| *v != var).collect();
+        let mut steps: Vec<Step> = (0..vars).map(Step::Var).collect();
+        let low_root = splice(&mut steps, &implementation(low, vars - 1), &others);
+        let root = if low == high {
+            low_root
+        } else {
+            let high_root = splice(&mut steps, &implementation(high, vars - 1), &others);
+            steps.push(Step::Mux(var, high_root, low_root));
+            steps.len() - 1
+        };
+        let candidate = compact(&steps, root);
+        let gates = |steps: &[Step]| steps.iter().filter(|step| step.is_gate()).count();
+        if best.as_ref().is_none_or(|best| gates(&candidate) < gates(best)) {
+            best = Some(candidate);
+        }
+    }
+    best.expect("at least one variable")
+}
+
+// Adds the steps to the egraph with `Var(i)` as `leaves[i]`, returns the class of the root.
+// The added nodes are recorded in `chosen` when given, to evaluate the new classes.
+pub(crate) fn insert(egraph: &mut PropEGraph, steps: &[Step], leaves: &[Id], mut chosen: Option<&mut HashMap<Id, Prop>>) -> Id {
+    let mut ids: Vec<Id> = Vec::with_capacity(steps.len());
+    for step in steps {
+        let node = match *step {
+            Step::Var(i) => {
+                ids.push(leaves[i]);
+                continue;
+            }
+            Step::Const(value) => Prop::Symbol(if value { "1" } else { "0" }.into()),
+            Step::Not(a) => Prop::Not(ids[a]),
+            Step::And(a, b) => Prop::And([ids[a], ids[b]]),
+            Step::Or(a, b) => Prop::Or([ids[a], ids[b]]),
+            Step::Xor(a, b) => Prop::Xor([ids[a], ids[b]]),
+            Step::Xnor(a, b) => Prop::Xnor([ids[a], ids[b]]),
+            Step::Mux(s, a, b) => Prop::Mux([ids[s], ids[a], ids[b]]),
+        };
+        let id = egraph.add(node.clone());
+        if let Some(chosen) = chosen.as_deref_mut() {
+            chosen.entry(id).or_insert(node);
+        }
+        ids.push(id);
+    }
+    *ids.last().expect("implementations are not empty")
+}
+
+// Unions every class with the library implementations of its cuts that save gates
+pub fn cut_rewrite(egraph: &mut PropEGraph, config: &CutConfig) -> CutStats {
+    let (order, _) = evaluation_order(egraph);
+    let cuts = enumerate_cuts(egraph, &order, &CutConfig { cut_size: config.cut_size.min(6), ..*config });
+    let mut stats = CutStats::default();
+    let mut npn_classes = HashSet::new();
+    let mut implementations: HashMap<(u64, usize), Vec<Step>> = HashMap::new();
+    for id in &order {
+        // the trivial cut has nothing to replace
+        for cut in cuts[id].iter().filter(|cut| cut.leaves != [*id]) {
+            stats.cuts += 1;
+            let vars = cut.leaves.len();
+            if vars <= 4 {
+                npn_classes.insert(LIBRARY.class(replicate(cut.table, vars)).canonical);
+            }
+            let steps = implementations.entry((cut.table, vars)).or_insert_with(|| implementation(cut.table, vars));
+            let gates = steps.iter().filter(|step| step.is_gate()).count();
+            if gates < cut.cone.len() || (config.zero_gain && gates == cut.cone.len()) {
+                let new = insert(egraph, steps, &cut.leaves, None);
+                if egraph.find(new) != egraph.find(*id) {
+                    egraph.union_trusted(*id, new, "npn");
+                    stats.inserted += 1;
+                }
+            }
+        }
+    }
+    egraph.rebuild();
+    stats.npn_classes = npn_classes.len();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::netlist::{assert_equivalent, parse_circuit};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // the table computed by `steps`, variable `i` is bit `i` of the assignment
+    fn eval_steps(steps: &[Step]) -> u64 {
+        let var = |i: usize| (0..64).filter(|m| m >> i & 1 == 1).fold(0u64, |word, m| word | 1 << m);
+        let mut values: Vec<u64> = Vec::with_capacity(steps.len());
+        for step in steps {
+            let value = match *step {
+                Step::Var(i) => var(i),
+                Step::Const(c) => 0u64.wrapping_sub(c as u64),
+                Step::Not(a) => !values[a],
+                Step::And(a, b) => values[a] & values[b],
+                Step::Or(a, b) => values[a] | values[b],
+                Step::Xor(a, b) => values[a] ^ values[b],
+                Step::Xnor(a, b) => !(values[a] ^ values[b]),
+                Step::Mux(s, a, b) => (values[s] & values[a]) | (!values[s] & values[b]),
+            };
+            values.push(value);
+        }
+        *values.last().unwrap()
+    }
+
+    #[test]
+    fn implementations_compute_their_table() {
+        for table in (0..1u64 << 16).step_by(7) {
+            assert_eq!(eval_steps(&implementation(table, 4)) & mask(4), table, "{:#06x}", table);
+        }
+        for table in 0..1u64 << 8 {
+            assert_eq!(eval_steps(&implementation(table, 3)) & mask(3), table, "{:#04x}", table);
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        for vars in [5, 6] {
+            for _ in 0..20 {
+                let table = rng.gen::<u64>() & mask(vars);
+                assert_eq!(eval_steps(&implementation(table, vars)) & mask(vars), table, "{:#x}", table);
+            }
+        }
+    }
+
+    // `f` is `a`, `g` an xor of and/or gates and `h` a majority of and/or gates
+    const CIRCUIT: &str = "INORDER = a b c d;\nOUTORDER = f g h;\nf = a * b + a * !b;\ng = (c + d) * !(c * d);\nh = a * b + a * c + b * c;\n";
+
+    #[test]
+    fn cones_are_unioned_with_smaller_implementations() {
+        let (netlist, mut egraph, roots) = parse_circuit(CIRCUIT);
+        let stats = cut_rewrite(&mut egraph, &CutConfig::default());
+        assert_equivalent(&netlist, &egraph, &roots);
+        assert!(stats.inserted >= 3 && stats.npn_classes > 0, "{:?}", stats);
+
+        let input = |name: &str| egraph.lookup(Prop::Symbol(name.into())).unwrap();
+        let [f, g, h] = [0, 1, 2].map(|i| egraph.find(roots[i].1));
+        assert_eq!(f, input("a"));
+        let (c, d) = (input("c"), input("d"));
+        assert!([Prop::Xor([c, d]), Prop::Xor([d, c]), Prop::Xnor([c, d]), Prop::Xnor([d, c])].into_iter().any(|node| egraph[g].nodes.contains(&node)), "{:?}", egraph[g].nodes);
+        assert!(egraph[h].nodes.len() > 1);
+    }
+
+    #[test]
+    fn cones_as_small_as_their_implementation_are_kept_without_zero_gain() {
+        let (netlist, mut egraph, roots) = parse_circuit("INORDER = a b c;\nOUTORDER = f;\nf = a * b * c;\n");
+        let stats = cut_rewrite(&mut egraph, &CutConfig::default());
+        assert_eq!(stats.inserted, 0);
+        let config = CutConfig { cut_size: 6, cuts_per_class: 16, zero_gain: true };
+        let stats = cut_rewrite(&mut egraph, &config);
+        assert!(stats.inserted > 0, "{:?}", stats);
+        assert_equivalent(&netlist, &egraph, &roots);
+    }
+}
@@ -0,0 +1,343 @@
+// Don't-care-aware resynthesis inside the egraph.
+//
+// The care set of a cut is computed by simulation: the assignments of its leaves that occur
+// (satisfiability don't cares are the ones that never occur) and, with observability don't cares,
+// only those where flipping the class changes one of the nodes using it. Inputs are simulated on
+// every assignment when there are few of them, otherwise on random patterns, and a care set on
+// the primary inputs can be supplied as a single-output circuit. The cheapest function of the
+// leaves that agrees with the cut on its care set is taken from the NPN library. When it is
+// cheaper than the cut, it is added and, once proven equal on the care set by exhaustive
+// simulation or SAT, unioned with the class, or with observability don't cares the users of the
+// class get a node with the new function in its place, unioned with the user.
+use crate::utils::cuts::{enumerate_cuts, implementation, insert, mask, replicate, CutConfig};
+use crate::utils::fraig::{assignment_words, cone, encode, evaluation_order, inputs, prove, simulate, Check, FraigConfig};
+use crate::utils::language::{Prop, PropEGraph};
+use crate::utils::netlist::{NetlistError, Pos};
+use crate::utils::npn::LIBRARY;
+use crate::utils::preprocess::{process_file, OutputOrder};
+use cec::sat::{Lit, Solver};
+use egg::{Id, Language, Symbol};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+
+// circuits with at most this many inputs are simulated on every assignment
+const EXHAUSTIVE_INPUTS: usize = 12;
+// cuts with more don't care assignments are left alone, every completion is tried
+const MAX_DONT_CARES: u32 = 12;
+
+// The input assignments that can occur: a circuit with one output that is 1 on them
+#[derive(Debug)]
+pub struct CareSet {
+    // the cone of the output, children first
+    order: Vec<Id>,
+    chosen: HashMap<Id, Prop>,
+    root: Id,
+    support: Vec<Symbol>,
+}
+
+impl CareSet {
+    // Reads the care set from a netlist file (eqn, BLIF, AIGER or Verilog) with a single output
+    pub fn from_file(file_name: &str) -> Result<CareSet, NetlistError> {
+        let circuit = process_file(file_name, OutputOrder::Declared)?;
+        if circuit.roots.len() != 1 {
+            return Err(NetlistError::Syntax {
+                pos: Pos::default(),
+                msg: format!("{} declares {} outputs, a care set has one", file_name, circuit.roots.len()),
+            });
+        }
+        let root = circuit.egraph.find(circuit.roots[0].1);
+        let (_, chosen) = evaluation_order(&circuit.egraph);
+        let order = cone(&chosen, &[root]);
+        let support = inputs(&chosen, &order);
+        Ok(CareSet { order, chosen, root, support })
+    }
+
+    pub fn support(&self) -> &[Symbol] {
+        &self.support
+    }
+
+    // the care bits of the patterns, `input` gives the words of an input
+    pub(crate) fn simulate(&self, words: usize, input: impl FnMut(Symbol) -> Vec<u64>) -> Vec<u64> {
+        simulate(&self.order, &self.chosen, words, input).remove(&self.root).expect("the root is simulated")
+    }
+
+    // a literal that is true on the care set, inputs are shared with the other circuits in the solver
+    pub(crate) fn encode(&self, solver: &mut Solver, inputs: &mut HashMap<Symbol, Lit>) -> Lit {
+        encode(solver, &self.chosen, &self.order, inputs)[&self.root]
+    }
+}
+
+pub struct DontCareConfig {
+    // cuts of at most this many leaves, the NPN library has functions of up to 4
+    pub cut_size: usize,
+    pub cuts_per_class: usize,
+    // also ignore the patterns where a class is not observed by the nodes using it
+    pub observability: bool,
+    // simulation words, proof limits and the care set on the inputs
+    pub check: FraigConfig,
+}
+
+impl Default for DontCareConfig {
+    fn default() -> Self {
+        DontCareConfig {
+            cut_size: 4,
+            cuts_per_class: 8,
+            observability: false,
+            check: FraigConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DontCareStats {
+    // cuts with don't care assignments of their leaves
+    pub cuts: usize,
+    // cheaper functions that were checked
+    pub candidates: usize,
+    pub proven: usize,
+    // disproven or undecided, the simulated care set was too small
+    pub rejected: usize,
+    pub sat_checks: usize,
+    pub inserted: usize,
+}
+
+// The patterns where flipping `id` changes one of its users, `None` if a user can not be simulated
+fn observability(id: Id, users: &[(Id, Prop)], chosen: &HashMap<Id, Prop>, signatures: &HashMap<Id, Vec<u64>>, words: usize) -> Option<Vec<u64>> {
+    let mut observed = vec![0u64; words];
+    for (class, node) in users {
+        if !chosen.contains_key(class) || node.children().iter().any(|child| !signatures.contains_key(child)) {
+            return None;
+        }
+        for (word, observed) in observed.iter_mut().enumerate() {
+            let value = |flip: bool| {
+                node.eval_bits(|child| {
+                    let bits = signatures[&child][word];
+                    if flip && child == id {
+                        !bits
+                    } else {
+                        bits
+                    }
+                })
+            };
+            *observed |= value(false)? ^ value(true)?;
+        }
+    }
+    Some(observed)
+}
+
+// The assignments of the leaves that occur in the patterns of `care`
+fn leaf_care(leaves: &[Id], signatures: &HashMap<Id, Vec<u64>>, care: &[u64]) -> u64 {
+    let mut table = 0u64;
+    for (word, care) in care.iter().enumerate() {
+        let mut bits = *care;
+        while bits != 0 {
+            let bit = bits.trailing_zeros();
+            bits &= bits - 1;
+            let m = leaves.iter().enumerate().fold(0usize, |m, (i, leaf)| m | ((signatures[leaf][word] >> bit & 1) as usize) << i);
+            table |= 1 << m;
+        }
+    }
+    table
+}
+
+// The cheapest table that agrees with `table` outside of `dont_care`, and its gates
+fn cheapest_completion(table: u64, dont_care: u64, vars: usize) -> (u64, usize) {
+    let fixed = table & !dont_care;
+    let mut best = (table, LIBRARY.gates(replicate(table, vars)));
+    // every subset of the don't cares, in increasing order
+    let mut subset = 0u64;
+    loop {
+        let gates = LIBRARY.gates(replicate(fixed | subset, vars));
+        if gates < best.1 {
+            best = (fixed | subset, gates);
+        }
+        if subset == dont_care {
+            break;
+        }
+        subset = subset.wrapping_sub(dont_care) & dont_care;
+    }
+    best
+}
+
+// Unions classes (or their users) with cheaper functions that are equal on their care sets.
+// `roots` are observed by the environment. The egraph is rebuilt afterwards.
+pub fn dont_care_rewrite(egraph: &mut PropEGraph, roots: &[Id], config: &DontCareConfig) -> DontCareStats {
+    let (order, mut chosen) = evaluation_order(egraph);
+    let names = inputs(&chosen, &order);
+    let exhaustive = names.len() <= EXHAUSTIVE_INPUTS;
+    let words = if exhaustive { (1usize << names.len()).div_ceil(64) } else { config.check.words.max(1) };
+    let mut rng = StdRng::seed_from_u64(config.check.seed);
+    let mut patterns: HashMap<Symbol, Vec<u64>> = names
+        .iter()
+        .enumerate()
+        .map(|(var, name)| (*name, if exhaustive { assignment_words(var, words) } else { (0..words).map(|_| rng.gen()).collect() }))
+        .collect();
+    let mut input = |name: Symbol| patterns.entry(name).or_insert_with(|| (0..words).map(|_| rng.gen()).collect()).clone();
+    let signatures = simulate(&order, &chosen, words, &mut input);
+    let mut valid = match &config.check.care {
+        Some(care) => care.simulate(words, input),
+        None => vec![u64::MAX; words],
+    };
+    if exhaustive && names.len() < 6 {
+        valid[0] &= (1u64 << (1 << names.len())) - 1;
+    }
+
+    let cut_config = CutConfig {
+        cut_size: config.cut_size.min(4),
+        cuts_per_class: config.cuts_per_class,
+        zero_gain: false,
+    };
+    let cuts = enumerate_cuts(egraph, &order, &cut_config);
+    let roots: HashSet<Id> = roots.iter().map(|id| egraph.find(*id)).collect();
+    let mut users: HashMap<Id, Vec<(Id, Prop)>> = HashMap::new();
+    for class in egraph.classes() {
+        for node in &class.nodes {
+            let mut children: Vec<Id> = node.children().iter().map(|child| egraph.find(*child)).collect();
+            children.sort_unstable();
+            children.dedup();
+            // nodes on a cycle through their own class do not observe it
+            for child in children.into_iter().filter(|child| *child != class.id) {
+                users.entry(child).or_default().push((class.id, node.clone()));
+            }
+        }
+    }
+
+    let mut stats = DontCareStats::default();
+    let mut proven: Vec<(Id, Id)> = Vec::new();
+    for id in &order {
+        let id = *id;
+        // the patterns the class matters on, with observability don't cares it is replaced in its users
+        let users: &[(Id, Prop)] = users.get(&id).map_or(&[], |users| users.as_slice());
+        let observed = if config.observability && !roots.contains(&id) {
+            observability(id, users, &chosen, &signatures, words)
+        } else {
+            None
+        };
+        let care: Vec<u64> = match &observed {
+            Some(observed) => observed.iter().zip(&valid).map(|(observed, valid)| observed & valid).collect(),
+            None => valid.clone(),
+        };
+        let replace = care != valid;
+        for cut in cuts[&id].iter().filter(|cut| cut.leaves != [id]) {
+            let vars = cut.leaves.len();
+            let dont_care = !leaf_care(&cut.leaves, &signatures, &care) & mask(vars);
+            if dont_care == 0 || dont_care.count_ones() > MAX_DONT_CARES {
+                continue;
+            }
+            stats.cuts += 1;
+            let (table, gates) = cheapest_completion(cut.table, dont_care, vars);
+            if gates >= LIBRARY.gates(replicate(cut.table, vars)).min(cut.cone.len()) {
+                continue;
+            }
+            stats.candidates += 1;
+            let steps = implementation(table, vars);
+            let new = insert(egraph, &steps, &cut.leaves, Some(&mut chosen));
+            // the class itself, or every user with the new function in place of the class
+            let pairs: Vec<(Id, Id)> = if replace {
+                users
+                    .iter()
+                    .map(|(class, node)| {
+                        let node = node.clone().map_children(|child| if child == id { new } else { child });
+                        let user = egraph.add(node.clone());
+                        chosen.entry(user).or_insert(node);
+                        (*class, user)
+                    })
+                    .collect()
+            } else {
+                vec![(id, new)]
+            };
+            for (a, b) in pairs {
+                match prove(&chosen, a, b, &config.check, &mut stats.sat_checks) {
+                    Check::Equal => proven.push((a, b)),
+                    Check::Different | Check::Unknown => stats.rejected += 1,
+                }
+            }
+        }
+    }
+
+    stats.proven = proven.len();
+    for (a, b) in proven {
+        if egraph.find(a) != egraph.find(b) {
+            egraph.union_trusted(a, b, "dont-care");
+            stats.inserted += 1;
+        }
+    }
+    egraph.rebuild();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::netlist::{assert_equivalent, parse_circuit};
+    use std::sync::Arc;
+    use std::{env, fs};
+
+    // the rewritten egraph and the class of every output
+    fn rewrite(circuit: &str, config: &DontCareConfig, equivalent: bool) -> (PropEGraph, HashMap<String, Id>, DontCareStats) {
+        let (netlist, mut egraph, roots) = parse_circuit(circuit);
+        let ids: Vec<Id> = roots.iter().map(|(_, id)| *id).collect();
+        let stats = dont_care_rewrite(&mut egraph, &ids, config);
+        if equivalent {
+            assert_equivalent(&netlist, &egraph, &roots);
+        }
+        let outputs = roots.into_iter().map(|(name, id)| (name, egraph.find(id))).collect();
+        (egraph, outputs, stats)
+    }
+
+    fn class(egraph: &PropEGraph, expr: &str) -> Id {
+        egraph.lookup_expr(&expr.parse().unwrap()).unwrap_or_else(|| panic!("{} is not in the egraph", expr))
+    }
+
+    #[test]
+    fn satisfiability_dont_cares_union_a_class_with_a_cheaper_function() {
+        // `t = 1, u = 0` never occurs, so `f` is `t`
+        let (egraph, outputs, stats) = rewrite("INORDER = a b;\nOUTORDER = f;\nt = a * b;\nu = a + b;\nf = t * u;\n", &DontCareConfig::default(), true);
+        assert_eq!(outputs["f"], class(&egraph, "(* a b)"));
+        assert!(stats.inserted > 0 && stats.rejected == 0, "{:?}", stats);
+    }
+
+    #[test]
+    fn observability_dont_cares_replace_a_class_in_its_users() {
+        // `x` is only observed when `a` is 1, where it is `b + c`
+        let config = DontCareConfig { observability: true, ..Default::default() };
+        let (egraph, outputs, stats) = rewrite("INORDER = a b c d;\nOUTORDER = f g;\nx = a * b + c;\nf = a * x;\ng = f + d;\n", &config, true);
+        assert!(stats.inserted > 0, "{:?}", stats);
+        let or = ["(+ b c)", "(+ c b)"].iter().find_map(|expr| egraph.lookup_expr(&expr.parse().unwrap())).expect("b + c is added");
+        assert!(egraph[outputs["f"]].nodes.iter().any(|node| matches!(node, Prop::And(children) if children.contains(&or))));
+        // `x` itself is not `b + c`
+        assert_ne!(class(&egraph, "(+ (* a b) c)"), or);
+    }
+
+    #[test]
+    fn unions_that_only_hold_on_the_care_set_need_it() {
+        // `f` is `b` on the care set `a`
+        const CIRCUIT: &str = "INORDER = a b;\nOUTORDER = f;\nf = a * b;\n";
+        let (egraph, outputs, stats) = rewrite(CIRCUIT, &DontCareConfig::default(), true);
+        assert_ne!(outputs["f"], class(&egraph, "b"));
+        assert_eq!(stats.inserted, 0);
+
+        let path = env::temp_dir().join(format!("dont-care-{}.eqn", std::process::id()));
+        fs::write(&path, "INORDER = a;\nOUTORDER = care;\ncare = a;\n").unwrap();
+        let care = CareSet::from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        let care = Arc::new(care.unwrap());
+        assert_eq!(care.support(), [Symbol::from("a")]);
+        for exhaustive_support in [16, 0] {
+            let check = FraigConfig { care: Some(care.clone()), exhaustive_support, ..Default::default() };
+            let (egraph, outputs, stats) = rewrite(CIRCUIT, &DontCareConfig { check, ..Default::default() }, false);
+            assert_eq!(outputs["f"], class(&egraph, "b"), "{:?}", stats);
+            assert_eq!(stats.sat_checks > 0, exhaustive_support == 0);
+        }
+    }
+
+    #[test]
+    fn care_sets_have_one_output() {
+        let path = env::temp_dir().join(format!("dont-care-outputs-{}.eqn", std::process::id()));
+        fs::write(&path, "INORDER = a b;\nOUTORDER = x y;\nx = a;\ny = b;\n").unwrap();
+        let care = CareSet::from_file(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(care, Err(NetlistError::Syntax { .. })));
+    }
+}
@@ -0,0 +1,342 @@
+// Functional reduction of the egraph (FRAIGing inside the egraph).
+//
+// Every class is simulated bit-parallel on random input patterns through one of its nodes whose
+// children are simulated before it, all nodes of a class compute the same function. Classes with
+// the same signature are candidate equivalences. A candidate is proven by simulating all input
+// assignments when the support of both cones is small, otherwise with a SAT check on the miter of
+// the two cones, and proven classes are unioned. Candidates that are disproven are compared among
+// themselves, those the SAT solver gives up on are left alone. With a care set, signatures and
+// proofs only consider the input assignments of the care set.
+use crate::utils::dont_care::CareSet;
+use crate::utils::language::{Prop, PropEGraph};
+use cec::sat::{Lit, SatResult, Solver};
+use cec::sim::assignment_word;
+use egg::{Id, Language, Symbol};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+pub struct FraigConfig {
+    // 64 random patterns per word
+    pub words: usize,
+    // candidates with at most this many inputs are checked by exhaustive simulation
+    pub exhaustive_support: usize,
+    // conflicts before a SAT check gives up
+    pub conflict_limit: u64,
+    pub seed: u64,
+    // classes only need to agree on these input assignments
+    pub care: Option<Arc<CareSet>>,
+}
+
+impl Default for FraigConfig {
+    fn default() -> Self {
+        FraigConfig {
+            words: 4,
+            exhaustive_support: 16,
+            conflict_limit: 1000,
+            seed: 0,
+            care: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FraigStats {
+    pub classes: usize,
+    // pairs of classes with equal signatures that were checked
+    pub candidates: usize,
+    pub proven: usize,
+    pub disproven: usize,
+    pub undecided: usize,
+    pub sat_checks: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Check {
+    Equal,
+    Different,
+    Unknown,
+}
+
+fn is_constant(node: &Prop) -> bool {
+    matches!(node, Prop::Bool(_)) || matches!(node, Prop::Symbol(name) if name.as_str() == "0" || name.as_str() == "1")
+}
+
+// One node per class whose children's classes come before it, in that order. Classes that only
+// have let/&/root nodes or only nodes on cycles are left out.
+pub(crate) fn evaluation_order(egraph: &PropEGraph) -> (Vec<Id>, HashMap<Id, Prop>) {
+    let mut pending: HashMap<(Id, usize), usize> = HashMap::new();
+    let mut users: HashMap<Id, Vec<(Id, usize)>> = HashMap::new();
+    let mut ready: Vec<(Id, Prop)> = Vec::new();
+    for class in egraph.classes() {
+        if let Some(value) = class.data {
+            ready.push((class.id, Prop::Symbol(if value { "1" } else { "0" }.into())));
+            continue;
+        }
+        for (index, node) in class.nodes.iter().enumerate() {
+            if matches!(node, Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_)) {
+                continue;
+            }
+            let mut children: Vec<Id> = node.children().iter().map(|child| egraph.find(*child)).collect();
+            children.sort_unstable();
+            children.dedup();
+            if children.is_empty() {
+                ready.push((class.id, node.clone()));
+                continue;
+            }
+            pending.insert((class.id, index), children.len());
+            for child in children {
+                users.entry(child).or_default().push((class.id, index));
+            }
+        }
+    }
+
+    let mut order = Vec::new();
+    let mut chosen: HashMap<Id, Prop> = HashMap::new();
+    while let Some((id, node)) = ready.pop() {
+        if chosen.contains_key(&id) {
+            continue;
+        }
+        chosen.insert(id, node.map_children(|child| egraph.find(child)));
+        order.push(id);
+        for (user, index) in users.get(&id).into_iter().flatten() {
+            let count = pending.get_mut(&(*user, *index)).unwrap();
+            *count -= 1;
+            if *count == 0 && !chosen.contains_key(user) {
+                ready.push((*user, egraph[*user].nodes[*index].clone()));
+            }
+        }
+    }
+    (order, chosen)
+}
+
+// Simulates the classes of `order` (children first), `input` gives the words of an input
+pub(crate) fn simulate(order: &[Id], chosen: &HashMap<Id, Prop>, words: usize, mut input: impl FnMut(Symbol) -> Vec<u64>) -> HashMap<Id, Vec<u64>> {
+    let mut signatures: HashMap<Id, Vec<u64>> = HashMap::with_capacity(order.len());
+    for id in order {
+        let node = &chosen[id];
+        let signature = match node {
+            Prop::Symbol(name) if !is_constant(node) => input(*name),
+            _ => (0..words).map(|word| node.eval_bits(|child| signatures[&child][word]).expect("simulated nodes are operators")).collect(),
+        };
+        signatures.insert(*id, signature);
+    }
+    signatures
+}
+
+// the classes of the cones of `roots`, children first
+pub(crate) fn cone(chosen: &HashMap<Id, Prop>, roots: &[Id]) -> Vec<Id> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(Id, bool)> = roots.iter().map(|id| (*id, false)).collect();
+    while let Some((id, expanded)) = stack.pop() {
+        if expanded {
+            order.push(id);
+        } else if seen.insert(id) {
+            stack.push((id, true));
+            stack.extend(chosen[&id].children().iter().map(|child| (*child, false)));
+        }
+    }
+    order
+}
+
+pub(crate) fn inputs(chosen: &HashMap<Id, Prop>, cone: &[Id]) -> Vec<Symbol> {
+    let mut names: Vec<Symbol> = cone
+        .iter()
+        .filter_map(|id| match &chosen[id] {
+            node @ Prop::Symbol(name) if !is_constant(node) => Some(*name),
+            _ => None,
+        })
+        .collect();
+    names.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+    names.dedup();
+    names
+}
+
+// the words of input `var` when simulating every assignment
+pub(crate) fn assignment_words(var: usize, words: usize) -> Vec<u64> {
+    (0..words).map(|word| assignment_word(var, word)).collect()
+}
+
+// simulates every assignment of the inputs
+fn check_exhaustive(chosen: &HashMap<Id, Prop>, cone: &[Id], support: &[Symbol], a: Id, b: Id, care: Option<&CareSet>) -> Check {
+    let rows = 1usize << support.len();
+    let words = rows.div_ceil(64);
+    let assignments: HashMap<Symbol, Vec<u64>> = support.iter().enumerate().map(|(var, name)| (*name, assignment_words(var, words))).collect();
+    let signatures = simulate(cone, chosen, words, |name| assignments[&name].clone());
+    let care = match care {
+        Some(care) => care.simulate(words, |name| assignments[&name].clone()),
+        None => vec![u64::MAX; words],
+    };
+    let mask = if rows >= 64 { u64::MAX } else { (1u64 << rows) - 1 };
+    let differ = signatures[&a].iter().zip(&signatures[&b]).zip(&care).any(|((x, y), care)| (x ^ y) & care & mask != 0);
+    if differ {
+        Check::Different
+    } else {
+        Check::Equal
+    }
+}
+
+// the output literal of every class of `cone` (children first)
+pub(crate) fn encode(solver: &mut Solver, chosen: &HashMap<Id, Prop>, cone: &[Id], inputs: &mut HashMap<Symbol, Lit>) -> HashMap<Id, Lit> {
+    let mut lits: HashMap<Id, Lit> = HashMap::with_capacity(cone.len());
+    for id in cone {
+        let node = &chosen[id];
+        let x = |i: usize| lits[&node.children()[i]];
+        let lit = match node {
+            Prop::Bool(value) => solver.constant(*value),
+            Prop::Symbol(name) if is_constant(node) => solver.constant(name.as_str() == "1"),
+            Prop::Symbol(name) => *inputs.entry(*name).or_insert_with(|| solver.new_lit()),
+            Prop::Not(_) => !x(0),
+            Prop::And(_) => solver.and(x(0), x(1)),
+            Prop::Or(_) => solver.or(x(0), x(1)),
+            Prop::Implies(_) => solver.or(!x(0), x(1)),
+            Prop::Xor(_) => solver.xor(x(0), x(1)),
+            Prop::Xnor(_) => !solver.xor(x(0), x(1)),
+            Prop::Mux(_) => solver.mux(x(0), x(1), x(2)),
+            Prop::Maj(_) => solver.maj(x(0), x(1), x(2)),
+            Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_) => unreachable!("structural nodes are never simulated"),
+        };
+        lits.insert(*id, lit);
+    }
+    lits
+}
+
+// the two classes differ iff their miter is satisfiable (on the care set)
+fn check_sat(chosen: &HashMap<Id, Prop>, cone: &[Id], a: Id, b: Id, care: Option<&CareSet>, conflict_limit: u64) -> Check {
+    let mut solver = Solver::new();
+    let mut inputs = HashMap::new();
+    let lits = encode(&mut solver, chosen, cone, &mut inputs);
+    let mut assumptions = vec![solver.xor(lits[&a], lits[&b])];
+    if let Some(care) = care {
+        assumptions.push(care.encode(&mut solver, &mut inputs));
+    }
+    match solver.solve(&assumptions, Some(conflict_limit)) {
+        SatResult::Unsat => Check::Equal,
+        SatResult::Sat => Check::Different,
+        SatResult::Unknown => Check::Unknown,
+    }
+}
+
+// Checks that two classes of `chosen` are equal on the care set, by exhaustive simulation when
+// they depend on few inputs and with SAT otherwise, `sat_checks` counts the SAT checks
+pub(crate) fn prove(chosen: &HashMap<Id, Prop>, a: Id, b: Id, config: &FraigConfig, sat_checks: &mut usize) -> Check {
+    let cone = cone(chosen, &[a, b]);
+    let mut support = inputs(chosen, &cone);
+    if let Some(care) = &config.care {
+        support.extend_from_slice(care.support());
+        support.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+        support.dedup();
+    }
+    if support.len() <= config.exhaustive_support {
+        check_exhaustive(chosen, &cone, &support, a, b, config.care.as_deref())
+    } else {
+        *sat_checks += 1;
+        check_sat(chosen, &cone, a, b, config.care.as_deref(), config.conflict_limit)
+    }
+}
+
+// Unions every pair of classes proven equivalent, the egraph is rebuilt afterwards
+pub fn fraig(egraph: &mut PropEGraph, config: &FraigConfig) -> FraigStats {
+    let (mut order, mut chosen) = evaluation_order(egraph);
+    let words = config.words.max(1);
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut patterns: HashMap<Symbol, Vec<u64>> = HashMap::new();
+    let mut input = |name: Symbol| patterns.entry(name).or_insert_with(|| (0..words).map(|_| rng.gen()).collect()).clone();
+    let mut signatures = simulate(&order, &chosen, words, &mut input);
+    // patterns outside of the care set are ignored
+    let care = match &config.care {
+        Some(care) => care.simulate(words, input),
+        None => vec![u64::MAX; words],
+    };
+    for signature in signatures.values_mut() {
+        signature.iter_mut().zip(&care).for_each(|(w, care)| *w &= care);
+    }
+
+    // classes that look constant are compared with the constant classes
+    for value in [false, true] {
+        let constant: Vec<u64> = care.iter().map(|care| care & 0u64.wrapping_sub(value as u64)).collect();
+        if signatures.values().any(|signature| *signature == constant) {
+            let id = egraph.add(Prop::Symbol(if value { "1" } else { "0" }.into()));
+            if let std::collections::hash_map::Entry::Vacant(entry) = chosen.entry(id) {
+                entry.insert(Prop::Symbol(if value { "1" } else { "0" }.into()));
+                order.insert(0, id);
+                signatures.insert(id, constant);
+            }
+        }
+    }
+
+    // candidate groups, each in evaluation order with constants first
+    let position: HashMap<Id, usize> = order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let mut groups: HashMap<&[u64], Vec<Id>> = HashMap::new();
+    for id in &order {
+        groups.entry(signatures[id].as_slice()).or_default().push(*id);
+    }
+    let mut groups: Vec<Vec<Id>> = groups.into_values().filter(|group| group.len() > 1).collect();
+    groups.sort_by_key(|group| position[&group[0]]);
+
+    let mut stats = FraigStats { classes: order.len(), ..Default::default() };
+    let mut proven: Vec<(Id, Id)> = Vec::new();
+    for mut group in groups {
+        while group.len() > 1 {
+            let representative = group[0];
+            let mut rest = Vec::new();
+            for id in &group[1..] {
+                stats.candidates += 1;
+                match prove(&chosen, representative, *id, config, &mut stats.sat_checks) {
+                    Check::Equal => proven.push((representative, *id)),
+                    Check::Different => rest.push(*id),
+                    Check::Unknown => stats.undecided += 1,
+                }
+            }
+            stats.disproven += rest.len();
+            group = rest;
+        }
+    }
+
+    stats.proven = proven.len();
+    for (representative, id) in proven {
+        egraph.union_trusted(representative, id, "fraig");
+    }
+    egraph.rebuild();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::netlist::{assert_equivalent, parse_circuit};
+
+    // `f` and `g`, `h` and `x`, `k` and 0 compute the same function
+    const CIRCUIT: &str = "INORDER = a b c;\nOUTORDER = f g h x k;\nf = a * b + a * c;\ng = a * (b + c);\nh = a * !b + !a * b;\nx = (a + b) * !(a * b);\nk = a * !a * c;\n";
+
+    fn run(config: &FraigConfig) -> FraigStats {
+        let (netlist, mut egraph, roots) = parse_circuit(CIRCUIT);
+        let stats = fraig(&mut egraph, config);
+        assert_equivalent(&netlist, &egraph, &roots);
+        let class = |name: &str| egraph.find(roots.iter().find(|(root, _)| root == name).unwrap().1);
+        assert_eq!(class("f"), class("g"));
+        assert_eq!(class("h"), class("x"));
+        assert_eq!(Some(class("k")), egraph.lookup(Prop::Symbol("0".into())));
+        assert_ne!(class("f"), class("h"));
+        let inputs: HashSet<Id> = ["a", "b", "c"].iter().map(|name| egraph.lookup(Prop::Symbol((*name).into())).unwrap()).collect();
+        assert_eq!(inputs.len(), 3);
+        stats
+    }
+
+    #[test]
+    fn simulation_merges_the_equal_outputs() {
+        let stats = run(&FraigConfig::default());
+        assert!(stats.proven >= 3, "{:?}", stats);
+        assert_eq!(stats.sat_checks, 0);
+        assert_eq!(stats.undecided, 0);
+    }
+
+    #[test]
+    fn sat_merges_the_equal_outputs() {
+        let stats = run(&FraigConfig { exhaustive_support: 0, ..Default::default() });
+        assert!(stats.proven >= 3, "{:?}", stats);
+        assert_eq!(stats.sat_checks, stats.candidates);
+    }
+}
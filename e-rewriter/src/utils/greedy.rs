@@ -0,0 +1,349 @@
+// Greedy destructive rewriting: only rewrites that strictly reduce a cost are applied, and the
+// circuit is rebuilt after every pass so replaced nodes are dropped instead of being kept next
+// to their rewritten form as in equality saturation. A fast baseline for circuits too large to
+// saturate, and a cheap preprocessing step before saturation.
+//
+// The term DAG is kept as an egraph with one node per class (constant folded classes become
+// constants), so the rules are matched with egg's pattern matcher as usual. With `Size` a match
+// gains the `wight_size` op cost of the nodes it frees (nodes of the left-hand side used by nothing
+// else) minus the cost of the right-hand side nodes that are not in the DAG yet. With `Depth` it
+// gains the difference of the `wight_depth` arrival times of both sides. A pass that makes the
+// whole circuit worse is undone and ends the rewriting.
+use crate::utils::cost::{wight_depth, wight_size};
+use crate::utils::language::{ConstantFold, Prop, PropEGraph};
+use egg::{CostFunction, ENodeOrVar, Id, Language, PatternAst, Rewrite, Subst};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GreedyCost {
+    Size,
+    Depth,
+}
+
+#[derive(Debug, Error)]
+pub enum GreedyError {
+    #[error("the term DAG has a cycle through class {0}")]
+    Cycle(Id),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GreedyStats {
+    pub passes: usize,
+    pub rewrites: usize,
+    pub cost_before: usize,
+    pub cost_after: usize,
+}
+
+// the node standing for a class of the term DAG
+pub(crate) fn representative(egraph: &PropEGraph, id: Id) -> Prop {
+    match egraph[id].data {
+        Some(value) => Prop::Symbol(if value { "1" } else { "0" }.into()),
+        None => egraph[id].nodes[0].clone(),
+    }
+}
+
+// classes reachable from `starts` through their representatives, children before parents
+fn topological_order(egraph: &PropEGraph, starts: impl IntoIterator<Item = Id>) -> Vec<Id> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(Id, bool)> = starts.into_iter().map(|id| (egraph.find(id), false)).collect();
+    while let Some((id, expanded)) = stack.pop() {
+        if expanded {
+            order.push(id);
+        } else if seen.insert(id) {
+            stack.push((id, true));
+            for child in representative(egraph, id).children() {
+                stack.push((egraph.find(*child), false));
+            }
+        }
+    }
+    order
+}
+
+fn arrival_times(egraph: &PropEGraph) -> HashMap<Id, usize> {
+    let mut arrival = HashMap::new();
+    for id in topological_order(egraph, egraph.classes().map(|class| class.id)) {
+        let time = wight_depth.cost(&representative(egraph, id), |child| arrival[&egraph.find(child)]);
+        arrival.insert(id, time);
+    }
+    arrival
+}
+
+// the cost of the circuit: the area of the DAG or the arrival time of the slowest output
+fn circuit_cost(egraph: &PropEGraph, roots: &[Id], cost: GreedyCost) -> usize {
+    match cost {
+        GreedyCost::Size => topological_order(egraph, roots.iter().copied())
+            .into_iter()
+            .map(|id| wight_size.cost(&representative(egraph, id), |_| 0))
+            .sum(),
+        GreedyCost::Depth => {
+            let arrival = arrival_times(egraph);
+            roots.iter().map(|id| arrival[&egraph.find(*id)]).max().unwrap_or(0)
+        }
+    }
+}
+
+// the `wight_depth` arrival time of a side of a rule instantiated with `subst`
+fn arrival_of(egraph: &PropEGraph, pattern: &PatternAst<Prop>, subst: &Subst, arrival: &HashMap<Id, usize>) -> usize {
+    let mut values: Vec<usize> = Vec::with_capacity(pattern.as_ref().len());
+    for node in pattern.as_ref() {
+        let value = match node {
+            ENodeOrVar::Var(var) => arrival[&egraph.find(subst[*var])],
+            ENodeOrVar::ENode(node) => wight_depth.cost(node, |child| values[usize::from(child)]),
+        };
+        values.push(value);
+    }
+    values.last().copied().unwrap_or(0)
+}
+
+// the classes of the nodes of a side of a rule instantiated with `subst`, `None` for new nodes
+fn instance_classes(egraph: &PropEGraph, pattern: &PatternAst<Prop>, subst: &Subst) -> Vec<Option<Id>> {
+    let mut classes: Vec<Option<Id>> = Vec::with_capacity(pattern.as_ref().len());
+    for node in pattern.as_ref() {
+        let class = match node {
+            ENodeOrVar::Var(var) => Some(egraph.find(subst[*var])),
+            ENodeOrVar::ENode(node) => {
+                let children: Option<Vec<Id>> = node.children().iter().map(|child| classes[usize::from(*child)]).collect();
+                children.and_then(|children| {
+                    let mut children = children.into_iter();
+                    egraph.lookup(node.clone().map_children(|_| children.next().unwrap()))
+                })
+            }
+        };
+        classes.push(class);
+    }
+    classes
+}
+
+// the op cost freed by replacing the left-hand side minus the op cost of the new right-hand side nodes
+fn size_gain(egraph: &PropEGraph, lhs: &PatternAst<Prop>, rhs: &PatternAst<Prop>, subst: &Subst, refs: &HashMap<Id, usize>) -> Option<isize> {
+    let lhs_classes = instance_classes(egraph, lhs, subst);
+    // parents come after their children, so walking backwards frees from the root down
+    let mut freed: HashSet<Id> = HashSet::from([(*lhs_classes.last()?)?]);
+    let mut remaining: HashMap<Id, usize> = HashMap::new();
+    let mut saved = 0;
+    for (node, class) in lhs.as_ref().iter().zip(&lhs_classes).rev() {
+        let (ENodeOrVar::ENode(node), Some(class)) = (node, class) else { continue };
+        if !freed.contains(class) {
+            continue;
+        }
+        saved += wight_size.cost(node, |_| 0);
+        for child in node.children() {
+            if let (ENodeOrVar::ENode(_), Some(child)) = (&lhs[*child], lhs_classes[usize::from(*child)]) {
+                let count = remaining.entry(child).or_insert_with(|| refs.get(&child).copied().unwrap_or(0));
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    freed.insert(child);
+                }
+            }
+        }
+    }
+    let added: usize = rhs
+        .as_ref()
+        .iter()
+        .zip(instance_classes(egraph, rhs, subst))
+        .filter(|(_, class)| class.is_none_or(|class| freed.contains(&class)))
+        .map(|(node, _)| match node {
+            ENodeOrVar::ENode(node) => wight_size.cost(node, |_| 0),
+            ENodeOrVar::Var(_) => 0,
+        })
+        .sum();
+    Some(saved as isize - added as isize)
+}
+
+// how often each reachable class is used by the representatives and the roots
+fn reference_counts(egraph: &PropEGraph, roots: &[Id]) -> HashMap<Id, usize> {
+    let mut refs: HashMap<Id, usize> = HashMap::new();
+    for id in topological_order(egraph, roots.iter().copied()) {
+        for child in representative(egraph, id).children() {
+            *refs.entry(egraph.find(*child)).or_insert(0) += 1;
+        }
+    }
+    for root in roots {
+        *refs.entry(egraph.find(*root)).or_insert(0) += 1;
+    }
+    refs
+}
+
+// a rewrite chosen for a class: the right-hand side and the substitution of its variables
+struct Chosen<'a> {
+    gain: isize,
+    rhs: &'a PatternAst<Prop>,
+    subst: Subst,
+}
+
+// Rebuilds the term DAG from the roots, classes with a chosen rewrite are replaced by its
+// right-hand side. Also returns how many rewrites are used, some replace unreachable classes.
+// Fails when a right-hand side uses a class that depends on the rewritten one.
+fn rebuild_dag(egraph: &PropEGraph, roots: &[Id], chosen: &HashMap<Id, Chosen>) -> Result<(PropEGraph, Vec<Id>, usize), GreedyError> {
+    let mut used = 0;
+    let mut dag = PropEGraph::default();
+    let mut built: HashMap<Id, Id> = HashMap::new();
+    let mut visiting = HashSet::new();
+    let dependencies = |id: Id| -> Vec<Id> {
+        match chosen.get(&id) {
+            Some(rewrite) => rewrite.rhs.as_ref().iter().filter_map(|node| match node {
+                ENodeOrVar::Var(var) => Some(egraph.find(rewrite.subst[*var])),
+                ENodeOrVar::ENode(_) => None,
+            }).collect(),
+            None => representative(egraph, id).children().iter().map(|child| egraph.find(*child)).collect(),
+        }
+    };
+
+    let mut stack: Vec<(Id, bool)> = roots.iter().map(|id| (egraph.find(*id), false)).collect();
+    while let Some((id, expanded)) = stack.pop() {
+        if built.contains_key(&id) {
+            continue;
+        }
+        if !expanded {
+            if !visiting.insert(id) {
+                return Err(GreedyError::Cycle(id));
+            }
+            stack.push((id, true));
+            stack.extend(dependencies(id).into_iter().filter(|dep| !built.contains_key(dep)).map(|dep| (dep, false)));
+            continue;
+        }
+        let new_id = match chosen.get(&id) {
+            Some(rewrite) => {
+                used += 1;
+                let mut ids: Vec<Id> = Vec::with_capacity(rewrite.rhs.as_ref().len());
+                for node in rewrite.rhs.as_ref() {
+                    let new_id = match node {
+                        ENodeOrVar::Var(var) => built[&egraph.find(rewrite.subst[*var])],
+                        ENodeOrVar::ENode(node) => dag.add(node.clone().map_children(|child| ids[usize::from(child)])),
+                    };
+                    ids.push(new_id);
+                }
+                *ids.last().unwrap()
+            }
+            None => dag.add(representative(egraph, id).map_children(|child| built[&egraph.find(child)])),
+        };
+        built.insert(id, new_id);
+    }
+    dag.rebuild();
+    let new_roots = roots.iter().map(|id| dag.find(built[&egraph.find(*id)])).collect();
+    Ok((dag, new_roots, used))
+}
+
+// a rule with both sides as patterns
+type CostedRule<'a> = (&'a Rewrite<Prop, ConstantFold>, &'a PatternAst<Prop>, &'a PatternAst<Prop>);
+
+// Applies rules that strictly reduce `cost` until no rule does or `max_passes` passes ran
+pub fn greedy_rewrite(
+    egraph: &PropEGraph,
+    roots: &[Id],
+    rules: &[Rewrite<Prop, ConstantFold>],
+    cost: GreedyCost,
+    max_passes: usize,
+) -> Result<(PropEGraph, Vec<Id>, GreedyStats), GreedyError> {
+    // only pattern rules can be costed
+    let rules: Vec<CostedRule> = rules
+        .iter()
+        .filter_map(|rule| Some((rule, rule.searcher.get_pattern_ast()?, rule.applier.get_pattern_ast()?)))
+        .collect();
+
+    let mut stats = GreedyStats {
+        cost_before: circuit_cost(egraph, roots, cost),
+        ..Default::default()
+    };
+    let (mut dag, mut roots, _) = rebuild_dag(egraph, roots, &HashMap::new())?;
+    while stats.passes < max_passes {
+        let (arrival, refs) = match cost {
+            GreedyCost::Size => (HashMap::new(), reference_counts(&dag, &roots)),
+            GreedyCost::Depth => (arrival_times(&dag), HashMap::new()),
+        };
+        let mut chosen: HashMap<Id, Chosen> = HashMap::new();
+        for (rule, lhs, rhs) in &rules {
+            for matches in rule.search(&dag) {
+                let id = dag.find(matches.eclass);
+                if dag[id].data.is_some() {
+                    continue;
+                }
+                for subst in matches.substs {
+                    let gain = match cost {
+                        GreedyCost::Size => size_gain(&dag, lhs, rhs, &subst, &refs).unwrap_or(0),
+                        GreedyCost::Depth => arrival_of(&dag, lhs, &subst, &arrival) as isize - arrival_of(&dag, rhs, &subst, &arrival) as isize,
+                    };
+                    if gain > chosen.get(&id).map_or(0, |best| best.gain) {
+                        chosen.insert(id, Chosen { gain, rhs, subst });
+                    }
+                }
+            }
+        }
+        if chosen.is_empty() {
+            break;
+        }
+        // rewrites of one pass can overlap, so their gains are only estimates and together
+        // they can even make a class use itself, such a pass is undone like a worse one
+        let Ok((next, next_roots, used)) = rebuild_dag(&dag, &roots, &chosen) else {
+            break;
+        };
+        if circuit_cost(&next, &next_roots, cost) > circuit_cost(&dag, &roots, cost) {
+            break;
+        }
+        (dag, roots) = (next, next_roots);
+        stats.passes += 1;
+        stats.rewrites += used;
+    }
+    stats.cost_after = circuit_cost(&dag, &roots, cost);
+    Ok((dag, roots, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::language::make_rules;
+    use crate::utils::netlist::{assert_equivalent, parse_circuit};
+
+    // the rewritten circuit, checked against the parsed one
+    fn rewrite(circuit: &str, cost: GreedyCost) -> (PropEGraph, Vec<Id>, GreedyStats) {
+        let (netlist, egraph, roots) = parse_circuit(circuit);
+        let ids: Vec<Id> = roots.iter().map(|(_, id)| *id).collect();
+        let (dag, ids, stats) = greedy_rewrite(&egraph, &ids, &make_rules(), cost, 10).unwrap();
+        let rewritten: Vec<(String, Id)> = roots.into_iter().map(|(name, _)| name).zip(ids.iter().copied()).collect();
+        assert_equivalent(&netlist, &dag, &rewritten);
+        (dag, ids, stats)
+    }
+
+    #[test]
+    fn size_rewriting_replaces_redundant_logic_and_keeps_one_node_per_class() {
+        let (dag, roots, stats) = rewrite("INORDER = a b c;\nOUTORDER = f g h;\nf = a * (a + b);\ng = !(!c);\nh = c + b * !b;\n", GreedyCost::Size);
+        for (root, input) in roots.iter().zip(["a", "c", "c"]) {
+            assert_eq!(Some(dag.find(*root)), dag.lookup(Prop::Symbol(input.into())), "{} is not rewritten to an input", input);
+        }
+        assert!(stats.cost_after < stats.cost_before && stats.rewrites >= 3, "{:?}", stats);
+        // destructive rewriting, the replaced nodes are gone
+        assert!(dag.classes().all(|class| class.nodes.len() == 1 || class.data.is_some()));
+        assert!(dag.lookup_expr(&"(+ a b)".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn depth_rewriting_shortens_a_chain() {
+        let (dag, roots, stats) = rewrite("INORDER = a b c d e f g h;\nOUTORDER = y;\ny = ((((((a * b) * c) * d) * e) * f) * g) * h;\n", GreedyCost::Depth);
+        assert!(stats.cost_after < stats.cost_before, "{:?}", stats);
+        assert_eq!(circuit_cost(&dag, &roots, GreedyCost::Depth), stats.cost_after);
+    }
+
+    #[test]
+    fn rewriting_without_a_gain_keeps_the_circuit() {
+        let (dag, roots, stats) = rewrite("INORDER = a b c;\nOUTORDER = y;\ny = (a * b) + c;\n", GreedyCost::Size);
+        assert_eq!((stats.passes, stats.rewrites, stats.cost_after), (0, 0, stats.cost_before));
+        assert_eq!(Some(dag.find(roots[0])), dag.lookup_expr(&"(+ (* a b) c)".parse().unwrap()));
+    }
+
+    #[test]
+    fn a_rewrite_that_uses_its_own_parent_is_a_cycle() {
+        let mut egraph = PropEGraph::default();
+        let and = egraph.add_expr(&"(* a b)".parse().unwrap());
+        let not = egraph.add(Prop::Not(and));
+        egraph.rebuild();
+        // (* a b) rewritten to (! (! a b)) would use itself
+        let rhs: PatternAst<Prop> = "(! ?x)".parse().unwrap();
+        let mut subst = Subst::with_capacity(1);
+        subst.insert("?x".parse().unwrap(), not);
+        let chosen = HashMap::from([(and, Chosen { gain: 1, rhs: &rhs, subst })]);
+        assert!(matches!(rebuild_dag(&egraph, &[not], &chosen), Err(GreedyError::Cycle(_))));
+        assert!(rebuild_dag(&egraph, &[not], &HashMap::new()).is_ok());
+    }
+}
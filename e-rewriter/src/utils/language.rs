@@ -1,6 +1,6 @@
 use egg::*;
 use ::serde::{Deserialize, Serialize};
-//use crate::{Analysis, EClass, EGraph, Id, Language, RecExpr};
+use crate::utils::rule_file::{build_rewrites, parse_rules_text, DEFAULT_RULES, SIMPLIFY_RULES};
 define_language! {
     #[derive(Serialize, Deserialize)]
     pub enum Prop {
@@ -10,6 +10,11 @@ define_language! {
         "!" = Not(Id),
         "+" = Or([Id; 2]),
         "->" = Implies([Id; 2]),
+        "^" = Xor([Id; 2]),
+        "xnor" = Xnor([Id; 2]),
+        // (mux s a b) is `a` when `s` is 1 and `b` otherwise
+        "mux" = Mux([Id; 3]),
+        "maj" = Maj([Id; 3]),
         "let" = Let([Id; 2]),
         "&" = Concat([Id; 2]),
         "root"= Rooting([Id; 2]),
@@ -17,293 +22,246 @@ define_language! {
     }
 }
 
-//type EGraph = egg::EGraph<Prop, ConstantFold>;
-
-
-// #[derive(Default)]
-// pub struct ConstantFold;
-// impl Analysis<Prop> for ConstantFold {
-//     type Data = Option<(bool, PatternAst<Prop>)>;
-//     fn merge(&mut self, to: &mut Self::Data, from: Self::Data) -> DidMerge {
-//         merge_option(to, from, |a, b| {
-//             assert_eq!(a.0, b.0, "Merged non-equal constants");
-//             DidMerge(false, false)
-//         })
-//     }
-//     fn make(egraph: &egg::EGraph<Prop, ConstantFold>, enode: &Prop) -> Self::Data {
-//         let x = |i: &Id| egraph[*i].data.as_ref().map(|c| c.0);
-//         let result = match enode {
-//             Prop::Let([a, b]) => Some((
-//                 x(a) == x(b),
-//                 format!("(let {} {})", x(a)?, x(b)?).parse().unwrap(),
-//             )),
-//             Prop::Bool(c) => Some((*c, c.to_string().parse().unwrap())),
-//             Prop::And([a, b]) => Some((
-//                 x(a)? && x(b)?,
-//                 format!("(* {} {})", x(a)?, x(b)?).parse().unwrap(),
-//             )),
-//             Prop::Not(a) => Some((!x(a)?, format!("(not {})", x(a)?).parse().unwrap())),
-//             Prop::Or([a, b]) => Some((
-//                 x(a)? || x(b)?,
-//                 format!("(+ {} {})", x(a)?, x(b)?).parse().unwrap(),
-//             )),
-//             Prop::Implies([a, b]) => Some((
-//                 !x(a)? || x(b)?,
-//                 format!("(-> {} {})", x(a)?, x(b)?).parse().unwrap(),
-//             )),
-//             Prop::Concat([a, b]) => Some((
-//                 x(a)? > x(b)?,
-//                 format!("(& {} {})", x(a)?, x(b)?).parse().unwrap(),
-//             )),
-//             Prop::Symbol(_) => None,
-//         };
-//         //println!("Make: {:?} -> {:?}", enode, result);
-//         result
-//     }
-//     fn modify(egraph: &mut egg::EGraph<Prop, ConstantFold>, id: Id) {
-//         if let Some(c) = egraph[id].data.clone() {
-//             egraph.union_instantiations(
-//                 &c.1,
-//                 &c.0.to_string().parse().unwrap(),
-//                 &Default::default(),
-//                 "analysis".to_string(),
-//             );
-//         }
-//     }
-// }
-
-//pub fn make_rules() -> Vec<Rewrite<Prop, ConstantFold>> {
-//    let mut rws: Vec<Rewrite<Prop, ConstantFold>> = vec![
-    pub fn make_rules() -> Vec<Rewrite<Prop, ()>> {
-        let mut rws: Vec<Rewrite<Prop, ()>> = vec![
-        rewrite!("null-element1"; "(* ?b 0)" => "0"),
-        rewrite!("null-element2"; "(+ ?b 1)" => "1"),
-        rewrite!("complements1"; "(* ?b (! ?b))" => "0"),
-        rewrite!("complements2"; "(+ ?b (! ?b))" => "1"),
-        rewrite!("covering1"; "(* ?b (+ ?b ?c))" => "?b"),
-        rewrite!("covering2"; "(+ ?b (* ?b ?c))" => "?b"),
-
-        rewrite!("identity1"; "(* ?b 1)" => "?b"),
-        rewrite!("identity2'"; "(+ ?b 0)" => "?b"),
-        rewrite!("idempotency1"; "(* ?b ?b)" => "?b"),
-        rewrite!("idempotency2"; "(+ ?b ?b)" => "?b"),
-        rewrite!("involution1"; "(! (! ?b))" => "?b"),
-
-
-        rewrite!("combining1"; "(+ (* ?b ?c) (* ?b (! ?c)))" => "?b"),
-        rewrite!("combining2"; "(* (+ ?b ?c) (+ ?b (! ?c)))" => "?b"),
-        
-        rewrite!("consensus1"; "(+ (+ (* ?b ?c) (* (! ?b) ?d)) (* ?c ?d))" => "(+ (* ?b ?c) (* (! ?b) ?d))"),
-        rewrite!("consensus2"; "(* (* (+ ?b ?c) (+ (! ?b) ?d)) (+ ?c ?d))" => "(* (+ ?b ?c) (+ (! ?b) ?d))"),
-        rewrite!("distributivity1"; "(+ (* ?b ?c) (* ?b ?d))" => "(* ?b (+ ?c ?d))"),
-        rewrite!("distributivity2"; "(* (+ ?b ?c) (+ ?b ?d))" => "(+ ?b (* ?c ?d))"),
-        rewrite!("distributivity3"; "(* ?a (+ ?b ?c))" => "(+ (* ?a ?b) (* ?a ?c))"),
-    ];
+pub type PropEGraph = egg::EGraph<Prop, ConstantFold>;
 
+impl Prop {
+    // Value of the operator given the values of its children.
+    // `None` for inputs (symbols other than "0"/"1") and for the structural let/&/root nodes.
+    pub fn eval(&self, mut value: impl FnMut(Id) -> bool) -> Option<bool> {
+        let mut v = |id: &Id| value(*id);
+        Some(match self {
+            Prop::Bool(c) => *c,
+            Prop::Symbol(s) if s.as_str() == "0" => false,
+            Prop::Symbol(s) if s.as_str() == "1" => true,
+            Prop::Not(a) => !v(a),
+            Prop::And([a, b]) => v(a) & v(b),
+            Prop::Or([a, b]) => v(a) | v(b),
+            Prop::Implies([a, b]) => !v(a) | v(b),
+            Prop::Xor([a, b]) => v(a) ^ v(b),
+            Prop::Xnor([a, b]) => v(a) == v(b),
+            Prop::Mux([s, a, b]) => {
+                let (s, a, b) = (v(s), v(a), v(b));
+                if s { a } else { b }
+            }
+            Prop::Maj([a, b, c]) => {
+                let (a, b, c) = (v(a), v(b), v(c));
+                (a & b) | (c & (a | b))
+            }
+            Prop::Symbol(_) | Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_) => return None,
+        })
+    }
 
-    rws.extend(rewrite!("commutativity1"; "(* ?b ?c)" <=> "(* ?c ?b)"));
-    rws.extend(rewrite!("commutativity2"; "(+ ?b ?c)" <=> "(+ ?c ?b)"));
-    rws.extend(rewrite!("associativity1"; "(*(* ?b ?c) ?d)" <=> "(* ?b (* ?c ?d))"));
-    rws.extend(rewrite!("associativity2"; "(+(+ ?b ?c) ?d)" <=> "(+ ?b (+ ?c ?d))"));
-    rws.extend(rewrite!("de-morgan1"; "(! (* ?b ?c))" <=> "(+ (! ?b) (! ?c))"));
-    rws.extend(rewrite!("de-morgan2"; "(! (+ ?b ?c))" <=> "(* (! ?b) (! ?c))"));
-    rws
+    // `eval` on 64 assignments at once, one per bit
+    pub fn eval_bits(&self, mut value: impl FnMut(Id) -> u64) -> Option<u64> {
+        let mut v = |id: &Id| value(*id);
+        Some(match self {
+            Prop::Bool(c) => 0u64.wrapping_sub(*c as u64),
+            Prop::Symbol(s) if s.as_str() == "0" => 0,
+            Prop::Symbol(s) if s.as_str() == "1" => u64::MAX,
+            Prop::Not(a) => !v(a),
+            Prop::And([a, b]) => v(a) & v(b),
+            Prop::Or([a, b]) => v(a) | v(b),
+            Prop::Implies([a, b]) => !v(a) | v(b),
+            Prop::Xor([a, b]) => v(a) ^ v(b),
+            Prop::Xnor([a, b]) => !(v(a) ^ v(b)),
+            Prop::Mux([s, a, b]) => {
+                let (s, a, b) = (v(s), v(a), v(b));
+                (s & a) | (!s & b)
+            }
+            Prop::Maj([a, b, c]) => {
+                let (a, b, c) = (v(a), v(b), v(c));
+                (a & b) | (c & (a | b))
+            }
+            Prop::Symbol(_) | Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_) => return None,
+        })
+    }
 }
 
-
-
-pub fn make_rules_simplify() -> Vec<Rewrite<Prop, ()>> {
-    vec![
-        //version 1
-        //rewrite!("a"; "(-> ?a ?b)"      =>       "(+ (! ?a) ?b)"          ),
-        rewrite!("q"; "(+ ?a (! ?a))"   =>    "1"                   ) ,
-        rewrite!("null-element1"; "(* ?b 0)" => "0"),
-        rewrite!("null-element2"; "(+ ?b 1)" => "1"),
-        rewrite!("complements1"; "(* ?b (! ?b))" => "0"),
-        rewrite!("identity1"; "(* ?b 1)" => "?b"),
-        rewrite!("identity2'"; "(+ ?b 0)" => "?b"),
-
-
-        rewrite!("involution1"; "(! (! ?a))"      =>       "?a"                     ),
-        rewrite!("associativity2"; "(+ ?a (+ ?b ?c))"=> "(+ (+ ?a ?b) ?c)"       ),
-        rewrite!("d"; "(* ?a (+ ?b ?c))"=> "(+ (* ?a ?b) (* ?a ?c))"),
-        rewrite!("e"; "(+ ?a (* ?b ?c))"=> "(* (+ ?a ?b) (+ ?a ?c))"),
-        rewrite!("f"; "(+ ?a ?b)"       =>        "(+ ?b ?a)"              ),
-        rewrite!("r"; "(* ?a ?b)"       =>        "(* ?b ?a)"              ),
-
-
-        rewrite!("th1"; "(+ ?x (* ?x ?y))" => "?x"),
-        // Theorem 2: X + !X · Y = X + Y
-        rewrite!("th2"; "(+ ?x (* (! ?x) ?y))" => "(+ ?x ?y)"),
-        // Theorem 3: X · Y + !X · Z + Y · Z = X · Y + !X · Z
-        rewrite!("th3"; "(+ (* ?x ?y) (+ (* (! ?x) ?z) (* ?y ?z)))" => "(+ (* ?x ?y) (* (! ?x) ?z))"),
-        // Theorem 4: X(X + Y) = X
-        rewrite!("th4"; "(* ?x (+ ?x ?y))" => "?x"),
-        // Theorem 5: X(!X + Y) = X · Y
-        rewrite!("th5"; "(* ?x (+ (! ?x) ?y))" => "(* ?x ?y)"),
-        // Theorem 6: (X + Y)(X + !Y) = X
-        rewrite!("th6"; "(* (+ ?x ?y) (+ ?x (! ?y)))" => "?x"),
-        // Theorem 7: (X + Y)(!X + Z) = X · Z + !X · Y
-        rewrite!("th7"; "(* (+ ?x ?y) (+ (! ?x) ?z))" => "(+ (* ?x ?z) (* (! ?x) ?y))"),
-        // Theorem 8: (X + Y)(!X + Z)(Y + Z) = (X + Y)(!X + Z)
-        rewrite!("th8"; "(* (+ ?x ?y) (* (+ (! ?x) ?z) (+ ?y ?z)))" => "(* (+ ?x ?y) (+ (! ?x) ?z))"),
-]
+// Constant propagation: the data of a class is its value if the class is known to be
+// constant. `Bool(b)` and the symbols "0"/"1" are the same constants, and every class
+// proven constant is merged with the "0"/"1" symbol class.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ConstantFold;
+impl Analysis<Prop> for ConstantFold {
+    type Data = Option<bool>;
+    fn merge(&mut self, to: &mut Self::Data, from: Self::Data) -> DidMerge {
+        merge_option(to, from, |a, b| {
+            // only an unsound rule or union makes 0 and 1 equal, the class keeps its value
+            if *a != b {
+                log::warn!("merged non-equal constants, keeping {}", a);
+            }
+            DidMerge(false, false)
+        })
+    }
+    fn make(egraph: &PropEGraph, enode: &Prop) -> Self::Data {
+        let x = |i: &Id| egraph[*i].data;
+        match enode {
+            Prop::Bool(c) => Some(*c),
+            Prop::Symbol(s) => match s.as_str() {
+                "0" => Some(false),
+                "1" => Some(true),
+                _ => None,
+            },
+            Prop::Not(a) => Some(!x(a)?),
+            Prop::And([a, b]) => match (x(a), x(b)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Prop::Or([a, b]) => match (x(a), x(b)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            Prop::Implies([a, b]) => match (x(a), x(b)) {
+                (Some(false), _) | (_, Some(true)) => Some(true),
+                (Some(true), Some(false)) => Some(false),
+                _ => None,
+            },
+            Prop::Xor([a, b]) if a == b => Some(false),
+            Prop::Xor([a, b]) => Some(x(a)? != x(b)?),
+            Prop::Xnor([a, b]) if a == b => Some(true),
+            Prop::Xnor([a, b]) => Some(x(a)? == x(b)?),
+            Prop::Mux([s, a, b]) => match x(s) {
+                Some(true) => x(a),
+                Some(false) => x(b),
+                None if x(a) == x(b) => x(a),
+                None => None,
+            },
+            Prop::Maj([a, b, c]) => match (x(a), x(b), x(c)) {
+                (Some(p), Some(q), _) | (Some(p), _, Some(q)) | (_, Some(p), Some(q)) if p == q => Some(p),
+                _ => None,
+            },
+            Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_) => None,
+        }
+    }
+    fn modify(egraph: &mut PropEGraph, id: Id) {
+        if let Some(c) = egraph[id].data {
+            let constant = egraph.add(Prop::Symbol(if c { "1" } else { "0" }.into()));
+            egraph.union_trusted(id, constant, "constant-fold");
+        }
+    }
 }
-// pub fn make_rules() -> Vec<Rewrite<Prop, ConstantFold>> {
-//     let mut rws: Vec<Rewrite<Prop, ConstantFold>> = vec![
-//         // Boolean theorems of one variable (Table 2.2 pg 62)
-//         rewrite!("null-element1"; "(* ?b 0)" => "0"),
-//         rewrite!("null-element2"; "(+ ?b 1)" => "1"),
-//         rewrite!("complements1"; "(* ?b (! ?b))" => "0"),
-//         rewrite!("complements2"; "(+ ?b (! ?b))" => "1"),
-//         rewrite!("covering1"; "(* ?b (+ ?b ?c))" => "?b"),
-//         rewrite!("covering2"; "(+ ?b (* ?b ?c))" => "?b"),
-//         rewrite!("combining1"; "(+ (* ?b ?c) (* ?b (! ?c)))" => "?b"),
-//         rewrite!("combining2"; "(* (+ ?b ?c) (+ ?b (! ?c)))" => "?b"),
-//         rewrite!("distributivity3"; "(* ?a (+ ?b ?c))" => "(+ (* ?a ?b) (* ?a ?c))"),
-//         //rewrite!("q"; "(+ ?a (! ?a))"   =>    "1"                   ) ,
-//         //rewrite!("null-element1"; "(* ?b 0)" => "0"),
-//         //rewrite!("null-element2"; "(+ ?b 1)" => "1"),
-//         //rewrite!("complements1"; "(* ?b (! ?b))" => "0"),
-//         //rewrite!("identity1"; "(* ?b 1)" => "?b"),
-//         //rewrite!("identity2'"; "(+ ?b 0)" => "?b"),
-//         //rewrite!("involution1"; "(! (! ?a))"      =>       "?a"                     ),
-//         //rewrite!("associativity2"; "(+ ?a (+ ?b ?c))"=> "(+ (+ ?a ?b) ?c)"       ),
-//         // rewrite!("d"; "(* ?a (+ ?b ?c))"=> "(+ (* ?a ?b) (* ?a ?c))"),
-//         // rewrite!("e"; "(+ ?a (* ?b ?c))"=> "(* (+ ?a ?b) (+ ?a ?c))"),
-//         // //rewrite!("f"; "(+ ?a ?b)"       =>        "(+ ?b ?a)"              ),
-//         // // rewrite!("r"; "(* ?a ?b)"       =>        "(* ?b ?a)"              ),
-//         // rewrite!("th1"; "(+ ?x (* ?x ?y))" => "?x"),
-//         // // Theorem 2: X + !X · Y = X + Y
-//         // rewrite!("th2"; "(+ ?x (* (! ?x) ?y))" => "(+ ?x ?y)"),
-//         // // Theorem 3: X · Y + !X · Z + Y · Z = X · Y + !X · Z
-//         // rewrite!("th3"; "(+ (* ?x ?y) (+ (* (! ?x) ?z) (* ?y ?z)))" => "(+ (* ?x ?y) (* (! ?x) ?z))"),
-//         // // Theorem 4: X(X + Y) = X
-//         // rewrite!("th4"; "(* ?x (+ ?x ?y))" => "?x"),
-//         // // Theorem 5: X(!X + Y) = X · Y
-//         // rewrite!("th5"; "(* ?x (+ (! ?x) ?y))" => "(* ?x ?y)"),
-//         // // Theorem 6: (X + Y)(X + !Y) = X
-//         // rewrite!("th6"; "(* (+ ?x ?y) (+ ?x (! ?y)))" => "?x"),
-//         // // Theorem 7: (X + Y)(!X + Z) = X · Z + !X · Y
-//         // rewrite!("th7"; "(* (+ ?x ?y) (+ (! ?x) ?z))" => "(+ (* ?x ?z) (* (! ?x) ?y))"),
-//         // // Theorem 8: (X + Y)(!X + Z)(Y + Z) = (X + Y)(!X + Z)
-//         // rewrite!("th8"; "(* (+ ?x ?y) (* (+ (! ?x) ?z) (+ ?y ?z)))" => "(* (+ ?x ?y) (+ (! ?x) ?z))"),
-//     ];
-
-//     rws.extend(rewrite!("identity1"; "(* ?b 1)" <=> "?b"));
-//     rws.extend(rewrite!("identity2'"; "(+ ?b 0)" <=> "?b"));
-//     rws.extend(rewrite!("idempotency1"; "(* ?b ?b)" <=> "?b"));
-//     rws.extend(rewrite!("idempotency2"; "(+ ?b ?b)" <=> "?b"));
-//     rws.extend(rewrite!("involution1"; "(! (! ?b))" <=> "?b"));
-//     rws.extend(rewrite!("commutativity1"; "(* ?b ?c)" <=> "(* ?c ?b)"));
-//     rws.extend(rewrite!("commutativity2"; "(+ ?b ?c)" <=> "(+ ?c ?b)"));
-//     rws.extend(rewrite!("associativity1"; "(*(* ?b ?c) ?d)" <=> "(* ?b (* ?c ?d))"));
-//     rws.extend(rewrite!("associativity2"; "(+(+ ?b ?c) ?d)" <=> "(+ ?b (+ ?c ?d))"));
-//     rws.extend(rewrite!("distributivity1"; "(+ (* ?b ?c) (* ?b ?d))" <=> "(* ?b (+ ?c ?d))"));
-//     rws.extend(rewrite!("distributivity2"; "(* (+ ?b ?c) (+ ?b ?d))" <=> "(+ ?b (* ?c ?d))"));
-   
-//     rws.extend(rewrite!("consensus1"; "(+ (+ (* ?b ?c) (* (! ?b) ?d)) (* ?c ?d))" <=> "(+ (* ?b ?c) (* (! ?b) ?d))"));
-//     rws.extend(rewrite!("consensus2"; "(* (* (+ ?b ?c) (+ (! ?b) ?d)) (+ ?c ?d))" <=> "(* (+ ?b ?c) (+ (! ?b) ?d))"));
-//     rws.extend(rewrite!("de-morgan1"; "(! (* ?b ?c))" <=> "(+ (! ?b) (! ?c))"));
-//     rws.extend(rewrite!("de-morgan2"; "(! (+ ?b ?c))" <=> "(* (! ?b) (! ?c))"));
-//     rws
-// }
-
 
+// The built-in rule sets, see rules/default.rules and rules/simplify.rules
+pub fn make_rules() -> Vec<Rewrite<Prop, ConstantFold>> {
+    let specs = parse_rules_text(DEFAULT_RULES).expect("rules/default.rules is well-formed");
+    build_rewrites(&specs).expect("rules/default.rules has valid rules")
+}
 
+pub fn make_rules_simplify() -> Vec<Rewrite<Prop, ConstantFold>> {
+    let specs = parse_rules_text(SIMPLIFY_RULES).expect("rules/simplify.rules is well-formed");
+    build_rewrites(&specs).expect("rules/simplify.rules has valid rules")
+}
 
-//test_rules_most
-// pub fn make_rules() -> Vec<Rewrite<Prop, ConstantFold>> {
-//     vec![
-//         //version 1
-//         //rewrite!("a"; "(-> ?a ?b)"      =>       "(+ (! ?a) ?b)"          ),
-//         rewrite!("q"; "(+ ?a (! ?a))"   =>    "1"                   ) ,
-//         rewrite!("null-element1"; "(* ?b 0)" => "0"),
-//         rewrite!("null-element2"; "(+ ?b 1)" => "1"),
-//         rewrite!("complements1"; "(* ?b (! ?b))" => "0"),
-//         rewrite!("identity1"; "(* ?b 1)" => "?b"),
-//         rewrite!("identity2'"; "(+ ?b 0)" => "?b"),
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn ops() -> Vec<Prop> {
+        let (a, b, c) = (Id::from(0), Id::from(1), Id::from(2));
+        vec![
+            Prop::Bool(false),
+            Prop::Bool(true),
+            Prop::Symbol("0".into()),
+            Prop::Symbol("1".into()),
+            Prop::Not(a),
+            Prop::And([a, b]),
+            Prop::Or([a, b]),
+            Prop::Implies([a, b]),
+            Prop::Xor([a, b]),
+            Prop::Xnor([a, b]),
+            Prop::Mux([a, b, c]),
+            Prop::Maj([a, b, c]),
+        ]
+    }
 
-//         rewrite!("involution1"; "(! (! ?a))"      =>       "?a"                     ),
-//         rewrite!("associativity2"; "(+ ?a (+ ?b ?c))"=> "(+ (+ ?a ?b) ?c)"       ),
-//         rewrite!("d"; "(* ?a (+ ?b ?c))"=> "(+ (* ?a ?b) (* ?a ?c))"),
-//         rewrite!("e"; "(+ ?a (* ?b ?c))"=> "(* (+ ?a ?b) (+ ?a ?c))"),
-//         rewrite!("f"; "(+ ?a ?b)"       =>        "(+ ?b ?a)"              ),
-//         rewrite!("r"; "(* ?a ?b)"       =>        "(* ?b ?a)"              ),
+    // the value of an operator of `ops` when its children have the values `x`
+    fn value(op: &Prop, x: [bool; 3]) -> bool {
+        let [a, b, c] = x;
+        match op {
+            Prop::Bool(value) => *value,
+            Prop::Symbol(s) => s.as_str() == "1",
+            Prop::Not(_) => !a,
+            Prop::And(_) => a && b,
+            Prop::Or(_) => a || b,
+            Prop::Implies(_) => !a || b,
+            Prop::Xor(_) => a != b,
+            Prop::Xnor(_) => a == b,
+            Prop::Mux(_) => if a { b } else { c },
+            Prop::Maj(_) => (a && b) || (c && (a || b)),
+            _ => unreachable!(),
+        }
+    }
 
+    #[test]
+    fn eval_bits_agrees_with_eval() {
+        // the 8 assignments of the three children, one per bit
+        let words = [0xaau64, 0xcc, 0xf0];
+        for op in ops() {
+            let bits = op.eval_bits(|id| words[usize::from(id)]).unwrap();
+            for bit in 0..8 {
+                let x = [0, 1, 2].map(|i| words[i] >> bit & 1 == 1);
+                let value_of = op.eval(|id| x[usize::from(id)]).unwrap();
+                assert_eq!(value_of, value(&op, x), "{} on assignment {}", op, bit);
+                assert_eq!(bits >> bit & 1 == 1, value_of, "{} on assignment {}", op, bit);
+            }
+        }
+        for op in [Prop::Symbol("a".into()), Prop::Concat([Id::from(0), Id::from(1)])] {
+            assert_eq!(op.eval(|_| false), None);
+            assert_eq!(op.eval_bits(|_| 0), None);
+        }
+    }
 
-//         rewrite!("th1"; "(+ ?x (* ?x ?y))" => "?x"),
-//         // Theorem 2: X + !X · Y = X + Y
-//         rewrite!("th2"; "(+ ?x (* (! ?x) ?y))" => "(+ ?x ?y)"),
-//         // Theorem 3: X · Y + !X · Z + Y · Z = X · Y + !X · Z
-//         rewrite!("th3"; "(+ (* ?x ?y) (+ (* (! ?x) ?z) (* ?y ?z)))" => "(+ (* ?x ?y) (* (! ?x) ?z))"),
-//         // Theorem 4: X(X + Y) = X
-//         rewrite!("th4"; "(* ?x (+ ?x ?y))" => "?x"),
-//         // Theorem 5: X(!X + Y) = X · Y
-//         rewrite!("th5"; "(* ?x (+ (! ?x) ?y))" => "(* ?x ?y)"),
-//         // Theorem 6: (X + Y)(X + !Y) = X
-//         rewrite!("th6"; "(* (+ ?x ?y) (+ ?x (! ?y)))" => "?x"),
-//         // Theorem 7: (X + Y)(!X + Z) = X · Z + !X · Y
-//         rewrite!("th7"; "(* (+ ?x ?y) (+ (! ?x) ?z))" => "(+ (* ?x ?z) (* (! ?x) ?y))"),
-//         // Theorem 8: (X + Y)(!X + Z)(Y + Z) = (X + Y)(!X + Z)
-//         rewrite!("th8"; "(* (+ ?x ?y) (* (+ (! ?x) ?z) (+ ?y ?z)))" => "(* (+ ?x ?y) (+ (! ?x) ?z))"),
-//         //version2
-//     //     rewrite!("identity"; "(* ?b true)" => "?b"),
-//     //     rewrite!("identity'"; "(+ ?b false)" => "?b"),
-//     //     rewrite!("null-element"; "(* ?b false)" => "false"),
-//     //   // rewrite!("null-element"; "false" => "(* ?b false)"),
-//     //     rewrite!("null-element'"; "(+ ?b true)" => "true"),
-//     //   // rewrite!("null-element'"; "true"=> "(+ ?b true)"),
-//     //     rewrite!("idempotency"; "(* ?b ?b)" => "?b"),
-//     //     rewrite!("idempotency'"; "(+ ?b ?b)" => "?b"),
-//     //     rewrite!("involution"; "(! (! ?b))" => "?b"),
-//     //     rewrite!("complements"; "(* ?b (! ?b))" => "false"),
-//     //     rewrite!("complements'"; "(+ ?b (! ?b))" => "true"),
-//     //     // Boolean theorems of several variables (Table 2.3 pg 63)
-//     //     rewrite!("commutativity"; "(* ?b ?c)" => "(* ?c ?b)"),
-//     //     rewrite!("commutativity'"; "(+ ?b ?c)" => "(+ ?c ?b)"),
-//     //     rewrite!("associativity"; "(*(* ?b ?c) ?d)" => "(* ?b (* ?c ?d))"),
-//     //     rewrite!("associativity'"; "(+(+ ?b ?c) ?d)" => "(+ ?b (+ ?c ?d))"),
-//     //     rewrite!("distributivity"; "(+ (* ?b ?c) (* ?b ?d))" => "(* ?b (+ ?c ?d))"),
-//     //     rewrite!("distributivity'"; "(* (+ ?b ?c) (+ ?b ?d))" => "(+ ?b (* ?c ?d))"),
-//     //     rewrite!("covering"; "(* ?b (+ ?b ?c))" => "?b"),
-//     //     rewrite!("covering'"; "(+ ?b (* ?b ?c))" => "?b"),
-//     //     rewrite!("combining"; "(+ (* ?b ?c) (* ?b (! ?c)))" => "?b"),
-//     //     rewrite!("combining'"; "(* (+ ?b ?c) (+ ?b (! ?c)))" => "?b"),
-//     //     rewrite!("consensus"; "(+ (+ (* ?b ?c) (* (! ?b) ?d)) (* ?c ?d))" => "(+ (* ?b ?c) (* (! ?b) ?d))"),
-//     //     rewrite!("consensus'"; "(* (* (+ ?b ?c) (+ (! ?b) ?d)) (+ ?c ?d))" => "(* (+ ?b ?c) (+ (! ?b) ?d))"),
-//     //     rewrite!("de-morgan"; "(! (* ?b ?c))" => "(+ (! ?b) (! ?c))"),
-//     //     rewrite!("de-morgan'"; "(! (+ ?b ?c))" => "(* (! ?b) (! ?c))"),
-//     //  version3 
-//     // rewrite!("a"; "(-> ?a ?b)"      =>       "(+ (! ?a) ?b)"          ),
-//     // rewrite!("b"; "(! (! ?a))"      =>       "?a"                     ),
-//     // rewrite!("c"; "(+ ?a (+ ?b ?c))"=> "(+ (+ ?a ?b) ?c)"       ),
-//     // rewrite!("d"; "(* ?a (+ ?b ?c))"=> "(+ (* ?a ?b) (* ?a ?c))"),
-//     // rewrite!("e"; "(+ ?a (* ?b ?c))"=> "(* (+ ?a ?b) (+ ?a ?c))"),
-//     // rewrite!("f"; "(+ ?a ?b)"       =>        "(+ ?b ?a)"              ),
-//     // rewrite!("r"; "(* ?a ?b)"       =>        "(* ?b ?a)"              ),
-//     // //rewrite!("q"; "(+ ?a (! ?a))"   =>    "true"                   ) ,
-//     // //rewrite!("s"; "(+ ?a true)"     =>         "true"                ) ,
-//     // rewrite!("g"; "(* ?a true)"     =>         "?a"                  ),
-//     // rewrite!("y"; "(-> ?a ?b)"      =>    "(-> (! ?b) (! ?a))"     ),
-//     // rewrite!("th1"; "(+ ?x (* ?x ?y))" => "?x"),
-//     // // Theorem 2: X + !X · Y = X + Y
-//     // rewrite!("th2"; "(+ ?x (* (! ?x) ?y))" => "(+ ?x ?y)"),
-//     // // Theorem 3: X · Y + !X · Z + Y · Z = X · Y + !X · Z
-//     // rewrite!("th3"; "(+ (* ?x ?y) (+ (* (! ?x) ?z) (* ?y ?z)))" => "(+ (* ?x ?y) (* (! ?x) ?z))"),
-//     // // Theorem 4: X(X + Y) = X
-//     // rewrite!("th4"; "(* ?x (+ ?x ?y))" => "?x"),
-//     // // Theorem 5: X(!X + Y) = X · Y
-//     // rewrite!("th5"; "(* ?x (+ (! ?x) ?y))" => "(* ?x ?y)"),
-//     // // Theorem 6: (X + Y)(X + !Y) = X
-//     // rewrite!("th6"; "(* (+ ?x ?y) (+ ?x (! ?y)))" => "?x"),
-//     // // Theorem 7: (X + Y)(!X + Z) = X · Z + !X · Y
-//     // rewrite!("th7"; "(* (+ ?x ?y) (+ (! ?x) ?z))" => "(+ (* ?x ?z) (* (! ?x) ?y))"),
-//     // // Theorem 8: (X + Y)(!X + Z)(Y + Z) = (X + Y)(!X + Z)
-//     // rewrite!("th8"; "(* (+ ?x ?y) (* (+ (! ?x) ?z) (+ ?y ?z)))" => "(* (+ ?x ?y) (+ (! ?x) ?z))")
+    #[test]
+    fn constant_children_fold_to_their_value() {
+        for values in 0..8u64 {
+            let mut egraph = PropEGraph::default();
+            let children: Vec<Id> = (0..3).map(|i| egraph.add(Prop::Symbol(if values >> i & 1 == 1 { "1" } else { "0" }.into()))).collect();
+            for op in ops() {
+                let expected = value(&op, [0, 1, 2].map(|i| values >> i & 1 == 1));
+                let id = egraph.add(op.clone().map_children(|id| children[usize::from(id)]));
+                egraph.rebuild();
+                let constant = egraph.add(Prop::Symbol(if expected { "1" } else { "0" }.into()));
+                assert_eq!(egraph[id].data, Some(expected), "{}", op);
+                assert_eq!(egraph.find(id), egraph.find(constant), "{} is not in the constant's class", op);
+            }
+        }
+    }
 
+    #[test]
+    fn constant_subexpressions_fold() {
+        let mut egraph = PropEGraph::default();
+        let zero = egraph.add_expr(&"0".parse().unwrap());
+        let one = egraph.add_expr(&"1".parse().unwrap());
+        for (expr, constant) in [("(* a 0)", zero), ("(+ b (! 0))", one), ("(^ a a)", zero), ("(xnor a a)", one), ("(mux a 1 1)", one), ("(maj 0 a 0)", zero), ("(+ (* a (! 1)) 0)", zero)] {
+            let id = egraph.add_expr(&expr.parse().unwrap());
+            egraph.rebuild();
+            assert_eq!(egraph.find(id), egraph.find(constant), "{}", expr);
+        }
+        // a class that becomes constant by a union folds its parents when the egraph is rebuilt
+        let and = egraph.add_expr(&"(* a b)".parse().unwrap());
+        let or = egraph.add_expr(&"(+ (* a b) c)".parse().unwrap());
+        let b = egraph.add_expr(&"b".parse().unwrap());
+        assert_eq!(egraph[and].data, None);
+        egraph.union(b, zero);
+        egraph.rebuild();
+        assert_eq!(egraph.find(and), egraph.find(zero));
+        assert_eq!(egraph[or].data, None);
+        // (+ 0 c) is not constant, only the rules prove it equal to c
+        assert_ne!(egraph.find(or), egraph.find(egraph.lookup_expr(&"c".parse().unwrap()).unwrap()));
+    }
 
-//     ]
-    
-// }
+    #[test]
+    fn merging_non_equal_constants_keeps_the_value() {
+        let mut egraph = PropEGraph::default();
+        let zero = egraph.add_expr(&"0".parse().unwrap());
+        let one = egraph.add_expr(&"1".parse().unwrap());
+        let to = egraph.find(zero);
+        egraph.union(zero, one);
+        egraph.rebuild();
+        let kept = if egraph.find(zero) == to { Some(false) } else { Some(true) };
+        assert_eq!(egraph[zero].data, kept);
+        assert_eq!(egraph.find(zero), egraph.find(one));
+    }
+}
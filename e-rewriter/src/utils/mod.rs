@@ -1,7 +1,20 @@
 pub mod cost;
+pub mod cuts;
+pub mod dont_care;
+pub mod fraig;
+pub mod greedy;
 pub mod language;
+pub mod netlist;
+pub mod npn;
 pub mod order_trait;
+pub mod partition;
 pub mod preprocess;
+pub mod proof;
+pub mod qor;
 pub mod random_gen;
+pub mod rule_file;
+pub mod schedule;
+pub mod soundness;
+pub mod synthesis;
 pub mod extract_new;
-pub mod runner_modified;
\ No newline at end of file
+pub mod runner_modified;
@@ -0,0 +1,191 @@
+// The netlist model is shared with the equivalence checker, see the `cec` crate
+pub use cec::netlist::*;
+
+use crate::utils::language::{Prop, PropEGraph};
+use egg::*;
+use std::collections::HashMap;
+
+// Adds the netlist to `egraph`.
+// Returns the ids of the outputs (in declaration order) and of the inputs.
+pub fn add_to_egraph(netlist: &Netlist, egraph: &mut PropEGraph) -> Result<(Vec<Id>, Vec<Id>), NetlistError> {
+    add_nodes(netlist, &mut |node| egraph.add(node))
+}
+
+// Every output as an expression of its cone, shared logic stays shared
+pub fn output_exprs(netlist: &Netlist) -> Result<Vec<(String, RecExpr<Prop>)>, NetlistError> {
+    let mut nodes = RecExpr::default();
+    let (output_ids, _) = add_nodes(netlist, &mut |node| nodes.add(node))?;
+    let nodes = nodes.as_ref();
+    let exprs: Vec<RecExpr<Prop>> = output_ids
+        .iter()
+        .map(|root| {
+            // the cone in order, children keep coming first
+            let mut cone = vec![false; usize::from(*root) + 1];
+            cone[usize::from(*root)] = true;
+            for index in (0..cone.len()).rev() {
+                if cone[index] {
+                    nodes[index].for_each(|child| cone[usize::from(child)] = true);
+                }
+            }
+            let mut ids: HashMap<usize, Id> = HashMap::new();
+            let mut expr = RecExpr::default();
+            for (index, node) in nodes[..cone.len()].iter().enumerate().filter(|(index, _)| cone[*index]) {
+                let id = expr.add(node.clone().map_children(|child| ids[&usize::from(child)]));
+                ids.insert(index, id);
+            }
+            expr
+        })
+        .collect();
+    Ok(netlist.outputs.iter().map(|(name, _)| name.clone()).zip(exprs).collect())
+}
+
+fn add_nodes(netlist: &Netlist, add: &mut impl FnMut(Prop) -> Id) -> Result<(Vec<Id>, Vec<Id>), NetlistError> {
+    let order = netlist.topo_order()?;
+    let mut ids: HashMap<&str, Id> = HashMap::new();
+    ids.insert("0", add(Prop::Symbol("0".into())));
+    ids.insert("1", add(Prop::Symbol("1".into())));
+
+    let mut input_ids = Vec::with_capacity(netlist.inputs.len());
+    for (input, _) in &netlist.inputs {
+        let id = add(Prop::Symbol(input.as_str().into()));
+        ids.insert(input.as_str(), id);
+        input_ids.push(id);
+    }
+
+    for index in order {
+        let assign = &netlist.assigns[index];
+        let id = add_expr(add, &ids, &assign.expr);
+        ids.insert(assign.name.as_str(), id);
+    }
+
+    let mut output_ids = Vec::with_capacity(netlist.outputs.len());
+    for (output, pos) in &netlist.outputs {
+        match ids.get(output.as_str()) {
+            Some(&id) => output_ids.push(id),
+            None => {
+                return Err(NetlistError::Undefined {
+                    pos: *pos,
+                    name: output.clone(),
+                })
+            }
+        }
+    }
+    Ok((output_ids, input_ids))
+}
+
+fn add_expr(add: &mut impl FnMut(Prop) -> Id, ids: &HashMap<&str, Id>, expr: &Expr) -> Id {
+    match expr {
+        Expr::Const(false) => ids["0"],
+        Expr::Const(true) => ids["1"],
+        Expr::Var(name, _) => ids[name.as_str()],
+        Expr::Not(e) => match e.as_ref() {
+            // the last xor of the chain becomes an xnor
+            Expr::Xor(es) if es.len() > 1 => {
+                let (last, rest) = es.split_last().unwrap();
+                let lhs = add_nary(add, ids, Prop::Xor, rest);
+                let rhs = add_expr(add, ids, last);
+                add(Prop::Xnor([lhs, rhs]))
+            }
+            e => {
+                let id = add_expr(add, ids, e);
+                add(Prop::Not(id))
+            }
+        },
+        Expr::And(es) => add_nary(add, ids, Prop::And, es),
+        Expr::Or(es) => add_nary(add, ids, Prop::Or, es),
+        Expr::Xor(es) => add_nary(add, ids, Prop::Xor, es),
+        Expr::Mux(es) => {
+            let [s, a, b] = es.as_ref();
+            let ids = [add_expr(add, ids, s), add_expr(add, ids, a), add_expr(add, ids, b)];
+            add(Prop::Mux(ids))
+        }
+        Expr::Maj(es) => {
+            let [a, b, c] = es.as_ref();
+            let ids = [add_expr(add, ids, a), add_expr(add, ids, b), add_expr(add, ids, c)];
+            add(Prop::Maj(ids))
+        }
+    }
+}
+
+// n-ary operators become a left-deep chain of binary nodes
+fn add_nary(add: &mut impl FnMut(Prop) -> Id, ids: &HashMap<&str, Id>, op: fn([Id; 2]) -> Prop, es: &[Expr]) -> Id {
+    let mut acc = add_expr(add, ids, &es[0]);
+    for e in &es[1..] {
+        let id = add_expr(add, ids, e);
+        acc = add(op([acc, id]));
+    }
+    acc
+}
+
+// The egraph of an eqn text with its outputs as roots, for the tests of the passes
+#[cfg(test)]
+pub fn parse_circuit(text: &str) -> (Netlist, PropEGraph, Vec<(String, Id)>) {
+    let netlist = cec::eqn_parser::parse_eqn(text).unwrap();
+    let mut egraph = PropEGraph::default();
+    let (output_ids, _) = add_to_egraph(&netlist, &mut egraph).unwrap();
+    egraph.rebuild();
+    let roots = netlist.outputs.iter().map(|(name, _)| name.clone()).zip(output_ids).collect();
+    (netlist, egraph, roots)
+}
+
+// The smallest circuit of every root as a netlist, so the result of a pass can be checked
+// against its input with the `cec` crate
+#[cfg(test)]
+pub fn extract_netlist(egraph: &PropEGraph, roots: &[(String, Id)]) -> Netlist {
+    let extractor = Extractor::new(egraph, AstSize);
+    let mut netlist = Netlist::default();
+    let mut names: HashMap<Id, String> = HashMap::new();
+    let mut stack: Vec<(Id, bool)> = roots.iter().map(|(_, id)| (egraph.find(*id), false)).collect();
+    while let Some((id, expanded)) = stack.pop() {
+        let node = extractor.find_best_node(id);
+        if !expanded {
+            if !names.contains_key(&id) {
+                stack.push((id, true));
+                stack.extend(node.children().iter().map(|child| (egraph.find(*child), false)));
+            }
+            continue;
+        }
+        if names.contains_key(&id) {
+            continue;
+        }
+        let var = |child: &Id| Expr::Var(names[&egraph.find(*child)].clone(), Pos::default());
+        let expr = match node {
+            Prop::Symbol(name) if name.as_str() == "0" || name.as_str() == "1" => Expr::Const(name.as_str() == "1"),
+            Prop::Symbol(name) => {
+                netlist.inputs.push((name.to_string(), Pos::default()));
+                names.insert(id, name.to_string());
+                continue;
+            }
+            Prop::Bool(value) => Expr::Const(*value),
+            Prop::Not(a) => Expr::Not(Box::new(var(a))),
+            Prop::And(es) => Expr::And(es.iter().map(var).collect()),
+            Prop::Or(es) => Expr::Or(es.iter().map(var).collect()),
+            Prop::Implies([a, b]) => Expr::Or(vec![Expr::Not(Box::new(var(a))), var(b)]),
+            Prop::Xor(es) => Expr::Xor(es.iter().map(var).collect()),
+            Prop::Xnor(es) => Expr::Not(Box::new(Expr::Xor(es.iter().map(var).collect()))),
+            Prop::Mux([s, a, b]) => Expr::Mux(Box::new([var(s), var(a), var(b)])),
+            Prop::Maj([a, b, c]) => Expr::Maj(Box::new([var(a), var(b), var(c)])),
+            other => panic!("`{}` nodes are not part of a circuit", other),
+        };
+        // `#` keeps the internal names apart from the eqn names of inputs and outputs
+        let name = format!("#{}", id);
+        netlist.assigns.push(Assign { name: name.clone(), pos: Pos::default(), expr });
+        names.insert(id, name);
+    }
+    for (output, id) in roots {
+        netlist.outputs.push((output.clone(), Pos::default()));
+        netlist.assigns.push(Assign {
+            name: output.clone(),
+            pos: Pos::default(),
+            expr: Expr::Var(names[&egraph.find(*id)].clone(), Pos::default()),
+        });
+    }
+    netlist
+}
+
+// Checks that the circuit of `roots` in `egraph` is equivalent to `netlist`
+#[cfg(test)]
+pub fn assert_equivalent(netlist: &Netlist, egraph: &PropEGraph, roots: &[(String, Id)]) {
+    let report = cec::check::check_equivalence(netlist, &extract_netlist(egraph, roots), &Default::default()).unwrap();
+    assert!(report.equivalent(), "{:?}", report.outputs);
+}
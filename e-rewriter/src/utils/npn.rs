@@ -0,0 +1,353 @@
+// The built-in NPN library: an optimal implementation of every 4-input NPN class.
+//
+// Two functions are in the same NPN class if one becomes the other by permuting and negating
+// inputs and negating the output. The 222 classes of 4-input functions are found by applying
+// all 768 transforms to the smallest table not yet seen, the smallest table of a class is its
+// canonical form. The implementation of a class is a minimum formula with and/xor gates and free
+// inverters (or and xnor fold the inverters away), found once by growing formulas by gate count
+// over all 2^16 truth tables. The library is built on first use.
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+// A gate of an implementation, arguments are earlier steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Step {
+    Var(usize),
+    Const(bool),
+    Not(usize),
+    And(usize, usize),
+    Or(usize, usize),
+    Xor(usize, usize),
+    Xnor(usize, usize),
+    // (mux s a b) is `a` when `s` is 1 and `b` otherwise
+    Mux(usize, usize, usize),
+}
+
+impl Step {
+    pub fn is_gate(&self) -> bool {
+        !matches!(self, Step::Var(_) | Step::Const(_) | Step::Not(_))
+    }
+}
+
+// How a table is obtained from its canonical form: input `i` of the table is input `perm[i]`
+// of the canonical form, negated if bit `i` of `negate` is set, and the output is negated if `output`.
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub perm: [usize; 4],
+    pub negate: u8,
+    pub output: bool,
+}
+
+pub struct NpnClass {
+    pub canonical: u16,
+    pub transform: Transform,
+}
+
+// how a table with a 0 first bit was first built: `table = output ^ op(a ^ na, b ^ nb)`
+#[derive(Debug, Clone, Copy)]
+enum Recipe {
+    Unknown,
+    Const,
+    Var(usize),
+    And { a: u16, na: bool, b: u16, nb: bool, output: bool },
+    Xor { a: u16, b: u16, output: bool },
+}
+
+pub struct NpnLibrary {
+    recipes: Vec<Recipe>,
+    // gates of the minimum formula of every table with a 0 first bit
+    sizes: Vec<u8>,
+    // the class of every table
+    classes: Vec<(u16, Transform)>,
+}
+
+pub const VAR_TABLES: [u16; 4] = [0xAAAA, 0xCCCC, 0xF0F0, 0xFF00];
+
+fn permutations() -> Vec<[usize; 4]> {
+    let mut perms = Vec::with_capacity(24);
+    for a in 0..4 {
+        for b in 0..4 {
+            for c in 0..4 {
+                if a != b && a != c && b != c {
+                    perms.push([a, b, c, 6 - a - b - c]);
+                }
+            }
+        }
+    }
+    perms
+}
+
+// the table of `x -> output ^ canonical(y)` where `y[perm[i]] = x[i] ^ negate[i]`
+fn apply(canonical: u16, transform: &Transform) -> u16 {
+    let mut table = 0u16;
+    for x in 0..16usize {
+        let mut y = 0usize;
+        for i in 0..4 {
+            if (x >> i & 1) ^ (transform.negate as usize >> i & 1) == 1 {
+                y |= 1 << transform.perm[i];
+            }
+        }
+        if (canonical >> y & 1 == 1) != transform.output {
+            table |= 1 << x;
+        }
+    }
+    table
+}
+
+// minimum formulas of every table and their gates, tables and their complements cost the same
+fn minimum_formulas() -> (Vec<Recipe>, Vec<u8>) {
+    let mut recipes = vec![Recipe::Unknown; 1 << 16];
+    let mut sizes = vec![0u8; 1 << 16];
+    recipes[0] = Recipe::Const;
+    let mut layers: Vec<Vec<u16>> = vec![vec![0]];
+    for (i, table) in VAR_TABLES.iter().enumerate() {
+        recipes[*table as usize] = Recipe::Var(i);
+        layers[0].push(*table);
+    }
+    let mut remaining = (1 << 15) - layers[0].len();
+    while remaining > 0 {
+        let gates = layers.len();
+        let mut layer = Vec::new();
+        for i in 0..=(gates - 1) / 2 {
+            let j = gates - 1 - i;
+            for (index, &a) in layers[i].iter().enumerate() {
+                let start = if i == j { index } else { 0 };
+                for &b in &layers[j][start..] {
+                    let candidates = [
+                        (a & b, Some((false, false))),
+                        (a & !b, Some((false, true))),
+                        (!a & b, Some((true, false))),
+                        (!a & !b, Some((true, true))),
+                        (a ^ b, None),
+                    ];
+                    for (table, polarity) in candidates {
+                        let output = table & 1 == 1;
+                        let table = if output { !table } else { table };
+                        if matches!(recipes[table as usize], Recipe::Unknown) {
+                            recipes[table as usize] = match polarity {
+                                Some((na, nb)) => Recipe::And { a, na, b, nb, output },
+                                None => Recipe::Xor { a, b, output },
+                            };
+                            sizes[table as usize] = gates as u8;
+                            layer.push(table);
+                            remaining -= 1;
+                        }
+                    }
+                }
+            }
+        }
+        layers.push(layer);
+    }
+    (recipes, sizes)
+}
+
+pub static LIBRARY: Lazy<NpnLibrary> = Lazy::new(NpnLibrary::new);
+
+impl NpnLibrary {
+    fn new() -> NpnLibrary {
+        let perms = permutations();
+        let mut classes: Vec<Option<(u16, Transform)>> = vec![None; 1 << 16];
+        for canonical in 0..=u16::MAX {
+            if classes[canonical as usize].is_some() {
+                continue;
+            }
+            for perm in &perms {
+                for negate in 0..16u8 {
+                    for output in [false, true] {
+                        let transform = Transform { perm: *perm, negate, output };
+                        let table = apply(canonical, &transform);
+                        if classes[table as usize].is_none() {
+                            classes[table as usize] = Some((canonical, transform));
+                        }
+                    }
+                }
+            }
+        }
+        let (recipes, sizes) = minimum_formulas();
+        NpnLibrary {
+            recipes,
+            sizes,
+            classes: classes.into_iter().map(|class| class.expect("every table is in a class")).collect(),
+        }
+    }
+
+    pub fn class(&self, table: u16) -> NpnClass {
+        let (canonical, transform) = self.classes[table as usize];
+        NpnClass { canonical, transform }
+    }
+
+    // gates of the minimum formula of `table`, inverters are free
+    pub fn gates(&self, table: u16) -> usize {
+        let table = if table & 1 == 1 { !table } else { table };
+        self.sizes[table as usize] as usize
+    }
+
+    // Appends the implementation of `table` to `steps` with `vars[i]` as input `i`, returns its step
+    pub fn implement(&self, table: u16, vars: &[usize; 4], steps: &mut Vec<Step>) -> usize {
+        let class = self.class(table);
+        // input `perm[i]` of the canonical form is input `i` of the table
+        let mut inputs = [0usize; 4];
+        for (i, var) in vars.iter().enumerate() {
+            inputs[class.transform.perm[i]] = if class.transform.negate >> i & 1 == 1 {
+                steps.push(Step::Not(*var));
+                steps.len() - 1
+            } else {
+                *var
+            };
+        }
+        let mut memo = HashMap::new();
+        let (root, negated) = self.build(class.canonical, &inputs, steps, &mut memo);
+        materialize(root, negated != class.transform.output, steps)
+    }
+
+    // a step computing `table` up to its complement, and whether it is complemented
+    fn build(&self, table: u16, inputs: &[usize; 4], steps: &mut Vec<Step>, memo: &mut HashMap<u16, usize>) -> (usize, bool) {
+        let negated = table & 1 == 1;
+        let table = if negated { !table } else { table };
+        if let Some(step) = memo.get(&table) {
+            return (*step, negated);
+        }
+        let (step, output) = match self.recipes[table as usize] {
+            Recipe::Const => (push(Step::Const(false), steps), false),
+            Recipe::Var(i) => (inputs[i], false),
+            Recipe::And { a, na, b, nb, output } => {
+                let (a, a_negated) = self.build(a, inputs, steps, memo);
+                let (b, b_negated) = self.build(b, inputs, steps, memo);
+                let (na, nb) = (na != a_negated, nb != b_negated);
+                if na && nb {
+                    (push(Step::Or(a, b), steps), !output)
+                } else {
+                    let a = materialize(a, na, steps);
+                    let b = materialize(b, nb, steps);
+                    (push(Step::And(a, b), steps), output)
+                }
+            }
+            Recipe::Xor { a, b, output } => {
+                let (a, a_negated) = self.build(a, inputs, steps, memo);
+                let (b, b_negated) = self.build(b, inputs, steps, memo);
+                let step = if a_negated != b_negated { Step::Xnor(a, b) } else { Step::Xor(a, b) };
+                (push(step, steps), output)
+            }
+            Recipe::Unknown => unreachable!("every table has a formula"),
+        };
+        let step = materialize(step, output, steps);
+        memo.insert(table, step);
+        (step, negated)
+    }
+}
+
+fn push(step: Step, steps: &mut Vec<Step>) -> usize {
+    steps.push(step);
+    steps.len() - 1
+}
+
+fn materialize(step: usize, negated: bool, steps: &mut Vec<Step>) -> usize {
+    if !negated {
+        return step;
+    }
+    match steps[step] {
+        Step::Not(inner) => inner,
+        Step::Const(value) => push(Step::Const(!value), steps),
+        Step::Xor(a, b) => push(Step::Xnor(a, b), steps),
+        Step::Xnor(a, b) => push(Step::Xor(a, b), steps),
+        _ => push(Step::Not(step), steps),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // the table computed by `steps` with `VAR_TABLES[i]` as `Var(i)`
+    fn eval_steps(steps: &[Step]) -> u16 {
+        let mut values: Vec<u16> = Vec::with_capacity(steps.len());
+        for step in steps {
+            let value = match *step {
+                Step::Var(i) => VAR_TABLES[i],
+                Step::Const(c) => 0u16.wrapping_sub(c as u16),
+                Step::Not(a) => !values[a],
+                Step::And(a, b) => values[a] & values[b],
+                Step::Or(a, b) => values[a] | values[b],
+                Step::Xor(a, b) => values[a] ^ values[b],
+                Step::Xnor(a, b) => !(values[a] ^ values[b]),
+                Step::Mux(s, a, b) => (values[s] & values[a]) | (!values[s] & values[b]),
+            };
+            values.push(value);
+        }
+        *values.last().unwrap()
+    }
+
+    fn implement(table: u16) -> Vec<Step> {
+        let mut steps: Vec<Step> = (0..4).map(Step::Var).collect();
+        let root = LIBRARY.implement(table, &[0, 1, 2, 3], &mut steps);
+        steps.truncate(root + 1);
+        steps
+    }
+
+    #[test]
+    fn known_functions_have_their_canonical_form() {
+        let [a, b, c, d] = VAR_TABLES;
+        let known = [
+            ("0", 0, 0),
+            ("1", 0xffff, 0),
+            ("a", a, 0x00ff),
+            ("!c", !c, 0x00ff),
+            ("a * b", a & b, 0x000f),
+            ("!b + d", !b | d, 0x000f),
+            ("a ^ b", a ^ b, 0x0ff0),
+            ("maj(a, b, c)", (a & b) | (c & (a | b)), 0x033f),
+            ("mux(a, b, c)", (a & b) | (!a & c), 0x03cf),
+            ("a ^ b ^ c ^ d", a ^ b ^ c ^ d, 0x6996),
+        ];
+        for (name, table, canonical) in known {
+            let class = LIBRARY.class(table);
+            assert_eq!(class.canonical, canonical, "{}", name);
+            assert_eq!(apply(class.canonical, &class.transform), table, "{}", name);
+        }
+    }
+
+    #[test]
+    fn every_table_is_a_transform_of_the_smallest_table_of_its_class() {
+        let mut canonicals = HashSet::new();
+        for table in 0..=u16::MAX {
+            let class = LIBRARY.class(table);
+            assert_eq!(apply(class.canonical, &class.transform), table, "{:#06x}", table);
+            assert!(class.canonical <= table);
+            assert_eq!(LIBRARY.class(class.canonical).canonical, class.canonical);
+            canonicals.insert(class.canonical);
+        }
+        assert_eq!(canonicals.len(), 222);
+    }
+
+    #[test]
+    fn implementations_compute_their_table_with_few_gates() {
+        for table in 0..=u16::MAX {
+            assert_eq!(eval_steps(&implement(table)), table, "{:#06x}", table);
+        }
+        let [a, b, c, d] = VAR_TABLES;
+        // gates the root depends on
+        let gates = |table: u16| {
+            let steps = implement(table);
+            let mut used = vec![false; steps.len()];
+            used[steps.len() - 1] = true;
+            for index in (0..steps.len()).rev() {
+                if !used[index] {
+                    continue;
+                }
+                match steps[index] {
+                    Step::Var(_) | Step::Const(_) => {}
+                    Step::Not(a) => used[a] = true,
+                    Step::And(a, b) | Step::Or(a, b) | Step::Xor(a, b) | Step::Xnor(a, b) => (used[a], used[b]) = (true, true),
+                    Step::Mux(s, a, b) => (used[s], used[a], used[b]) = (true, true, true),
+                }
+            }
+            steps.iter().zip(used).filter(|(step, used)| *used && step.is_gate()).count()
+        };
+        assert_eq!(gates(a), 0);
+        assert_eq!(gates(!a & b), 1);
+        assert_eq!(gates(a ^ !b), 1);
+        assert_eq!(gates((a & b) | (c & (a | b))), 4);
+        assert_eq!(gates(a ^ b ^ c ^ d), 3);
+    }
+}
@@ -0,0 +1,301 @@
+// Partitioned rewriting for circuits too large to saturate at once.
+//
+// The term DAG is split into windows, either by primary-output cones (the logic first reached
+// from each group of outputs) or into chunks of a node budget. Every class of logic belongs to
+// exactly one window. A class used by another window is a boundary: its owner exports it as an
+// extra root, the user sees it as an opaque input (`@cut<class>`), so shared logic is built once.
+// Windows only use classes of earlier windows, so after rewriting and extracting them in parallel
+// they are stitched back in order by replacing every boundary input with the extracted logic.
+use crate::utils::cost::{wight_depth, wight_size};
+use crate::utils::extract_new::Extractor2;
+use crate::utils::greedy::representative;
+use crate::utils::language::{Prop, PropEGraph};
+use egg::{CostFunction, Id, Language, Symbol};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    // the cones of this many primary outputs per window
+    Outputs(usize),
+    // at most this many logic nodes per window
+    Nodes(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowCost {
+    Size,
+    Depth,
+}
+
+pub struct Window {
+    pub egraph: PropEGraph,
+    pub roots: Vec<Id>,
+    // the input class of each root
+    outputs: Vec<Id>,
+    // boundary inputs, by the input class they stand for
+    inputs: HashMap<Symbol, Id>,
+}
+
+// a window after rewriting: the extracted nodes, children first, and the index of each root
+struct Extracted {
+    nodes: Vec<Prop>,
+    roots: Vec<usize>,
+}
+
+fn is_leaf(egraph: &PropEGraph, id: Id) -> bool {
+    representative(egraph, id).is_leaf()
+}
+
+fn boundary_name(id: Id) -> Symbol {
+    format!("@cut{}", id).into()
+}
+
+// logic classes reachable from the roots, children first, with the index of the root that first reaches them
+fn cone_order(egraph: &PropEGraph, roots: &[Id]) -> Vec<(Id, usize)> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    for (index, root) in roots.iter().enumerate() {
+        let mut stack = vec![(egraph.find(*root), false)];
+        while let Some((id, expanded)) = stack.pop() {
+            if expanded {
+                order.push((id, index));
+            } else if !is_leaf(egraph, id) && seen.insert(id) {
+                stack.push((id, true));
+                for child in representative(egraph, id).children() {
+                    stack.push((egraph.find(*child), false));
+                }
+            }
+        }
+    }
+    order
+}
+
+// Splits the term DAG of `egraph` into windows, at least one
+pub fn partition(egraph: &PropEGraph, roots: &[Id], mode: Partition) -> Vec<Window> {
+    let order = cone_order(egraph, roots);
+    let mut classes: Vec<Vec<Id>> = Vec::new();
+    match mode {
+        Partition::Outputs(per_window) => {
+            let per_window = per_window.max(1);
+            for (id, root) in order {
+                let window = root / per_window;
+                classes.resize_with(classes.len().max(window + 1), Vec::new);
+                classes[window].push(id);
+            }
+            classes.retain(|window| !window.is_empty());
+        }
+        Partition::Nodes(budget) => {
+            let ids: Vec<Id> = order.into_iter().map(|(id, _)| id).collect();
+            classes = ids.chunks(budget.max(1)).map(|chunk| chunk.to_vec()).collect();
+        }
+    }
+    if classes.is_empty() {
+        classes.push(Vec::new());
+    }
+    let owner: HashMap<Id, usize> = classes.iter().enumerate().flat_map(|(window, ids)| ids.iter().map(move |id| (*id, window))).collect();
+
+    // primary outputs first, then the classes other windows use
+    let mut outputs: Vec<Vec<Id>> = vec![Vec::new(); classes.len()];
+    for root in roots {
+        let root = egraph.find(*root);
+        if let Some(window) = owner.get(&root) {
+            outputs[*window].push(root);
+        }
+    }
+    for (window, ids) in classes.iter().enumerate() {
+        for id in ids {
+            for child in representative(egraph, *id).children() {
+                let child = egraph.find(*child);
+                if owner.get(&child).is_some_and(|other| *other != window) {
+                    outputs[owner[&child]].push(child);
+                }
+            }
+        }
+    }
+
+    classes
+        .into_iter()
+        .zip(outputs)
+        .map(|(ids, mut outputs)| {
+            let mut seen = HashSet::new();
+            outputs.retain(|id| seen.insert(*id));
+            let mut window = Window {
+                egraph: PropEGraph::default(),
+                roots: Vec::new(),
+                outputs: Vec::new(),
+                inputs: HashMap::new(),
+            };
+            let mut local: HashMap<Id, Id> = HashMap::new();
+            for id in &ids {
+                let node = representative(egraph, *id).map_children(|child| {
+                    let child = egraph.find(child);
+                    if let Some(id) = local.get(&child) {
+                        *id
+                    } else if is_leaf(egraph, child) {
+                        window.egraph.add(representative(egraph, child))
+                    } else {
+                        let name = boundary_name(child);
+                        window.inputs.insert(name, child);
+                        window.egraph.add(Prop::Symbol(name))
+                    }
+                });
+                local.insert(*id, window.egraph.add(node));
+            }
+            window.egraph.rebuild();
+            window.roots = outputs.iter().map(|id| window.egraph.find(local[id])).collect();
+            window.outputs = outputs;
+            window
+        })
+        .collect()
+}
+
+// the best nodes of the classes used by the roots, children first
+fn extract<CF>(egraph: &PropEGraph, roots: &[Id], cost: CF) -> Extracted
+where
+    CF: CostFunction<Prop>,
+    CF::Cost: Ord,
+{
+    let extractor = Extractor2::new(egraph, cost);
+    let mut index: HashMap<Id, usize> = HashMap::new();
+    let mut nodes = Vec::new();
+    for root in roots {
+        let mut stack = vec![(egraph.find(*root), false)];
+        while let Some((id, expanded)) = stack.pop() {
+            if index.contains_key(&id) {
+                continue;
+            }
+            let node = extractor.find_best_node(id);
+            if expanded {
+                index.insert(id, nodes.len());
+                nodes.push(node.clone().map_children(|child| Id::from(index[&egraph.find(child)])));
+            } else {
+                stack.push((id, true));
+                stack.extend(node.children().iter().map(|child| (egraph.find(*child), false)));
+            }
+        }
+    }
+    let roots = roots.iter().map(|root| index[&egraph.find(*root)]).collect();
+    Extracted { nodes, roots }
+}
+
+// Rewrites every window with `rewrite` (in parallel), extracts the best circuit of each window
+// with `cost` and stitches them back into one term DAG. Also returns what `rewrite` reported, or its first error.
+pub fn rewrite_partitioned<R, E, F>(egraph: &PropEGraph, roots: &[Id], windows: &[Window], cost: WindowCost, rewrite: F) -> Result<(PropEGraph, Vec<Id>, Vec<R>), E>
+where
+    R: Send,
+    E: Send,
+    F: Fn(usize, PropEGraph, Vec<Id>) -> Result<(PropEGraph, Vec<Id>, R), E> + Sync,
+{
+    let results: Vec<(Extracted, R)> = windows
+        .par_iter()
+        .enumerate()
+        .map(|(index, window)| {
+            let (rewritten, roots, report) = rewrite(index, window.egraph.clone(), window.roots.clone())?;
+            let extracted = match cost {
+                WindowCost::Size => extract(&rewritten, &roots, wight_size),
+                WindowCost::Depth => extract(&rewritten, &roots, wight_depth),
+            };
+            Ok((extracted, report))
+        })
+        .collect::<Result<_, E>>()?;
+
+    let mut dag = PropEGraph::default();
+    // the stitched class of every input class a window exported
+    let mut stitched: HashMap<Id, Id> = HashMap::new();
+    let mut reports = Vec::with_capacity(results.len());
+    for (window, (extracted, report)) in windows.iter().zip(results) {
+        let mut ids: Vec<Id> = Vec::with_capacity(extracted.nodes.len());
+        for node in extracted.nodes {
+            let id = match node {
+                Prop::Symbol(name) if window.inputs.contains_key(&name) => stitched[&window.inputs[&name]],
+                node => dag.add(node.map_children(|child| ids[usize::from(child)])),
+            };
+            ids.push(id);
+        }
+        for (output, root) in window.outputs.iter().zip(extracted.roots) {
+            stitched.insert(*output, ids[root]);
+        }
+        reports.push(report);
+    }
+    // outputs driven by an input or a constant are in no window
+    let roots: Vec<Id> = roots
+        .iter()
+        .map(|root| {
+            let root = egraph.find(*root);
+            match stitched.get(&root) {
+                Some(id) => *id,
+                None => dag.add(representative(egraph, root)),
+            }
+        })
+        .collect();
+    dag.rebuild();
+    let roots = roots.into_iter().map(|id| dag.find(id)).collect();
+    Ok((dag, roots, reports))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::netlist::{assert_equivalent, parse_circuit};
+
+    // outputs share `t` and `u`, so windows have boundaries
+    const CIRCUIT: &str = "INORDER = a b c d;\nOUTORDER = f g h;\nt = a * (a + b);\nu = t * c + t * !c;\nf = u * d;\ng = !(!u) + c * !c;\nh = t + d;\n";
+
+    fn logic_nodes(window: &Window) -> usize {
+        window.egraph.classes().filter(|class| !is_leaf(&window.egraph, class.id)).count()
+    }
+
+    #[test]
+    fn output_windows_hold_the_cones_of_their_outputs() {
+        let (_, egraph, roots) = parse_circuit(CIRCUIT);
+        let ids: Vec<Id> = roots.iter().map(|(_, id)| *id).collect();
+        let windows = partition(&egraph, &ids, Partition::Outputs(2));
+        assert_eq!(windows.len(), 2);
+        // f and g in the first window, h only uses `t` of the first window
+        assert_eq!(windows[0].outputs[..2], [egraph.find(ids[0]), egraph.find(ids[1])]);
+        assert_eq!(windows[1].outputs, [egraph.find(ids[2])]);
+        assert_eq!(logic_nodes(&windows[1]), 1);
+        assert_eq!(windows[1].inputs.len(), 1);
+        let boundary = windows[1].inputs.values().next().unwrap();
+        assert!(windows[0].outputs.contains(boundary), "the owner of `t` exports it");
+
+        assert_eq!(partition(&egraph, &ids, Partition::Outputs(1)).len(), 3);
+        assert_eq!(partition(&egraph, &ids, Partition::Outputs(3)).len(), 1);
+    }
+
+    #[test]
+    fn node_windows_respect_the_budget() {
+        let (_, egraph, roots) = parse_circuit(CIRCUIT);
+        let ids: Vec<Id> = roots.iter().map(|(_, id)| *id).collect();
+        let total = cone_order(&egraph, &ids).len();
+        for budget in [1, 2, 3, 5, total, total + 1] {
+            let windows = partition(&egraph, &ids, Partition::Nodes(budget));
+            assert_eq!(windows.len(), total.div_ceil(budget), "budget {}", budget);
+            assert!(windows.iter().all(|window| logic_nodes(window) <= budget), "budget {}", budget);
+            assert_eq!(windows.iter().map(logic_nodes).sum::<usize>(), total, "every class belongs to one window");
+        }
+        // windows only use classes of earlier windows
+        let windows = partition(&egraph, &ids, Partition::Nodes(2));
+        for (index, window) in windows.iter().enumerate() {
+            for class in window.inputs.values() {
+                assert!(windows[..index].iter().any(|earlier| earlier.outputs.contains(class)));
+            }
+        }
+    }
+
+    #[test]
+    fn stitched_windows_are_equivalent_and_errors_are_returned() {
+        let (netlist, egraph, roots) = parse_circuit(CIRCUIT);
+        let ids: Vec<Id> = roots.iter().map(|(_, id)| *id).collect();
+        let windows = partition(&egraph, &ids, Partition::Nodes(2));
+        let (dag, stitched, reports) = rewrite_partitioned(&egraph, &ids, &windows, WindowCost::Size, |index, egraph, roots| Ok::<_, ()>((egraph, roots, index))).unwrap();
+        assert_eq!(reports, (0..windows.len()).collect::<Vec<_>>());
+        assert!(dag.classes().all(|class| !matches!(&representative(&dag, class.id), Prop::Symbol(name) if name.as_str().starts_with("@cut"))));
+        let rewritten: Vec<(String, Id)> = roots.iter().map(|(name, _)| name.clone()).zip(stitched).collect();
+        assert_equivalent(&netlist, &dag, &rewritten);
+
+        let failed = rewrite_partitioned(&egraph, &ids, &windows, WindowCost::Size, |index, egraph, roots| if index == 1 { Err(index) } else { Ok((egraph, roots, ())) });
+        assert!(matches!(failed, Err(1)));
+    }
+}
@@ -0,0 +1,470 @@
+// Equivalence proofs from egg explanations.
+//
+// Every output of the input circuit and of the extracted circuit is added as an expression to an
+// egraph with explanations enabled, which is rewritten until each pair is in one class. The
+// explanation of each pair is flattened into a chain of terms, each one the term before it with
+// one rule applied at one position. The chain is written as text and can be read back and checked
+// without the egraph: a rule step must be an instance of the rule's patterns, and a constant-fold
+// step must replace a subterm that is constant under every assignment of its variables by that
+// constant.
+use crate::utils::language::{ConstantFold, Prop, PropEGraph};
+use crate::utils::runner_modified::Runner;
+use egg::{ENodeOrVar, FlatTerm, Id, Language, PatternAst, RecExpr, Rewrite, Var};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use thiserror::Error;
+
+// justification of the unions made by the constant folding analysis
+const CONSTANT_FOLD: &str = "constant-fold";
+// steps with more variables than this can not be evaluated
+const MAX_VARS: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum ProofError {
+    #[error("output `{0}` of the input circuit is not an output of the extracted circuit")]
+    MissingOutput(String),
+    #[error("line {line}: {msg}")]
+    Syntax { line: usize, msg: String },
+}
+
+// Output names and their expressions, see `netlist::output_exprs`
+pub type OutputExprs = [(String, RecExpr<Prop>)];
+
+pub struct ProofConfig {
+    pub iter_limit: usize,
+    pub node_limit: usize,
+    pub time_limit: Duration,
+    // outputs whose terms have more nodes (as trees) are not proven, every step prints a term in full
+    pub max_term_size: u64,
+}
+
+// The rule that rewrote the previous term, `forward` if it was applied left to right
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Justification {
+    pub rule: String,
+    pub forward: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    // `None` for the first term
+    pub justification: Option<Justification>,
+    pub term: RecExpr<Prop>,
+}
+
+#[derive(Debug, Clone)]
+pub enum OutputProof {
+    Proven { output: String, steps: Vec<Step> },
+    Unproven { output: String, reason: String },
+}
+
+impl OutputProof {
+    pub fn output(&self) -> &str {
+        match self {
+            OutputProof::Proven { output, .. } | OutputProof::Unproven { output, .. } => output,
+        }
+    }
+}
+
+// Number of nodes of the expression as a tree
+fn tree_size(expr: &RecExpr<Prop>) -> u64 {
+    let mut sizes: Vec<u64> = Vec::with_capacity(expr.as_ref().len());
+    for node in expr.as_ref() {
+        let size = node.children().iter().fold(1u64, |size, child| size.saturating_add(sizes[usize::from(*child)]));
+        sizes.push(size);
+    }
+    sizes.last().copied().unwrap_or(0)
+}
+
+// the rule marked somewhere in a flattened term
+fn justification(term: &FlatTerm<Prop>) -> Option<Justification> {
+    if let Some(rule) = term.forward_rule {
+        return Some(Justification { rule: rule.to_string(), forward: true });
+    }
+    if let Some(rule) = term.backward_rule {
+        return Some(Justification { rule: rule.to_string(), forward: false });
+    }
+    term.children.iter().find_map(justification)
+}
+
+// Proves every output of `extracted` equal to the output of `input` with the same name
+pub fn prove(
+    input: &OutputExprs,
+    extracted: &OutputExprs,
+    rules: &[Rewrite<Prop, ConstantFold>],
+    config: &ProofConfig,
+) -> Result<Vec<OutputProof>, ProofError> {
+    let extracted: HashMap<&str, &RecExpr<Prop>> = extracted.iter().map(|(name, expr)| (name.as_str(), expr)).collect();
+    let mut pairs = Vec::with_capacity(input.len());
+    for (name, expr) in input {
+        let other = extracted.get(name.as_str()).ok_or_else(|| ProofError::MissingOutput(name.clone()))?;
+        pairs.push((name.as_str(), expr, *other));
+    }
+
+    let mut egraph = PropEGraph::default().with_explanations_enabled();
+    let mut ids = Vec::with_capacity(pairs.len());
+    for (_, a, b) in &pairs {
+        ids.push((egraph.add_expr(a), egraph.add_expr(b)));
+    }
+    egraph.rebuild();
+    let pending = ids.clone();
+    let mut runner = Runner::default()
+        .with_egraph(egraph)
+        .with_iter_limit(config.iter_limit)
+        .with_node_limit(config.node_limit)
+        .with_time_limit(config.time_limit)
+        .with_hook(move |runner| {
+            // stop as soon as every output is proven
+            if pending.iter().all(|(a, b)| runner.egraph.find(*a) == runner.egraph.find(*b)) {
+                Err("every output is proven".to_string())
+            } else {
+                Ok(())
+            }
+        });
+    runner.roots = ids.iter().flat_map(|(a, b)| [*a, *b]).collect();
+    let mut runner = runner.run(rules);
+
+    let mut proofs = Vec::with_capacity(pairs.len());
+    for ((name, a, b), (a_id, b_id)) in pairs.into_iter().zip(ids) {
+        let output = name.to_string();
+        if runner.egraph.find(a_id) != runner.egraph.find(b_id) {
+            let reason = format!("not equal when rewriting stopped ({:?})", runner.stop_reason.as_ref().expect("the runner has stopped"));
+            proofs.push(OutputProof::Unproven { output, reason });
+            continue;
+        }
+        let size = tree_size(a).max(tree_size(b));
+        if size > config.max_term_size {
+            let reason = format!("equal, but the terms have {} nodes, more than the term size limit", size);
+            proofs.push(OutputProof::Unproven { output, reason });
+            continue;
+        }
+        let mut explanation = runner.explain_equivalence(a, b);
+        let steps = explanation
+            .make_flat_explanation()
+            .iter()
+            .map(|term| Step {
+                justification: justification(term),
+                term: term.get_recexpr(),
+            })
+            .collect();
+        proofs.push(OutputProof::Proven { output, steps });
+    }
+    Ok(proofs)
+}
+
+// The proof as text, `header` lines are written as comments first
+pub fn write_proof(proofs: &[OutputProof], header: &[String]) -> String {
+    let mut text = String::new();
+    for line in header {
+        text.push_str(&format!("# {}\n", line));
+    }
+    text.push_str("# every output is a chain of terms, `= RULE =>` applies RULE to the term above, `= RULE <=` applies it right to left\n");
+    for proof in proofs {
+        text.push_str(&format!("output {}\n", proof.output()));
+        match proof {
+            OutputProof::Proven { steps, .. } => {
+                for step in steps {
+                    if let Some(justification) = &step.justification {
+                        text.push_str(&format!("  = {} {}\n", justification.rule, if justification.forward { "=>" } else { "<=" }));
+                    }
+                    text.push_str(&format!("  {}\n", step.term));
+                }
+                text.push_str("qed\n");
+            }
+            OutputProof::Unproven { reason, .. } => text.push_str(&format!("unproven: {}\n", reason)),
+        }
+    }
+    text
+}
+
+// Reads a proof written by `write_proof`
+pub fn parse_proof(text: &str) -> Result<Vec<OutputProof>, ProofError> {
+    let mut proofs = Vec::new();
+    // the output being read and its steps
+    let mut current: Option<(String, Vec<Step>, Option<Justification>)> = None;
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let syntax = |msg: &str| ProofError::Syntax { line: line_number, msg: msg.to_string() };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("output ") {
+            if current.is_some() {
+                return Err(syntax("`output` before the previous output ended with `qed`"));
+            }
+            current = Some((name.trim().to_string(), Vec::new(), None));
+            continue;
+        }
+        let Some((output, steps, pending)) = current.as_mut() else {
+            return Err(syntax("expected `output NAME`"));
+        };
+        if let Some(reason) = line.strip_prefix("unproven:") {
+            if !steps.is_empty() {
+                return Err(syntax("`unproven` after the terms of a proof"));
+            }
+            proofs.push(OutputProof::Unproven { output: output.clone(), reason: reason.trim().to_string() });
+            current = None;
+        } else if line == "qed" {
+            if steps.is_empty() || pending.is_some() {
+                return Err(syntax("a proof ends with a term"));
+            }
+            let (output, steps, _) = current.take().expect("an output is being read");
+            proofs.push(OutputProof::Proven { output, steps });
+        } else if let Some(justification) = line.strip_prefix("= ") {
+            let (rule, forward) = match justification.rsplit_once(' ') {
+                Some((rule, "=>")) => (rule, true),
+                Some((rule, "<=")) => (rule, false),
+                _ => return Err(syntax("expected `= RULE =>` or `= RULE <=`")),
+            };
+            if steps.is_empty() || pending.is_some() {
+                return Err(syntax("a rule must follow a term"));
+            }
+            *pending = Some(Justification { rule: rule.trim().to_string(), forward });
+        } else {
+            let term: RecExpr<Prop> = line.parse().map_err(|err| syntax(&format!("invalid term: {}", err)))?;
+            if !steps.is_empty() && pending.is_none() {
+                return Err(syntax("two terms without a rule between them"));
+            }
+            steps.push(Step { justification: pending.take(), term });
+        }
+    }
+    if current.is_some() {
+        return Err(ProofError::Syntax { line: text.lines().count(), msg: "the last output does not end with `qed`".to_string() });
+    }
+    Ok(proofs)
+}
+
+fn root(expr: &RecExpr<Prop>) -> Id {
+    Id::from(expr.as_ref().len() - 1)
+}
+
+// Structural equality of two subterms
+fn same_term(a: &RecExpr<Prop>, a_id: Id, b: &RecExpr<Prop>, b_id: Id) -> bool {
+    let (x, y) = (&a[a_id], &b[b_id]);
+    x.matches(y) && x.children().iter().zip(y.children()).all(|(x, y)| same_term(a, *x, b, *y))
+}
+
+// Two expressions are the same term
+pub fn same_expr(a: &RecExpr<Prop>, b: &RecExpr<Prop>) -> bool {
+    same_term(a, root(a), b, root(b))
+}
+
+// Matches a pattern against a subterm. Variables that are not bound yet are bound if `bind`,
+// otherwise the match fails.
+fn match_pattern<'a>(
+    pattern: &PatternAst<Prop>,
+    pat: Id,
+    expr: &'a RecExpr<Prop>,
+    id: Id,
+    bindings: &mut HashMap<Var, (&'a RecExpr<Prop>, Id)>,
+    bind: bool,
+) -> bool {
+    match &pattern[pat] {
+        ENodeOrVar::Var(var) => match bindings.get(var) {
+            Some((bound, bound_id)) => same_term(bound, *bound_id, expr, id),
+            None if bind => {
+                bindings.insert(*var, (expr, id));
+                true
+            }
+            None => false,
+        },
+        ENodeOrVar::ENode(node) => {
+            node.matches(&expr[id]) && node.children().iter().zip(expr[id].children()).all(|(pat, id)| match_pattern(pattern, *pat, expr, *id, bindings, bind))
+        }
+    }
+}
+
+// The subterms where two terms may differ, from the roots down to the smallest one that contains
+// every difference. Outside of each of them the terms are equal.
+fn rewrite_sites(old: &RecExpr<Prop>, new: &RecExpr<Prop>) -> Vec<(Id, Id)> {
+    let mut sites = vec![(root(old), root(new))];
+    loop {
+        let (a, b) = *sites.last().expect("the roots are a site");
+        if !old[a].matches(&new[b]) {
+            break;
+        }
+        let differ: Vec<(Id, Id)> = old[a].children().iter().zip(new[b].children()).filter(|(x, y)| !same_term(old, **x, new, **y)).map(|(x, y)| (*x, *y)).collect();
+        if differ.len() != 1 {
+            break;
+        }
+        sites.push(differ[0]);
+    }
+    sites
+}
+
+// The truth tables of a subterm, inputs are numbered in `vars`
+fn truth_table(expr: &RecExpr<Prop>, id: Id, vars: &[String], words: usize) -> Option<Vec<u64>> {
+    let mut cone = HashSet::new();
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+        if cone.insert(id) {
+            stack.extend(expr[id].children());
+        }
+    }
+    let mut cone: Vec<Id> = cone.into_iter().collect();
+    cone.sort_unstable();
+    let mut values: HashMap<Id, Vec<u64>> = HashMap::with_capacity(cone.len());
+    for id in cone {
+        let value = match &expr[id] {
+            Prop::Symbol(name) if name.as_str() != "0" && name.as_str() != "1" => {
+                let var = vars.iter().position(|var| var == name.as_str())?;
+                (0..words).map(|word| cec::sim::assignment_word(var, word)).collect()
+            }
+            node => (0..words).map(|word| node.eval_bits(|child| values[&child][word])).collect::<Option<Vec<u64>>>()?,
+        };
+        values.insert(id, value);
+    }
+    values.remove(&id)
+}
+
+fn term_vars(expr: &RecExpr<Prop>, id: Id, vars: &mut Vec<String>) {
+    match &expr[id] {
+        Prop::Symbol(name) if name.as_str() != "0" && name.as_str() != "1" => {
+            if !vars.iter().any(|var| var == name.as_str()) {
+                vars.push(name.to_string());
+            }
+        }
+        node => node.for_each(|child| term_vars(expr, child, vars)),
+    }
+}
+
+// The value of a `0`/`1` literal, `None` for any other node
+fn literal(expr: &RecExpr<Prop>, id: Id) -> Option<bool> {
+    match &expr[id] {
+        Prop::Bool(value) => Some(*value),
+        Prop::Symbol(name) if name.as_str() == "0" || name.as_str() == "1" => Some(name.as_str() == "1"),
+        _ => None,
+    }
+}
+
+// A subterm is `value` under every assignment of its variables
+fn constant_by_evaluation(expr: &RecExpr<Prop>, id: Id, value: bool) -> bool {
+    let mut vars = Vec::new();
+    term_vars(expr, id, &mut vars);
+    if vars.len() > MAX_VARS {
+        return false;
+    }
+    let words = (1usize << vars.len()).div_ceil(64);
+    let mask = if vars.len() < 6 { (1u64 << (1 << vars.len())) - 1 } else { u64::MAX };
+    let expected = 0u64.wrapping_sub(value as u64);
+    truth_table(expr, id, &vars, words).is_some_and(|table| table.iter().all(|word| (word ^ expected) & mask == 0))
+}
+
+// The rules a proof may use, by name
+pub struct RuleTable {
+    rules: HashMap<String, (PatternAst<Prop>, PatternAst<Prop>)>,
+}
+
+impl RuleTable {
+    pub fn new(rules: &[Rewrite<Prop, ConstantFold>]) -> RuleTable {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| Some((rule.name.to_string(), (rule.searcher.get_pattern_ast()?.clone(), rule.applier.get_pattern_ast()?.clone()))))
+            .collect();
+        RuleTable { rules }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheckStats {
+    // steps that are instances of a rule
+    pub rule_steps: usize,
+    // constant-fold steps, checked by evaluating the folded subterm
+    pub evaluated_steps: usize,
+}
+
+// Checks that every step of a proof follows from the term before it
+pub fn check_steps(steps: &[Step], rules: &RuleTable, stats: &mut CheckStats) -> Result<(), String> {
+    if steps.first().is_some_and(|step| step.justification.is_some()) {
+        return Err("the first term has a rule".to_string());
+    }
+    for (index, pair) in steps.windows(2).enumerate() {
+        let (old, new) = (&pair[0].term, &pair[1].term);
+        let justification = pair[1].justification.as_ref().ok_or_else(|| format!("step {} has no rule", index + 1))?;
+        let sites = rewrite_sites(old, new);
+        // a rule may match above the smallest subterm that changed, the deepest site is tried first
+        let checked = if justification.rule == CONSTANT_FOLD {
+            stats.evaluated_steps += 1;
+            // the folded subterm is replaced by the literal of its value
+            let (from, to) = if justification.forward { (old, new) } else { (new, old) };
+            sites.iter().rev().any(|(a, b)| {
+                let (from_id, to_id) = if justification.forward { (*a, *b) } else { (*b, *a) };
+                literal(to, to_id).is_some_and(|value| constant_by_evaluation(from, from_id, value))
+            })
+        } else {
+            let (lhs, rhs) = rules.rules.get(&justification.rule).ok_or_else(|| format!("step {} uses the unknown rule `{}`", index + 1, justification.rule))?;
+            stats.rule_steps += 1;
+            let (from, to) = if justification.forward { (old, new) } else { (new, old) };
+            sites.iter().rev().any(|(a, b)| {
+                let (from_id, to_id) = if justification.forward { (*a, *b) } else { (*b, *a) };
+                let mut bindings = HashMap::new();
+                match_pattern(lhs, root_pattern(lhs), from, from_id, &mut bindings, true) && match_pattern(rhs, root_pattern(rhs), to, to_id, &mut bindings, false)
+            })
+        };
+        if !checked {
+            return Err(format!(
+                "step {} does not follow from the term before it by `{}` {}",
+                index + 1,
+                justification.rule,
+                if justification.forward { "=>" } else { "<=" }
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn root_pattern(pattern: &PatternAst<Prop>) -> Id {
+    Id::from(pattern.as_ref().len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::language::make_rules;
+
+    fn step(rule: Option<(&str, bool)>, term: &str) -> Step {
+        Step {
+            justification: rule.map(|(rule, forward)| Justification { rule: rule.to_string(), forward }),
+            term: term.parse().unwrap(),
+        }
+    }
+
+    fn check(steps: &[Step]) -> Result<(), String> {
+        check_steps(steps, &RuleTable::new(&make_rules()), &mut CheckStats::default())
+    }
+
+    #[test]
+    fn constant_fold_steps_replace_a_constant_subterm_by_its_value() {
+        assert!(check(&[step(None, "(+ b (* a (! a)))"), step(Some((CONSTANT_FOLD, true)), "(+ b 0)")]).is_ok());
+        assert!(check(&[step(None, "(+ b 0)"), step(Some((CONSTANT_FOLD, false)), "(+ b (* a (! a)))")]).is_ok());
+        // the wrong constant, and a subterm that is not constant
+        assert!(check(&[step(None, "(+ b (* a (! a)))"), step(Some((CONSTANT_FOLD, true)), "(+ b 1)")]).is_err());
+        assert!(check(&[step(None, "(+ b (* a c))"), step(Some((CONSTANT_FOLD, true)), "(+ b 0)")]).is_err());
+    }
+
+    #[test]
+    fn forged_constant_fold_steps_are_rejected() {
+        // equal under every assignment, but no constant was folded
+        assert!(check(&[step(None, "(* a (+ a b))"), step(Some((CONSTANT_FOLD, true)), "a")]).is_err());
+        assert!(check(&[step(None, "(+ c (* a (+ a b)))"), step(Some((CONSTANT_FOLD, false)), "(+ c a)")]).is_err());
+    }
+
+    #[test]
+    fn proofs_of_the_prover_check() {
+        let exprs = |terms: &[(&str, &str)]| -> Vec<(String, RecExpr<Prop>)> { terms.iter().map(|(name, term)| (name.to_string(), term.parse().unwrap())).collect() };
+        let input = exprs(&[("f", "(+ (* a (! a)) (* b (+ b c)))"), ("g", "(! (! (+ a (* 1 0))))")]);
+        let extracted = exprs(&[("f", "b"), ("g", "a")]);
+        let config = ProofConfig { iter_limit: 10, node_limit: 10_000, time_limit: Duration::from_secs(10), max_term_size: 1000 };
+        let mut stats = CheckStats::default();
+        for proof in prove(&input, &extracted, &make_rules(), &config).unwrap() {
+            match proof {
+                OutputProof::Proven { output, steps } => check_steps(&steps, &RuleTable::new(&make_rules()), &mut stats).unwrap_or_else(|err| panic!("{}: {}", output, err)),
+                OutputProof::Unproven { output, reason } => panic!("{}: {}", output, reason),
+            }
+        }
+        assert!(stats.rule_steps > 0);
+        // `(* 1 0)` is folded by the analysis
+        assert!(stats.evaluated_steps > 0);
+    }
+}
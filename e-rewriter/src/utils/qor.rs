@@ -0,0 +1,197 @@
+// Evaluators of the best circuit in the egraph, used by the runner to stop
+// early once a target delay is met or the cost stops improving.
+//
+// CostModel is the local cost model: the delay is the `wight_depth` cost of the
+// slowest output and the area the `wight_size` op cost of every node of the
+// extracted DAG. Abc writes the extracted circuit as eqn and reads the delay
+// and area from ABC's `stime` (with a liberty library) or `print_stats`.
+// SizeCost and DepthCost are the extraction costs tracked for the plateau check.
+use crate::utils::cost::{wight_depth, wight_size};
+use crate::utils::extract_new::Extractor2;
+use crate::utils::language::{ConstantFold, Prop};
+use crate::utils::runner_modified::{DelayEvaluator, PlateauCost, Qor};
+use egg::{CostFunction, EGraph, Id, Language};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// every class used by the best circuit of `roots`, children before parents
+fn extracted_classes<CF: CostFunction<Prop>>(extractor: &Extractor2<CF, Prop, ConstantFold>, egraph: &EGraph<Prop, ConstantFold>, roots: &[Id]) -> Vec<Id> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(Id, bool)> = roots.iter().rev().map(|id| (egraph.find(*id), false)).collect();
+    while let Some((id, expanded)) = stack.pop() {
+        if expanded {
+            order.push(id);
+            continue;
+        }
+        if !seen.insert(id) {
+            continue;
+        }
+        stack.push((id, true));
+        for child in extractor.find_best_node(id).children().iter().rev() {
+            stack.push((egraph.find(*child), false));
+        }
+    }
+    order
+}
+
+// the `wight_size` op cost of every node of the DAG extracted with `wight_size`
+fn dag_area(egraph: &EGraph<Prop, ConstantFold>, roots: &[Id]) -> usize {
+    let extractor = Extractor2::new(egraph, wight_size);
+    extracted_classes(&extractor, egraph, roots)
+        .into_iter()
+        .map(|id| wight_size.cost(extractor.find_best_node(id), |_| 0))
+        .sum()
+}
+
+pub struct SizeCost;
+
+impl PlateauCost<Prop, ConstantFold> for SizeCost {
+    fn cost(&mut self, egraph: &EGraph<Prop, ConstantFold>, roots: &[Id]) -> f64 {
+        dag_area(egraph, roots) as f64
+    }
+}
+
+pub struct DepthCost;
+
+impl PlateauCost<Prop, ConstantFold> for DepthCost {
+    fn cost(&mut self, egraph: &EGraph<Prop, ConstantFold>, roots: &[Id]) -> f64 {
+        let extractor = Extractor2::new(egraph, wight_depth);
+        roots.iter().map(|id| extractor.find_best_no_expr(*id).0).max().unwrap_or(0) as f64
+    }
+}
+
+pub struct CostModel;
+
+impl DelayEvaluator<Prop, ConstantFold> for CostModel {
+    fn evaluate(&mut self, egraph: &EGraph<Prop, ConstantFold>, roots: &[Id]) -> Result<Qor, String> {
+        let extractor = Extractor2::new(egraph, wight_depth);
+        let delay = roots.iter().map(|id| extractor.find_best_no_expr(*id).0).max().unwrap_or(0);
+        let area: usize = extracted_classes(&extractor, egraph, roots)
+            .into_iter()
+            .map(|id| wight_size.cost(extractor.find_best_node(id), |_| 0))
+            .sum();
+        Ok(Qor { delay: delay as f64, area: area as f64 })
+    }
+}
+
+pub struct Abc {
+    // path of the abc binary
+    pub abc: PathBuf,
+    // liberty library, without one the AIG levels and nodes are used as delay and area
+    pub lib: Option<PathBuf>,
+    // directory the extracted circuit is written to
+    pub work_dir: PathBuf,
+    pub root_names: Vec<String>,
+}
+
+// The best circuit as an eqn file, xor/xnor/mux/maj are written with `! * +`
+fn write_eqn(extractor: &Extractor2<wight_depth, Prop, ConstantFold>, egraph: &EGraph<Prop, ConstantFold>, roots: &[Id], root_names: &[String]) -> Result<String, String> {
+    let mut names: HashMap<Id, String> = HashMap::new();
+    let mut inputs = Vec::new();
+    let mut equations = Vec::new();
+    let classes = extracted_classes(extractor, egraph, roots);
+    // internal nodes are named `n<id>`, with `_` appended while that is an input or output name
+    let mut taken: HashSet<String> = root_names.iter().cloned().collect();
+    for id in &classes {
+        if let Prop::Symbol(name) = extractor.find_best_node(*id) {
+            taken.insert(name.to_string());
+        }
+    }
+    for id in classes {
+        let node = extractor.find_best_node(id);
+        let arg = |i: usize| names[&egraph.find(node.children()[i])].clone();
+        let expr = match node {
+            Prop::Symbol(name) if name.as_str() == "0" || name.as_str() == "1" => name.to_string(),
+            Prop::Symbol(name) => {
+                inputs.push(name.to_string());
+                names.insert(id, name.to_string());
+                continue;
+            }
+            Prop::Bool(value) => (*value as u8).to_string(),
+            Prop::Not(_) => format!("!{}", arg(0)),
+            Prop::And(_) => format!("{} * {}", arg(0), arg(1)),
+            Prop::Or(_) => format!("{} + {}", arg(0), arg(1)),
+            Prop::Implies(_) => format!("!{} + {}", arg(0), arg(1)),
+            Prop::Xor(_) => format!("{a} * !{b} + !{a} * {b}", a = arg(0), b = arg(1)),
+            Prop::Xnor(_) => format!("{a} * {b} + !{a} * !{b}", a = arg(0), b = arg(1)),
+            Prop::Mux(_) => format!("{s} * {a} + !{s} * {b}", s = arg(0), a = arg(1), b = arg(2)),
+            Prop::Maj(_) => format!("{a} * {b} + {a} * {c} + {b} * {c}", a = arg(0), b = arg(1), c = arg(2)),
+            other => return Err(format!("`{}` nodes cannot be written as eqn", other)),
+        };
+        let mut name = format!("n{}", id);
+        while taken.contains(&name) {
+            name.push('_');
+        }
+        equations.push(format!("{} = {};", name, expr));
+        names.insert(id, name);
+    }
+    for (root, name) in roots.iter().zip(root_names) {
+        equations.push(format!("{} = {};", name, names[&egraph.find(*root)]));
+    }
+    inputs.sort();
+    Ok(format!("INORDER = {};\nOUTORDER = {};\n{}\n", inputs.join(" "), root_names.join(" "), equations.join("\n")))
+}
+
+fn parse_number(output: &str, pattern: &str) -> Option<f64> {
+    Regex::new(pattern).unwrap().captures(output)?.get(1)?.as_str().parse().ok()
+}
+
+impl DelayEvaluator<Prop, ConstantFold> for Abc {
+    fn evaluate(&mut self, egraph: &EGraph<Prop, ConstantFold>, roots: &[Id]) -> Result<Qor, String> {
+        if roots.len() != self.root_names.len() {
+            return Err(format!("{} roots but {} output names", roots.len(), self.root_names.len()));
+        }
+        let extractor = Extractor2::new(egraph, wight_depth);
+        let eqn = write_eqn(&extractor, egraph, roots, &self.root_names)?;
+        fs::create_dir_all(&self.work_dir).map_err(|err| err.to_string())?;
+        let path = self.work_dir.join("qor_check.eqn");
+        fs::write(&path, eqn).map_err(|err| err.to_string())?;
+
+        let script = match &self.lib {
+            Some(lib) => format!("read_eqn {}; read_lib {}; st; dch; map; topo; stime", path.display(), lib.display()),
+            None => format!("read_eqn {}; st; ps", path.display()),
+        };
+        let output = Command::new(&self.abc)
+            .arg("-c")
+            .arg(&script)
+            .output()
+            .map_err(|err| format!("cannot run {}: {}", self.abc.display(), err))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (delay, area) = match self.lib {
+            Some(_) => (parse_number(&stdout, r"Delay\s*=\s*([0-9.]+)"), parse_number(&stdout, r"Area\s*=\s*([0-9.]+)")),
+            None => (parse_number(&stdout, r"lev\s*=\s*([0-9]+)"), parse_number(&stdout, r"and\s*=\s*([0-9]+)")),
+        };
+        match (delay, area) {
+            (Some(delay), Some(area)) => Ok(Qor { delay, area }),
+            _ => Err(format!("no delay and area in the output of `{}`: {}", script, stdout.trim())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::netlist::add_to_egraph;
+    use cec::eqn_parser::parse_eqn;
+
+    #[test]
+    fn internal_nodes_do_not_take_input_names() {
+        let mut egraph = EGraph::<Prop, ConstantFold>::default();
+        // `(! a)` is class 1, the input `n1` class 2
+        let root = egraph.add_expr(&"(* (! a) n1)".parse().unwrap());
+        egraph.rebuild();
+        let extractor = Extractor2::new(&egraph, wight_depth);
+        let eqn = write_eqn(&extractor, &egraph, &[root], &["f".to_string()]).unwrap();
+        assert!(eqn.contains("n1_ = !a;"), "{}", eqn);
+
+        // read back into the same egraph, the output is the same term
+        let written = parse_eqn(&eqn).unwrap();
+        let (outputs, _) = add_to_egraph(&written, &mut egraph).unwrap();
+        egraph.rebuild();
+        assert_eq!(egraph.find(outputs[0]), egraph.find(root));
+    }
+}
@@ -0,0 +1,291 @@
+// Rewrite rules read from a rule file instead of being compiled in.
+//
+// Text format, one rule per line (`#` starts a comment):
+//   [-]name: lhs => rhs
+//   [-]name: lhs <=> rhs
+// A leading `-` disables the rule, `<=>` also adds the reverse rule `name-rev`.
+// JSON format: a list of {"name", "lhs", "rhs", "direction", "enabled"} objects,
+// `direction` is "=>" (the default) or "<=>" and `enabled` defaults to true.
+use crate::utils::language::{ConstantFold, Prop};
+use egg::{Analysis, ENodeOrVar, Pattern, Rewrite};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+// the rule sets built into the binary
+pub const DEFAULT_RULES: &str = include_str!("../../rules/default.rules");
+pub const SIMPLIFY_RULES: &str = include_str!("../../rules/simplify.rules");
+
+#[derive(Debug, Error)]
+pub enum RuleError {
+    #[error("line {line}: {msg}")]
+    Syntax { line: usize, msg: String },
+    #[error("rule `{name}`: {msg}")]
+    Invalid { name: String, msg: String },
+    #[error("rule `{name}` is defined more than once")]
+    Duplicate { name: String },
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Direction {
+    #[default]
+    #[serde(rename = "=>")]
+    Forward,
+    #[serde(rename = "<=>")]
+    Both,
+}
+
+// A rule as written in the rule file, before its patterns are parsed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSpec {
+    pub name: String,
+    pub lhs: String,
+    pub rhs: String,
+    #[serde(default)]
+    pub direction: Direction,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+// the rule as a line of the text format
+impl fmt::Display for RuleSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self.direction {
+            Direction::Forward => "=>",
+            Direction::Both => "<=>",
+        };
+        let disabled = if self.enabled { "" } else { "-" };
+        write!(f, "{}{}: {} {} {}", disabled, self.name, self.lhs, arrow, self.rhs)
+    }
+}
+
+// Parses the text format
+pub fn parse_rules_text(src: &str) -> Result<Vec<RuleSpec>, RuleError> {
+    let mut specs = Vec::new();
+    for (index, raw) in src.lines().enumerate() {
+        let syntax = |msg: String| RuleError::Syntax { line: index + 1, msg };
+        let line = raw.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (enabled, line) = match line.strip_prefix('-') {
+            Some(rest) => (false, rest.trim_start()),
+            None => (true, line),
+        };
+        let Some((name, rule)) = line.split_once(':') else {
+            return Err(syntax(format!("expected `name: lhs => rhs`, found `{}`", line)));
+        };
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(syntax(format!("invalid rule name `{}`", name)));
+        }
+        let (direction, (lhs, rhs)) = if let Some(sides) = rule.split_once("<=>") {
+            (Direction::Both, sides)
+        } else if let Some(sides) = rule.split_once("=>") {
+            (Direction::Forward, sides)
+        } else {
+            return Err(syntax(format!("rule `{}` needs `=>` or `<=>`", name)));
+        };
+        let (lhs, rhs) = (lhs.trim(), rhs.trim());
+        if lhs.is_empty() || rhs.is_empty() || rhs.contains("=>") {
+            return Err(syntax(format!("rule `{}` needs exactly one left-hand and one right-hand side", name)));
+        }
+        specs.push(RuleSpec {
+            name: name.to_string(),
+            lhs: lhs.to_string(),
+            rhs: rhs.to_string(),
+            direction,
+            enabled,
+        });
+    }
+    Ok(specs)
+}
+
+// Parses the JSON format
+pub fn parse_rules_json(src: &str) -> Result<Vec<RuleSpec>, RuleError> {
+    Ok(serde_json::from_str(src)?)
+}
+
+// Reads a rule file, `.json` files are read as JSON and anything else as text
+pub fn read_rule_file(path: &str) -> Result<Vec<RuleSpec>, RuleError> {
+    let src = fs::read_to_string(path)?;
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("json") => parse_rules_json(&src),
+        _ => parse_rules_text(&src),
+    }
+}
+
+fn parse_pattern(name: &str, side: &str, src: &str) -> Result<Pattern<Prop>, RuleError> {
+    let invalid = |msg: String| RuleError::Invalid { name: name.to_string(), msg };
+    let pattern: Pattern<Prop> = src.parse().map_err(|err| invalid(format!("invalid {} `{}`: {}", side, src, err)))?;
+    Ok(pattern)
+}
+
+// a searcher that is just `?x` would match every e-class
+fn check_searcher(name: &str, side: &str, pattern: &Pattern<Prop>) -> Result<(), RuleError> {
+    if let Some(ENodeOrVar::Var(var)) = pattern.ast.as_ref().last() {
+        return Err(RuleError::Invalid {
+            name: name.to_string(),
+            msg: format!("{} `{}` is a bare variable and would match every e-class", side, var),
+        });
+    }
+    Ok(())
+}
+
+fn check_bound(name: &str, searcher: &Pattern<Prop>, applier: &Pattern<Prop>) -> Result<(), RuleError> {
+    let bound = searcher.vars();
+    match applier.vars().into_iter().find(|var| !bound.contains(var)) {
+        Some(var) => Err(RuleError::Invalid {
+            name: name.to_string(),
+            msg: format!("`{}` is not bound by `{}`", var, searcher),
+        }),
+        None => Ok(()),
+    }
+}
+
+// Parses and checks the patterns of every rule and builds the enabled ones.
+// Disabled rules are checked too, so a rule file never hides broken rules.
+pub fn build_rewrites<N: Analysis<Prop>>(specs: &[RuleSpec]) -> Result<Vec<Rewrite<Prop, N>>, RuleError> {
+    let mut rewrites = Vec::new();
+    let mut names = HashSet::new();
+    for spec in specs {
+        let lhs = parse_pattern(&spec.name, "left-hand side", &spec.lhs)?;
+        let rhs = parse_pattern(&spec.name, "right-hand side", &spec.rhs)?;
+        check_searcher(&spec.name, "left-hand side", &lhs)?;
+        check_bound(&spec.name, &lhs, &rhs)?;
+        let mut directed = vec![(spec.name.clone(), lhs.clone(), rhs.clone())];
+        if spec.direction == Direction::Both {
+            check_searcher(&spec.name, "right-hand side", &rhs)?;
+            check_bound(&spec.name, &rhs, &lhs)?;
+            directed.push((format!("{}-rev", spec.name), rhs, lhs));
+        }
+        if !spec.enabled {
+            continue;
+        }
+        for (name, searcher, applier) in directed {
+            if !names.insert(name.clone()) {
+                return Err(RuleError::Duplicate { name });
+            }
+            let rewrite = Rewrite::new(name.clone(), searcher, applier).map_err(|msg| RuleError::Invalid { name, msg })?;
+            rewrites.push(rewrite);
+        }
+    }
+    Ok(rewrites)
+}
+
+// Reads and builds the rules of a rule file
+pub fn load_rules(path: &str) -> Result<Vec<Rewrite<Prop, ConstantFold>>, RuleError> {
+    build_rewrites(&read_rule_file(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule_names(rules: &[Rewrite<Prop, ConstantFold>]) -> Vec<String> {
+        rules.iter().map(|rule| rule.name.to_string()).collect()
+    }
+
+    fn invalid(src: &str) -> String {
+        match parse_rules_text(src).and_then(|specs| build_rewrites(&specs)) {
+            Err(RuleError::Invalid { name, msg }) => format!("{}: {}", name, msg),
+            other => panic!("expected an invalid rule, got {:?}", other.map(|rules| rule_names(&rules))),
+        }
+    }
+
+    #[test]
+    fn syntax_errors_report_their_line() {
+        let src = "# comment\na: (* ?a 1) => ?a\n\nb (+ ?a 0) => ?a\n";
+        assert!(matches!(parse_rules_text(src), Err(RuleError::Syntax { line: 4, .. })));
+        assert!(matches!(parse_rules_text("a: (* ?a 1) ?a"), Err(RuleError::Syntax { line: 1, .. })));
+        assert!(matches!(parse_rules_text("a: (* ?a 1) => ?a\n-b: (+ ?a 0) => ?a\nc: ?a => ?a => ?a"), Err(RuleError::Syntax { line: 3, .. })));
+    }
+
+    #[test]
+    fn unbound_variables_and_bare_searchers_are_rejected() {
+        assert!(invalid("a: (* ?a 1) => ?b").contains("`?b` is not bound"));
+        // the reverse of a two-way rule must bind the variables of the left-hand side too
+        assert!(invalid("a: (* ?a ?b) <=> ?a").starts_with("a: right-hand side `?a` is a bare variable"));
+        assert!(invalid("a: (* ?a ?b) <=> (! ?a)").contains("`?b` is not bound"));
+        assert!(invalid("a: ?a => (* ?a 1)").starts_with("a: left-hand side `?a` is a bare variable"));
+        // disabled rules are checked too
+        assert!(invalid("-a: ?a => (* ?a 1)").contains("bare variable"));
+    }
+
+    #[test]
+    fn rule_names_are_unique() {
+        let specs = parse_rules_text("a: (* ?a 1) => ?a\na: (+ ?a 0) => ?a").unwrap();
+        assert!(matches!(build_rewrites::<ConstantFold>(&specs), Err(RuleError::Duplicate { name }) if name == "a"));
+        let specs = parse_rules_text("a: (* ?a ?b) <=> (* ?b ?a)\na-rev: (+ ?a 0) => ?a").unwrap();
+        assert!(matches!(build_rewrites::<ConstantFold>(&specs), Err(RuleError::Duplicate { name }) if name == "a-rev"));
+        // a disabled rule does not take its name
+        let specs = parse_rules_text("-a: (* ?a 1) => ?a\na: (+ ?a 0) => ?a").unwrap();
+        assert_eq!(rule_names(&build_rewrites(&specs).unwrap()), ["a"]);
+    }
+
+    #[test]
+    fn two_way_rules_add_their_reverse_and_disabled_rules_are_skipped() {
+        let specs = parse_rules_text("comm: (* ?a ?b) <=> (* ?b ?a)\n-id: (* ?a 1) => ?a\n  neg : (! (! ?a)) => ?a # comment").unwrap();
+        assert_eq!(specs[1].to_string(), "-id: (* ?a 1) => ?a");
+        assert_eq!(rule_names(&build_rewrites(&specs).unwrap()), ["comm", "comm-rev", "neg"]);
+    }
+
+    #[test]
+    fn json_rules_default_to_forward_and_enabled() {
+        let specs = parse_rules_json(r#"[
+            {"name": "id", "lhs": "(* ?a 1)", "rhs": "?a"},
+            {"name": "comm", "lhs": "(+ ?a ?b)", "rhs": "(+ ?b ?a)", "direction": "<=>"},
+            {"name": "off", "lhs": "(+ ?a 0)", "rhs": "?a", "enabled": false}
+        ]"#)
+        .unwrap();
+        assert_eq!((specs[0].direction, specs[0].enabled), (Direction::Forward, true));
+        assert_eq!((specs[1].direction, specs[1].enabled), (Direction::Both, true));
+        assert!(!specs[2].enabled);
+        assert_eq!(rule_names(&build_rewrites(&specs).unwrap()), ["id", "comm", "comm-rev"]);
+        assert!(matches!(parse_rules_json(r#"[{"name": "id", "lhs": "(* ?a 1)"}]"#), Err(RuleError::Json(_))));
+    }
+
+    #[test]
+    fn built_in_rule_files_have_the_rule_names_of_the_compiled_rules() {
+        let two_way = |name: &str| [name.to_string(), format!("{}-rev", name)];
+        let mut default: Vec<String> = [
+            "null-element1", "null-element2", "complements1", "complements2", "covering1", "covering2", "identity1", "identity2'", "idempotency1",
+            "idempotency2", "involution1", "combining1", "combining2", "consensus1", "consensus2", "distributivity1", "distributivity2", "distributivity3",
+        ]
+        .map(String::from)
+        .into();
+        default.extend(["commutativity1", "commutativity2", "associativity1", "associativity2", "de-morgan1", "de-morgan2"].into_iter().flat_map(two_way));
+        default.extend(
+            [
+                "xor-self", "xor-zero", "xor-one", "xor-complement", "xnor-self", "mux-same", "mux-one", "mux-zero", "mux-select-not", "mux-to-and",
+                "mux-to-or", "mux-not-to-xor", "maj-same1", "maj-complement", "maj-zero", "maj-one", "maj-rotate", "maj-swap",
+            ]
+            .map(String::from),
+        );
+        default.extend(
+            ["xor-def", "xnor-def", "xnor-not-xor", "xnor-xor-not", "xor-commutativity", "xnor-commutativity", "xor-associativity", "mux-def", "mux-not", "maj-def", "maj-not"]
+                .into_iter()
+                .flat_map(two_way),
+        );
+        let simplify = [
+            "q", "null-element1", "null-element2", "complements1", "identity1", "identity2'", "involution1", "associativity2", "d", "e", "f", "r", "th1", "th2",
+            "th3", "th4", "th5", "th6", "th7", "th8",
+        ];
+
+        let built = |src: &str| rule_names(&build_rewrites(&parse_rules_text(src).unwrap()).unwrap());
+        assert_eq!(built(DEFAULT_RULES), default);
+        assert_eq!(built(SIMPLIFY_RULES), simplify);
+    }
+}
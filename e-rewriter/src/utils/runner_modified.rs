@@ -5,7 +5,7 @@ use indexmap::IndexMap;
 use crate::*;
 use instant::Duration;
 use egg::*;
-use serde_json::Value;
+use serde_json::{json, Value};
 use serde::__private::fmt::Display;
 use std::io;
 /** Faciliates running rewrites over an [`EGraph`].
@@ -160,7 +160,10 @@ pub struct Runner<L: Language, N: Analysis<L>, IterData = ()> {
     node_limit: usize,
     time_limit: Duration,
     target_delay: f64,
-    check_interval:i32,
+    check_interval: usize,
+    evaluator: Option<Box<dyn DelayEvaluator<L, N>>>,
+    plateau: Option<(usize, Box<dyn PlateauCost<L, N>>)>,
+    initial_cost: Option<f64>,
     start_time: Option<Instant>,
     scheduler: Box<dyn RewriteScheduler<L, N>>,
 }
@@ -194,6 +197,9 @@ where
             time_limit,
             target_delay,
             check_interval,
+            evaluator,
+            plateau,
+            initial_cost,
             start_time,
             scheduler: _,
         } = self;
@@ -207,6 +213,11 @@ where
             .field("iter_limit", iter_limit)
             .field("node_limit", node_limit)
             .field("time_limit", time_limit)
+            .field("target_delay", target_delay)
+            .field("check_interval", check_interval)
+            .field("evaluator", &evaluator.as_ref().map(|_| format_args!("<dyn DelayEvaluator ..>")))
+            .field("plateau", &plateau.as_ref().map(|(patience, _)| patience))
+            .field("initial_cost", initial_cost)
             .field("start_time", start_time)
             .field("scheduler", &format_args!("<dyn RewriteScheduler ..>"))
             .finish()
@@ -227,6 +238,11 @@ pub enum StopReason {
     NodeLimit(usize),
     /// The time limit was hit. The data is the time limit in seconds.
     TimeLimit(f64),
+    /// The evaluated delay met the target delay. The data is the evaluated delay.
+    TargetDelay(f64),
+    /// The best extraction cost did not improve for the given number of
+    /// iterations. The data is the best cost.
+    Plateau(f64),
     /// Some other reason to stop.
     Other(String),
 }
@@ -252,6 +268,11 @@ pub struct Report {
     pub search_time: f64,
     pub apply_time: f64,
     pub rebuild_time: f64,
+    /// The best extraction cost of the input and after every iteration,
+    /// empty without a plateau check.
+    pub cost_history: Vec<f64>,
+    /// The number of times a rule was banned.
+    pub bans: usize,
 }
 
 impl std::fmt::Display for Report {
@@ -263,10 +284,15 @@ impl std::fmt::Display for Report {
         writeln!(f, "  Iterations: {}", self.iterations)?;
         writeln!(f, "  Egraph size: {} nodes, {} classes, {} memo", self.egraph_nodes, self.egraph_classes, self.memo_size)?;
         writeln!(f, "  Rebuilds: {}", self.rebuilds)?;
+        writeln!(f, "  Bans: {}", self.bans)?;
         writeln!(f, "  Total time: {}", self.total_time)?;
         writeln!(f, "    Search:  ({:.2}) {}", self.search_time / self.total_time, self.search_time)?;
         writeln!(f, "    Apply:   ({:.2}) {}", self.apply_time / self.total_time, self.apply_time)?;
         writeln!(f, "    Rebuild: ({:.2}) {}", self.rebuild_time / self.total_time, self.rebuild_time)?;
+        if !self.cost_history.is_empty() {
+            let history: Vec<String> = self.cost_history.iter().map(|cost| cost.to_string()).collect();
+            writeln!(f, "  Cost history: {}", history.join(" -> "))?;
+        }
         Ok(())
     }
 }
@@ -291,6 +317,9 @@ pub struct Iteration<IterData> {
     /// A map from rule name to number of times it was _newly_ applied
     /// in this iteration.
     pub applied: IndexMap<Symbol, usize>,
+    /// A map from rule name to the number of matches the scheduler let
+    /// through in this iteration.
+    pub matched: IndexMap<Symbol, usize>,
     /// Seconds spent running hooks.
     pub hook_time: f64,
     /// Seconds spent searching in this iteration.
@@ -308,6 +337,47 @@ pub struct Iteration<IterData> {
     pub n_rebuilds: usize,
     /// If the runner stopped on this iterations, this is the reason
     pub stop_reason: Option<StopReason>,
+    /// The quality of results evaluated after this iteration, only set
+    /// every `check_interval` iterations when an evaluator is given.
+    pub qor: Option<Qor>,
+    /// The best extraction cost after this iteration, only set when the
+    /// runner checks for a plateau.
+    pub best_cost: Option<f64>,
+    /// The rules the scheduler banned in this iteration.
+    pub banned: Vec<BanEvent>,
+}
+
+/// A rule banned by the scheduler because it matched too often.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize))]
+pub struct BanEvent {
+    pub rule: Symbol,
+    /// The number of matches found, searching stops after `threshold + 1`.
+    pub matches: usize,
+    pub threshold: usize,
+    /// The number of iterations the rule is banned for.
+    pub ban_length: usize,
+}
+
+/// Quality of results of the best circuit in the egraph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize))]
+pub struct Qor {
+    pub delay: f64,
+    pub area: f64,
+}
+
+/// Evaluates the best circuit of an egraph, used by [`Runner`] to stop
+/// early once the target delay is met.
+pub trait DelayEvaluator<L: Language, N: Analysis<L>> {
+    /// Evaluates the circuit rooted at `roots`.
+    fn evaluate(&mut self, egraph: &EGraph<L, N>, roots: &[Id]) -> Result<Qor, String>;
+}
+
+/// The extraction cost tracked by [`Runner`] to stop once it stops improving.
+pub trait PlateauCost<L: Language, N: Analysis<L>> {
+    /// The cost of the best circuit rooted at `roots`, lower is better.
+    fn cost(&mut self, egraph: &EGraph<L, N>, roots: &[Id]) -> f64;
 }
 
 type RunnerResult<T> = std::result::Result<T, StopReason>;
@@ -325,7 +395,10 @@ where
             node_limit: 10_000,
             time_limit: Duration::from_secs(5),
             target_delay: 0.0,
-            check_interval:0,
+            check_interval: 1,
+            evaluator: None,
+            plateau: None,
+            initial_cost: None,
             egraph: EGraph::new(analysis),
             roots: vec![],
             iterations: vec![],
@@ -352,14 +425,31 @@ where
         Self { time_limit, ..self }
     }
 
+    /// Sets the target delay, the runner stops with [`StopReason::TargetDelay`]
+    /// once the evaluated delay is at most this. Default: 0
     pub fn with_target_delay(self, target_delay: f64) -> Self {
         Self { target_delay, ..self }
     }
 
-    pub fn with_check_interval(self, check_interval: i32) -> Self {
+    /// Sets how many iterations pass between two evaluations. Default: 1
+    pub fn with_check_interval(self, check_interval: usize) -> Self {
         Self { check_interval, ..self }
     }
 
+    /// Sets the evaluator of the best circuit, without one the target
+    /// delay is never checked.
+    pub fn with_evaluator(self, evaluator: impl DelayEvaluator<L, N> + 'static) -> Self {
+        let evaluator = Some(Box::new(evaluator) as Box<dyn DelayEvaluator<L, N>>);
+        Self { evaluator, ..self }
+    }
+
+
+    /// Stops with [`StopReason::Plateau`] once `cost` did not improve
+    /// for `patience` iterations.
+    pub fn with_plateau(self, patience: usize, cost: impl PlateauCost<L, N> + 'static) -> Self {
+        let plateau = Some((patience, Box::new(cost) as Box<dyn PlateauCost<L, N>>));
+        Self { plateau, ..self }
+    }
 
     pub fn with_root_ids(mut self, root_ids: Vec<usize>) -> Self {
         self.roots = root_ids.iter().cloned().map(Id::from).collect();
@@ -434,18 +524,19 @@ where
     {
         let rules: Vec<&Rewrite<L, N>> = rules.into_iter().collect();
         check_rules(&rules);
-    
-        let mut iteration_count = 0;
+        if let Some((_, cost)) = self.plateau.as_mut() {
+            self.initial_cost = Some(cost.cost(&self.egraph, &self.roots));
+        }
     
         loop {
             let iter = self.run_one(&rules);
             self.iterations.push(iter);
-            let stop_reason = self.iterations.last().unwrap().stop_reason.clone();
-            iteration_count += 1;
-            if iteration_count % self.check_interval == 0{
-
-
-
+            let mut stop_reason = self.iterations.last().unwrap().stop_reason.clone();
+            if stop_reason.is_none() {
+                stop_reason = self.check_target_delay();
+            }
+            if stop_reason.is_none() {
+                stop_reason = self.check_plateau();
             }
 
             // parrallel version
@@ -485,6 +576,8 @@ where
             
             if let Some(stop_reason) = stop_reason.or_else(|| self.check_limits().err()) {
                 info!("Stopping: {:?}", stop_reason);
+                // the reason the last iteration ended the run, as in egg
+                self.iterations.last_mut().unwrap().stop_reason = Some(stop_reason.clone());
                 self.stop_reason = Some(stop_reason);
                 break;
             }
@@ -570,9 +663,102 @@ where
             apply_time: self.iterations.iter().map(|i| i.apply_time).sum(),
             rebuild_time: self.iterations.iter().map(|i| i.rebuild_time).sum(),
             total_time: self.iterations.iter().map(|i| i.total_time).sum(),
+            cost_history: self.cost_history(),
+            bans: self.iterations.iter().map(|i| i.banned.len()).sum(),
         }
     }
 
+    /// The [`report`](Runner::report()) with the data of every iteration
+    /// and per-rule match and apply counts as JSON.
+    pub fn report_json(&self) -> Value {
+        let report = self.report();
+        let stop_reason = match &report.stop_reason {
+            StopReason::Saturated => json!({ "reason": "Saturated" }),
+            StopReason::IterationLimit(limit) => json!({ "reason": "IterationLimit", "value": limit }),
+            StopReason::NodeLimit(limit) => json!({ "reason": "NodeLimit", "value": limit }),
+            StopReason::TimeLimit(seconds) => json!({ "reason": "TimeLimit", "value": seconds }),
+            StopReason::TargetDelay(delay) => json!({ "reason": "TargetDelay", "value": delay }),
+            StopReason::Plateau(cost) => json!({ "reason": "Plateau", "value": cost }),
+            StopReason::Other(msg) => json!({ "reason": "Other", "value": msg }),
+        };
+        let rule_counts = |matched: &IndexMap<Symbol, usize>, applied: &IndexMap<Symbol, usize>| {
+            let mut rules = serde_json::Map::new();
+            for name in matched.keys().chain(applied.keys()) {
+                rules.entry(name.to_string()).or_insert_with(|| {
+                    json!({
+                        "matched": matched.get(name).copied().unwrap_or(0),
+                        "applied": applied.get(name).copied().unwrap_or(0),
+                    })
+                });
+            }
+            Value::Object(rules)
+        };
+
+        let ban_events = |iteration: usize, banned: &[BanEvent]| -> Vec<Value> {
+            banned
+                .iter()
+                .map(|ban| {
+                    json!({
+                        "iteration": iteration,
+                        "rule": ban.rule.as_str(),
+                        "matches": ban.matches,
+                        "threshold": ban.threshold,
+                        "ban_length": ban.ban_length,
+                    })
+                })
+                .collect()
+        };
+
+        let mut total_matched: IndexMap<Symbol, usize> = IndexMap::default();
+        let mut total_applied: IndexMap<Symbol, usize> = IndexMap::default();
+        let iterations: Vec<Value> = self
+            .iterations
+            .iter()
+            .enumerate()
+            .map(|(i, iteration)| {
+                for (name, count) in &iteration.matched {
+                    *total_matched.entry(*name).or_insert(0) += count;
+                }
+                for (name, count) in &iteration.applied {
+                    *total_applied.entry(*name).or_insert(0) += count;
+                }
+                json!({
+                    "iteration": i + 1,
+                    "egraph_nodes": iteration.egraph_nodes,
+                    "egraph_classes": iteration.egraph_classes,
+                    "hook_time": iteration.hook_time,
+                    "search_time": iteration.search_time,
+                    "apply_time": iteration.apply_time,
+                    "rebuild_time": iteration.rebuild_time,
+                    "total_time": iteration.total_time,
+                    "n_rebuilds": iteration.n_rebuilds,
+                    "rules": rule_counts(&iteration.matched, &iteration.applied),
+                    "banned": ban_events(i + 1, &iteration.banned),
+                    "qor": iteration.qor.map(|qor| json!({ "delay": qor.delay, "area": qor.area })),
+                    "best_cost": iteration.best_cost,
+                    "stop_reason": iteration.stop_reason.as_ref().map(|reason| format!("{:?}", reason)),
+                })
+            })
+            .collect();
+
+        json!({
+            "stop_reason": stop_reason,
+            "iterations": report.iterations,
+            "egraph_nodes": report.egraph_nodes,
+            "egraph_classes": report.egraph_classes,
+            "memo_size": report.memo_size,
+            "rebuilds": report.rebuilds,
+            "total_time": report.total_time,
+            "search_time": report.search_time,
+            "apply_time": report.apply_time,
+            "rebuild_time": report.rebuild_time,
+            "cost_history": report.cost_history,
+            "rules": rule_counts(&total_matched, &total_applied),
+            "bans": self.iterations.iter().enumerate().flat_map(|(i, iteration)| ban_events(i + 1, &iteration.banned)).collect::<Vec<_>>(),
+            "per_iteration": iterations,
+        })
+    }
+
     fn run_one(&mut self, rules: &[&Rewrite<L, N>]) -> Iteration<IterData> {
         assert!(self.stop_reason.is_none());
 
@@ -603,6 +789,7 @@ where
         let start_time = Instant::now();
 
         let mut matches = Vec::new();
+        let mut matched = IndexMap::default();
         let mut applied = IndexMap::default();
         result = result.and_then(|_| {
             rules.iter().try_for_each(|rw| {
@@ -621,6 +808,9 @@ where
             rules.iter().zip(matches).try_for_each(|(rw, ms)| {
                 let total_matches: usize = ms.iter().map(|m| m.substs.len()).sum();
                 debug!("Applying {} {} times", rw.name, total_matches);
+                if total_matches > 0 {
+                    *matched.entry(rw.name.to_owned()).or_insert(0) += total_matches;
+                }
 
                 let actually_matched = self.scheduler.apply_rewrite(i, &mut self.egraph, rw, ms);
                 if actually_matched > 0 {
@@ -666,6 +856,7 @@ where
 
         Iteration {
             applied,
+            matched,
             egraph_nodes,
             egraph_classes,
             hook_time,
@@ -676,7 +867,52 @@ where
             data: IterData::make(self),
             total_time: start_time.elapsed().as_secs_f64(),
             stop_reason: result.err(),
+            qor: None,
+            best_cost: None,
+            banned: self.scheduler.take_ban_events(),
+        }
+    }
+
+    // Evaluates the best circuit every `check_interval` iterations and records it in the last iteration
+    fn check_target_delay(&mut self) -> Option<StopReason> {
+        let iterations = self.iterations.len();
+        if self.check_interval == 0 || !iterations.is_multiple_of(self.check_interval) {
+            return None;
         }
+        let evaluator = self.evaluator.as_mut()?;
+        match evaluator.evaluate(&self.egraph, &self.roots) {
+            Ok(qor) => {
+                info!("Iteration {}: delay {}, area {}", iterations, qor.delay, qor.area);
+                self.iterations.last_mut().unwrap().qor = Some(qor);
+                if qor.delay <= self.target_delay {
+                    return Some(StopReason::TargetDelay(qor.delay));
+                }
+            }
+            Err(err) => warn!("Iteration {}: evaluation failed: {}", iterations, err),
+        }
+        None
+    }
+
+    // Records the best cost of this iteration and stops if it has not improved for `patience` iterations
+    fn check_plateau(&mut self) -> Option<StopReason> {
+        let (patience, cost) = self.plateau.as_mut()?;
+        let patience = *patience;
+        let best_cost = cost.cost(&self.egraph, &self.roots);
+        info!("Iteration {}: best cost {}", self.iterations.len(), best_cost);
+        self.iterations.last_mut().unwrap().best_cost = Some(best_cost);
+
+        let history = self.cost_history();
+        let best = history.iter().cloned().fold(f64::INFINITY, f64::min);
+        // iterations since the best cost was first reached
+        let stalled = history.len() - 1 - history.iter().position(|cost| *cost == best).unwrap();
+        if stalled >= patience {
+            return Some(StopReason::Plateau(best));
+        }
+        None
+    }
+
+    fn cost_history(&self) -> Vec<f64> {
+        self.initial_cost.iter().cloned().chain(self.iterations.iter().filter_map(|i| i.best_cost)).collect()
     }
 
     fn try_start(&mut self) {
@@ -771,6 +1007,13 @@ where
     ) -> usize {
         rewrite.apply(egraph, &matches).len()
     }
+
+    /// The rules banned since the last call, the [`Runner`] records them
+    /// in the [`Iteration`].
+    /// Default implementation returns nothing.
+    fn take_ban_events(&mut self) -> Vec<BanEvent> {
+        vec![]
+    }
 }
 
 /// A very simple [`RewriteScheduler`] that runs every rewrite every
@@ -811,6 +1054,7 @@ pub struct BackoffScheduler {
     default_match_limit: usize,
     default_ban_length: usize,
     stats: IndexMap<Symbol, RuleStats>,
+    ban_events: Vec<BanEvent>,
 }
 
 #[derive(Debug)]
@@ -876,6 +1120,7 @@ impl Default for BackoffScheduler {
             stats: Default::default(),
             default_match_limit: 1_000,
             default_ban_length: 5,
+            ban_events: vec![],
         }
     }
 }
@@ -962,12 +1207,125 @@ where
                 threshold,
                 total_len,
             );
+            self.ban_events.push(BanEvent {
+                rule: rewrite.name,
+                matches: total_len,
+                threshold,
+                ban_length,
+            });
             vec![]
         } else {
             stats.times_applied += 1;
             matches
         }
     }
+
+    fn take_ban_events(&mut self) -> Vec<BanEvent> {
+        std::mem::take(&mut self.ban_events)
+    }
+}
+
+/// A [`RewriteScheduler`] that runs rules in priority tiers, with the
+/// per-rule match limits and bans of [`BackoffScheduler`].
+///
+/// Only the rules of tier 0 run until they stop changing the egraph, then
+/// the rules up to the next higher tier run for an iteration, taking turns
+/// so no tier starves. Whenever an iteration changes the egraph it starts
+/// over from tier 0, so cheap simplifying rules always run to a fixpoint
+/// before expanding rules like associativity get a turn. If a tier changes
+/// nothing the next tier joins, the egraph is saturated once all tiers ran
+/// without a change. Rules without a tier are in tier 0.
+///
+/// [`TieredScheduler`] is configurable in the builder-pattern style.
+///
+#[derive(Debug, Default)]
+pub struct TieredScheduler {
+    backoff: BackoffScheduler,
+    tiers: IndexMap<Symbol, usize>,
+    max_tier: usize,
+    active_tier: usize,
+    // the higher tier that had the last turn
+    last_tier: usize,
+    iteration: usize,
+    applied: bool,
+}
+
+impl TieredScheduler {
+    /// Create a scheduler that bans rules like `backoff`.
+    pub fn new(backoff: BackoffScheduler) -> Self {
+        Self {
+            backoff,
+            ..Default::default()
+        }
+    }
+
+    /// Set the tier of a rule, lower tiers run first.
+    pub fn rule_tier(mut self, name: impl Into<Symbol>, tier: usize) -> Self {
+        self.tiers.insert(name.into(), tier);
+        self.max_tier = self.max_tier.max(tier);
+        self
+    }
+
+    fn start_iteration(&mut self, iteration: usize) {
+        if iteration != self.iteration {
+            self.active_tier = if self.applied {
+                if self.active_tier > 0 {
+                    self.last_tier = self.active_tier;
+                }
+                0
+            } else if self.active_tier == 0 && self.max_tier > 0 {
+                self.last_tier % self.max_tier + 1
+            } else {
+                (self.active_tier + 1).min(self.max_tier)
+            };
+            debug!("Iteration {}: running tiers up to {}", iteration, self.active_tier);
+            self.iteration = iteration;
+            self.applied = false;
+        }
+    }
+}
+
+impl<L, N> RewriteScheduler<L, N> for TieredScheduler
+where
+    L: Language,
+    N: Analysis<L>,
+{
+    fn can_stop(&mut self, iteration: usize) -> bool {
+        // the next iteration adds the next tier
+        if self.active_tier < self.max_tier {
+            return false;
+        }
+        RewriteScheduler::<L, N>::can_stop(&mut self.backoff, iteration)
+    }
+
+    fn search_rewrite<'a>(
+        &mut self,
+        iteration: usize,
+        egraph: &EGraph<L, N>,
+        rewrite: &'a Rewrite<L, N>,
+    ) -> Vec<SearchMatches<'a, L>> {
+        self.start_iteration(iteration);
+        if self.tiers.get(&rewrite.name).copied().unwrap_or(0) > self.active_tier {
+            return vec![];
+        }
+        self.backoff.search_rewrite(iteration, egraph, rewrite)
+    }
+
+    fn apply_rewrite(
+        &mut self,
+        iteration: usize,
+        egraph: &mut EGraph<L, N>,
+        rewrite: &Rewrite<L, N>,
+        matches: Vec<SearchMatches<L>>,
+    ) -> usize {
+        let applied = self.backoff.apply_rewrite(iteration, egraph, rewrite, matches);
+        self.applied |= applied > 0;
+        applied
+    }
+
+    fn take_ban_events(&mut self) -> Vec<BanEvent> {
+        RewriteScheduler::<L, N>::take_ban_events(&mut self.backoff)
+    }
 }
 
 /// Custom data to inject into the [`Iteration`]s recorded by a [`Runner`]
@@ -996,4 +1354,75 @@ where
     N: Analysis<L>,
 {
     fn make(_: &Runner<L, N, Self>) -> Self {}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::language::{make_rules, ConstantFold, Prop};
+    use crate::utils::qor::{CostModel, SizeCost};
+
+    fn runner(expr: &str) -> Runner<Prop, ConstantFold> {
+        let mut runner = Runner::default().with_iter_limit(20).with_node_limit(100_000).with_expr(&expr.parse().unwrap());
+        // `run` expects a rebuilt egraph, as main rebuilds the parsed one
+        runner.egraph.rebuild();
+        runner
+    }
+
+    fn last_stop_reason(runner: &Runner<Prop, ConstantFold>) -> StopReason {
+        runner.iterations.last().unwrap().stop_reason.clone().expect("the last iteration records why the run stopped")
+    }
+
+    #[test]
+    fn target_delay_is_the_stop_reason_of_the_last_iteration() {
+        let runner = runner("(* (+ a b) (+ c d))").with_evaluator(CostModel).with_target_delay(1000.0).run(&make_rules());
+        assert!(matches!(runner.report().stop_reason, StopReason::TargetDelay(_)));
+        assert!(matches!(last_stop_reason(&runner), StopReason::TargetDelay(_)));
+        assert_eq!(runner.iterations.len(), 1);
+    }
+
+    #[test]
+    fn target_delay_is_only_checked_every_check_interval() {
+        let runner = runner("(* (+ a b) (+ c d))").with_evaluator(CostModel).with_target_delay(1000.0).with_check_interval(3).run(&make_rules());
+        assert!(matches!(last_stop_reason(&runner), StopReason::TargetDelay(_)));
+        assert_eq!(runner.iterations.len(), 3);
+        assert!(runner.iterations[..2].iter().all(|iteration| iteration.stop_reason.is_none()));
+    }
+    #[test]
+    fn plateau_is_the_stop_reason_of_the_last_iteration() {
+        let runner = runner("(* (+ a b) (+ c d))").with_plateau(2, SizeCost).run(&make_rules());
+        assert!(matches!(runner.report().stop_reason, StopReason::Plateau(_)));
+        assert!(matches!(last_stop_reason(&runner), StopReason::Plateau(_)));
+        assert!(runner.iterations[..runner.iterations.len() - 1].iter().all(|iteration| iteration.stop_reason.is_none()));
+    }
+    #[test]
+    fn json_report_sums_the_iterations() {
+        let runner = runner("(* (+ a b) (+ c d))").with_iter_limit(3).run(&make_rules());
+        let report = runner.report_json();
+        assert_eq!(report["stop_reason"], json!({ "reason": "IterationLimit", "value": 3 }));
+        assert_eq!(report["iterations"], 3);
+        assert_eq!(report["egraph_nodes"], runner.egraph.total_number_of_nodes());
+        assert_eq!(report["egraph_classes"], runner.egraph.number_of_classes());
+
+        let per_iteration = report["per_iteration"].as_array().unwrap();
+        assert_eq!(per_iteration.len(), 3);
+        for (i, (json, iteration)) in per_iteration.iter().zip(&runner.iterations).enumerate() {
+            assert_eq!(json["iteration"], i + 1);
+            assert_eq!(json["egraph_nodes"], iteration.egraph_nodes);
+            assert_eq!(json["egraph_classes"], iteration.egraph_classes);
+            let stop_reason = if i == 2 { json!("IterationLimit(3)") } else { Value::Null };
+            assert_eq!(json["stop_reason"], stop_reason);
+        }
+
+        let rules = report["rules"].as_object().unwrap();
+        assert!(rules.contains_key("commutativity1"));
+        for (name, counts) in rules {
+            for key in ["matched", "applied"] {
+                let total: u64 = per_iteration.iter().map(|iteration| iteration["rules"][name][key].as_u64().unwrap_or(0)).sum();
+                assert_eq!(counts[key].as_u64(), Some(total), "{} {}", name, key);
+            }
+            let applied: usize = runner.iterations.iter().map(|iteration| iteration.applied.get(&Symbol::from(name.as_str())).copied().unwrap_or(0)).sum();
+            assert_eq!(counts["applied"], applied, "{}", name);
+        }
+    }
+}
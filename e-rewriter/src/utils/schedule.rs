@@ -0,0 +1,244 @@
+// Per-rule scheduling read from a JSON config file and `NAME=VALUE` command-line overrides.
+//
+//   {
+//     "match_limit": 1000,
+//     "ban_length": 5,
+//     "rules": {
+//       "associativity*": { "match_limit": 200, "ban_length": 10, "tier": 1 },
+//       "double-negation": { "ban": false }
+//     }
+//   }
+//
+// A rule name ending in `*` applies to every rule starting with the rest of it, an exact
+// name wins over a prefix and a longer prefix over a shorter one. Tiers need the tiered
+// scheduler, every entry must name at least one rule.
+use crate::utils::runner_modified::{BackoffScheduler, TieredScheduler};
+use egg::{Analysis, Language, Rewrite};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("`{0}` does not name any rule")]
+    UnknownRule(String),
+    #[error("expected NAME=VALUE, found `{0}`")]
+    Override(String),
+    #[error("rule tiers need the tiered scheduler")]
+    TiersNeedTiered,
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSchedule {
+    pub match_limit: Option<usize>,
+    pub ban_length: Option<usize>,
+    pub tier: Option<usize>,
+    // `false` never bans the rule
+    pub ban: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    pub match_limit: Option<usize>,
+    pub ban_length: Option<usize>,
+    #[serde(default)]
+    pub rules: BTreeMap<String, RuleSchedule>,
+}
+
+pub fn read_schedule(path: &str) -> Result<ScheduleConfig, ScheduleError> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+// splits a `NAME=VALUE` command-line override
+fn parse_override(src: &str) -> Result<(String, usize), ScheduleError> {
+    let parsed = src.split_once('=').and_then(|(name, value)| Some((name.trim(), value.trim().parse().ok()?)));
+    match parsed {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value)),
+        _ => Err(ScheduleError::Override(src.to_string())),
+    }
+}
+
+impl ScheduleConfig {
+    // Adds `NAME=VALUE` overrides from the command line, they win over the config file
+    pub fn with_overrides(mut self, match_limits: &[String], ban_lengths: &[String], tiers: &[String]) -> Result<Self, ScheduleError> {
+        for src in match_limits {
+            let (name, value) = parse_override(src)?;
+            self.rules.entry(name).or_default().match_limit = Some(value);
+        }
+        for src in ban_lengths {
+            let (name, value) = parse_override(src)?;
+            self.rules.entry(name).or_default().ban_length = Some(value);
+        }
+        for src in tiers {
+            let (name, value) = parse_override(src)?;
+            self.rules.entry(name).or_default().tier = Some(value);
+        }
+        Ok(self)
+    }
+
+    pub fn has_tiers(&self) -> bool {
+        self.rules.values().any(|rule| rule.tier.is_some())
+    }
+
+    // The settings of one rule, shorter prefixes first so longer ones and exact names override them
+    fn settings(&self, name: &str) -> RuleSchedule {
+        let mut settings = RuleSchedule::default();
+        let mut prefixes: Vec<_> = self.rules.iter().filter(|(key, _)| key.strip_suffix('*').is_some_and(|prefix| name.starts_with(prefix))).collect();
+        prefixes.sort_by_key(|(key, _)| key.len());
+        let exact = self.rules.iter().filter(|(key, _)| key.as_str() == name);
+        for (_, rule) in prefixes.into_iter().chain(exact) {
+            settings.match_limit = rule.match_limit.or(settings.match_limit);
+            settings.ban_length = rule.ban_length.or(settings.ban_length);
+            settings.tier = rule.tier.or(settings.tier);
+            settings.ban = rule.ban.or(settings.ban);
+        }
+        settings
+    }
+
+    // typos in rule names would otherwise go unnoticed
+    pub fn check_names<L: Language, N: Analysis<L>>(&self, rules: &[Rewrite<L, N>]) -> Result<(), ScheduleError> {
+        for key in self.rules.keys() {
+            let matches = |name: &str| match key.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == key,
+            };
+            if !rules.iter().any(|rule| matches(rule.name.as_str())) {
+                return Err(ScheduleError::UnknownRule(key.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn backoff<L: Language, N: Analysis<L>>(&self, rules: &[Rewrite<L, N>]) -> Result<BackoffScheduler, ScheduleError> {
+        self.check_names(rules)?;
+        let mut scheduler = BackoffScheduler::default();
+        if let Some(limit) = self.match_limit {
+            scheduler = scheduler.with_initial_match_limit(limit);
+        }
+        if let Some(length) = self.ban_length {
+            scheduler = scheduler.with_ban_length(length);
+        }
+        for rule in rules {
+            let settings = self.settings(rule.name.as_str());
+            if let Some(limit) = settings.match_limit {
+                scheduler = scheduler.rule_match_limit(rule.name, limit);
+            }
+            if let Some(length) = settings.ban_length {
+                scheduler = scheduler.rule_ban_length(rule.name, length);
+            }
+            if settings.ban == Some(false) {
+                scheduler = scheduler.do_not_ban(rule.name);
+            }
+        }
+        Ok(scheduler)
+    }
+
+    pub fn tiered<L: Language, N: Analysis<L>>(&self, rules: &[Rewrite<L, N>]) -> Result<TieredScheduler, ScheduleError> {
+        let mut scheduler = TieredScheduler::new(self.backoff(rules)?);
+        for rule in rules {
+            if let Some(tier) = self.settings(rule.name.as_str()).tier {
+                scheduler = scheduler.rule_tier(rule.name, tier);
+            }
+        }
+        Ok(scheduler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::language::{make_rules, ConstantFold, Prop};
+    use crate::utils::runner_modified::{Runner, RewriteScheduler};
+    use egg::{rewrite, Symbol};
+
+    fn config(json: &str) -> ScheduleConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn run(expr: &str, rules: &[Rewrite<Prop, ConstantFold>], scheduler: impl RewriteScheduler<Prop, ConstantFold> + 'static) -> Runner<Prop, ConstantFold> {
+        let mut runner = Runner::default().with_iter_limit(30).with_node_limit(100_000).with_expr(&expr.parse().unwrap()).with_scheduler(scheduler);
+        runner.egraph.rebuild();
+        runner.run(rules)
+    }
+
+    #[test]
+    fn exact_names_win_over_prefixes_and_longer_prefixes_over_shorter_ones() {
+        let schedule = config(
+            r#"{ "rules": {
+                "assoc*": { "match_limit": 50, "ban_length": 3 },
+                "associativity*": { "match_limit": 200, "tier": 1 },
+                "associativity1": { "match_limit": 5 }
+            } }"#,
+        );
+        let exact = schedule.settings("associativity1");
+        assert_eq!((exact.match_limit, exact.ban_length, exact.tier), (Some(5), Some(3), Some(1)));
+        let prefix = schedule.settings("associativity2-rev");
+        assert_eq!((prefix.match_limit, prefix.ban_length, prefix.tier), (Some(200), Some(3), Some(1)));
+        assert_eq!(schedule.settings("commutativity1").match_limit, None);
+
+        // command-line overrides win over the file
+        let schedule = schedule.with_overrides(&["associativity1=7".to_string()], &[], &["associativity*=2".to_string()]).unwrap();
+        let exact = schedule.settings("associativity1");
+        assert_eq!((exact.match_limit, exact.tier), (Some(7), Some(2)));
+        assert!(matches!(ScheduleConfig::default().with_overrides(&["associativity1".to_string()], &[], &[]), Err(ScheduleError::Override(_))));
+    }
+
+    #[test]
+    fn unknown_rule_names_are_rejected() {
+        let rules = make_rules();
+        assert!(config(r#"{ "rules": { "associativity*": {}, "de-morgan1-rev": {} } }"#).check_names(&rules).is_ok());
+        for key in ["asociativity*", "associativity3", "commutativity"] {
+            let schedule = config(&format!(r#"{{ "rules": {{ "{}": {{ "match_limit": 1 }} }} }}"#, key));
+            assert!(matches!(schedule.check_names(&rules), Err(ScheduleError::UnknownRule(name)) if name == key));
+            assert!(schedule.backoff(&rules).is_err());
+        }
+    }
+
+    #[test]
+    fn higher_tiers_wait_until_tier_0_saturates() {
+        let rules: Vec<Rewrite<Prop, ConstantFold>> = vec![
+            rewrite!("assoc"; "(* ?a (* ?b ?c))" => "(* (* ?a ?b) ?c)"),
+            rewrite!("comm"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+        ];
+        let scheduler = config(r#"{ "rules": { "comm": { "tier": 1 } } }"#).tiered(&rules).unwrap();
+        let runner = run("(+ (* a (* b (* c (* d e)))) f)", &rules, scheduler);
+
+        let applied = |i: usize, rule: &str| runner.iterations[i].applied.get(&Symbol::from(rule)).copied().unwrap_or(0);
+        let first = (0..runner.iterations.len()).find(|&i| applied(i, "comm") > 0).expect("tier 1 runs once tier 0 saturated");
+        assert!(first >= 2, "tier 0 takes more than one iteration");
+        assert!((0..first).all(|i| !runner.iterations[i].matched.contains_key(&Symbol::from("comm"))));
+        assert!(applied(0, "assoc") > 0);
+        // the iteration before tier 1 joined changed nothing
+        assert!(runner.iterations[first - 1].applied.is_empty());
+    }
+
+    #[test]
+    fn rules_over_their_match_limit_are_banned_for_their_ban_length() {
+        let rules = make_rules();
+        let scheduler = config(r#"{ "match_limit": 1000, "rules": { "commutativity*": { "match_limit": 1, "ban_length": 4 } } }"#).backoff(&rules).unwrap();
+        let runner = run("(* (* a b) (* c d))", &rules, scheduler);
+
+        let banned = &runner.iterations[0].banned;
+        let ban = banned.iter().find(|ban| ban.rule == Symbol::from("commutativity1")).expect("commutativity1 is banned in the first iteration");
+        assert_eq!((ban.threshold, ban.matches, ban.ban_length), (1, 2, 4));
+        assert!(banned.iter().all(|ban| ban.rule.as_str().starts_with("commutativity")));
+        // banned rules do not run until the ban is over
+        for iteration in &runner.iterations[1..4.min(runner.iterations.len())] {
+            assert!(!iteration.matched.contains_key(&Symbol::from("commutativity1")));
+        }
+
+        let report = runner.report_json();
+        let bans: usize = runner.iterations.iter().map(|iteration| iteration.banned.len()).sum();
+        assert_eq!(report["bans"].as_array().unwrap().len(), bans);
+        assert_eq!(runner.report().bans, bans);
+        assert_eq!(report["bans"][0]["ban_length"], 4);
+    }
+}
@@ -0,0 +1,124 @@
+// Soundness check for boolean rewrite rules: both sides of a rule are evaluated under
+// every assignment of the pattern variables, a rule is unsound if they ever differ.
+use crate::utils::language::Prop;
+use egg::{Analysis, ENodeOrVar, PatternAst, Rewrite, Var};
+use std::fmt;
+
+// rules with more variables than this are reported as unchecked
+const MAX_VARS: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Sound,
+    // an assignment of the pattern variables and the values of the left and right side
+    Unsound { assignment: Vec<(Var, bool)>, lhs: bool, rhs: bool },
+    // the rule could not be checked, e.g. it is not a pattern rule or uses let/&/root
+    Unchecked(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleCheck {
+    pub name: String,
+    pub verdict: Verdict,
+}
+
+impl fmt::Display for RuleCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.verdict {
+            Verdict::Sound => write!(f, "{}: sound", self.name),
+            Verdict::Unsound { assignment, lhs, rhs } => {
+                let assignment: Vec<String> = assignment.iter().map(|(var, value)| format!("{}={}", var, *value as u8)).collect();
+                write!(
+                    f,
+                    "{}: UNSOUND, with {} the left side is {} and the right side is {}",
+                    self.name,
+                    if assignment.is_empty() { "no variables".to_string() } else { assignment.join(" ") },
+                    *lhs as u8,
+                    *rhs as u8
+                )
+            }
+            Verdict::Unchecked(reason) => write!(f, "{}: not checked, {}", self.name, reason),
+        }
+    }
+}
+
+// Evaluates a pattern, `None` if it contains an operator without a boolean value
+pub fn eval_pattern(pattern: &PatternAst<Prop>, assignment: &[(Var, bool)]) -> Option<bool> {
+    let nodes = pattern.as_ref();
+    let mut values: Vec<bool> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let value = match node {
+            ENodeOrVar::Var(var) => assignment.iter().find(|(v, _)| v == var)?.1,
+            ENodeOrVar::ENode(node) => node.eval(|id| values[usize::from(id)])?,
+        };
+        values.push(value);
+    }
+    values.last().copied()
+}
+
+fn pattern_vars(pattern: &PatternAst<Prop>, vars: &mut Vec<Var>) {
+    for node in pattern.as_ref() {
+        if let ENodeOrVar::Var(var) = node {
+            if !vars.contains(var) {
+                vars.push(*var);
+            }
+        }
+    }
+}
+
+// Checks one pair of patterns by enumerating all assignments of their variables
+pub fn check_patterns(lhs: &PatternAst<Prop>, rhs: &PatternAst<Prop>) -> Verdict {
+    let mut vars = Vec::new();
+    pattern_vars(lhs, &mut vars);
+    pattern_vars(rhs, &mut vars);
+    if vars.len() > MAX_VARS {
+        return Verdict::Unchecked(format!("{} variables are too many to enumerate", vars.len()));
+    }
+    for bits in 0..1u64 << vars.len() {
+        let assignment: Vec<(Var, bool)> = vars.iter().enumerate().map(|(i, var)| (*var, bits >> i & 1 == 1)).collect();
+        let (Some(l), Some(r)) = (eval_pattern(lhs, &assignment), eval_pattern(rhs, &assignment)) else {
+            return Verdict::Unchecked("a side uses an operator without a boolean value".to_string());
+        };
+        if l != r {
+            return Verdict::Unsound { assignment, lhs: l, rhs: r };
+        }
+    }
+    Verdict::Sound
+}
+
+pub fn check_rule<N: Analysis<Prop>>(rule: &Rewrite<Prop, N>) -> RuleCheck {
+    let verdict = match (rule.searcher.get_pattern_ast(), rule.applier.get_pattern_ast()) {
+        (Some(lhs), Some(rhs)) => check_patterns(lhs, rhs),
+        _ => Verdict::Unchecked("it is not a pattern rewrite".to_string()),
+    };
+    RuleCheck {
+        name: rule.name.to_string(),
+        verdict,
+    }
+}
+
+pub fn check_rules<N: Analysis<Prop>>(rules: &[Rewrite<Prop, N>]) -> Vec<RuleCheck> {
+    rules.iter().map(check_rule).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::language::{make_rules, make_rules_simplify};
+    use crate::utils::rule_file::{build_rewrites, parse_rules_text};
+
+    #[test]
+    fn built_in_rules_are_sound() {
+        for (set, rules) in [("default", make_rules()), ("simplify", make_rules_simplify())] {
+            for check in check_rules(&rules) {
+                assert_eq!(check.verdict, Verdict::Sound, "{} rule set: {}", set, check);
+            }
+        }
+    }
+
+    #[test]
+    fn unsound_rule_is_reported() {
+        let rules = build_rewrites::<()>(&parse_rules_text("bad: (+ ?a ?b) => (* ?a ?b)").unwrap()).unwrap();
+        assert!(matches!(check_rule(&rules[0]).verdict, Verdict::Unsound { lhs: true, rhs: false, .. }));
+    }
+}
@@ -0,0 +1,340 @@
+// Enumerative rule synthesis in the style of Ruler.
+//
+// Terms are enumerated by size (number of operators, variables and constants) over a few
+// variables. A term's children are always the smallest known term of their truth table, so
+// the enumeration stays small. Terms with the same truth table are equal, every such term
+// gives a candidate rewrite to the smallest term of its table. Candidates are then minimized:
+// all terms are put in an egraph, candidates are visited from small to large and a candidate
+// is kept only if the rules kept so far cannot already prove it. A last pass drops every rule
+// that the others prove, since a later rule can make an earlier one redundant.
+//
+// The egraph has no constant folding: it would merge `(* a 0)` with `0` before any rule is
+// proposed, so rules like `(* ?a 0) => 0` would never be kept.
+use crate::utils::language::Prop;
+use crate::utils::rule_file::{build_rewrites, Direction, RuleSpec};
+use egg::{EGraph, Id, RecExpr, Runner};
+use std::collections::{HashMap, HashSet};
+
+type SynthEGraph = EGraph<Prop, ()>;
+
+pub struct SynthConfig {
+    // number of variables, at most 6 so a truth table fits in a u64
+    pub vars: usize,
+    // largest term size that is enumerated
+    pub max_size: usize,
+    // operators to enumerate: any of `! * + ^ xnor mux maj`
+    pub ops: Vec<String>,
+    // whether the constants 0 and 1 are leaves of the enumeration
+    pub constants: bool,
+    // limits of the runs that decide whether a candidate is already derivable
+    pub iter_limit: usize,
+    pub node_limit: usize,
+}
+
+impl Default for SynthConfig {
+    fn default() -> Self {
+        SynthConfig {
+            vars: 3,
+            max_size: 5,
+            ops: vec!["!".to_string(), "*".to_string(), "+".to_string()],
+            constants: true,
+            iter_limit: 3,
+            node_limit: 100_000,
+        }
+    }
+}
+
+const VAR_NAMES: [&str; 6] = ["a", "b", "c", "d", "e", "f"];
+
+#[derive(Debug, Clone)]
+struct Term {
+    // s-expression over the variable names, e.g. `(* a (! b))`
+    expr: String,
+    size: usize,
+    table: u64,
+}
+
+fn arity(op: &str) -> Option<usize> {
+    match op {
+        "!" => Some(1),
+        "*" | "+" | "^" | "xnor" => Some(2),
+        "mux" | "maj" => Some(3),
+        _ => None,
+    }
+}
+
+fn apply(op: &str, tables: &[u64], mask: u64) -> u64 {
+    match (op, tables) {
+        ("!", [a]) => !a & mask,
+        ("*", [a, b]) => a & b,
+        ("+", [a, b]) => a | b,
+        ("^", [a, b]) => a ^ b,
+        ("xnor", [a, b]) => !(a ^ b) & mask,
+        ("mux", [s, a, b]) => (s & a) | (!s & b & mask),
+        ("maj", [a, b, c]) => (a & b) | (c & (a | b)),
+        _ => unreachable!("operator arity is checked when the config is read"),
+    }
+}
+
+// all ways to split `total` into `parts` positive sizes
+fn compositions(total: usize, parts: usize) -> Vec<Vec<usize>> {
+    if parts == 1 {
+        return if total >= 1 { vec![vec![total]] } else { Vec::new() };
+    }
+    let mut out = Vec::new();
+    for first in 1..total {
+        for mut rest in compositions(total - first, parts - 1) {
+            rest.insert(0, first);
+            out.push(rest);
+        }
+    }
+    out
+}
+
+// Enumerates terms up to `max_size`, returns all terms and the representative
+// (smallest, first enumerated) term index of each truth table
+fn enumerate(config: &SynthConfig) -> (Vec<Term>, HashMap<u64, usize>) {
+    let rows = 1u32 << config.vars;
+    let mask = if rows == 64 { u64::MAX } else { (1u64 << rows) - 1 };
+    let mut terms: Vec<Term> = Vec::new();
+    let mut reps: HashMap<u64, usize> = HashMap::new();
+    // representatives of each size, children are only picked from these
+    let mut reps_by_size: Vec<Vec<usize>> = vec![Vec::new(); config.max_size + 1];
+
+    let mut add = |term: Term, terms: &mut Vec<Term>, reps_by_size: &mut Vec<Vec<usize>>| {
+        let index = terms.len();
+        if let std::collections::hash_map::Entry::Vacant(entry) = reps.entry(term.table) {
+            entry.insert(index);
+            reps_by_size[term.size].push(index);
+        }
+        terms.push(term);
+    };
+
+    for (i, name) in VAR_NAMES.iter().enumerate().take(config.vars) {
+        let table = (0..rows).filter(|m| m >> i & 1 == 1).fold(0u64, |t, m| t | 1 << m);
+        add(Term { expr: name.to_string(), size: 1, table }, &mut terms, &mut reps_by_size);
+    }
+    if config.constants {
+        add(Term { expr: "0".to_string(), size: 1, table: 0 }, &mut terms, &mut reps_by_size);
+        add(Term { expr: "1".to_string(), size: 1, table: mask }, &mut terms, &mut reps_by_size);
+    }
+
+    for size in 2..=config.max_size {
+        let mut layer = Vec::new();
+        for op in &config.ops {
+            let arity = arity(op).unwrap();
+            for sizes in compositions(size - 1, arity) {
+                // cartesian product of the representatives of each child size
+                let mut choices: Vec<Vec<usize>> = vec![Vec::new()];
+                for child_size in &sizes {
+                    choices = choices
+                        .into_iter()
+                        .flat_map(|prefix| {
+                            reps_by_size[*child_size].iter().map(move |child| {
+                                let mut next = prefix.clone();
+                                next.push(*child);
+                                next
+                            })
+                        })
+                        .collect();
+                }
+                for children in choices {
+                    let tables: Vec<u64> = children.iter().map(|c| terms[*c].table).collect();
+                    let args: Vec<&str> = children.iter().map(|c| terms[*c].expr.as_str()).collect();
+                    layer.push(Term {
+                        expr: format!("({} {})", op, args.join(" ")),
+                        size,
+                        table: apply(op, &tables, mask),
+                    });
+                }
+            }
+        }
+        for term in layer {
+            add(term, &mut terms, &mut reps_by_size);
+        }
+    }
+    (terms, reps)
+}
+
+// Both terms as patterns, variables are renamed in order of first appearance
+// so candidates that only differ by a renaming of variables look the same
+fn canonical(lhs: &str, rhs: &str, vars: usize) -> (String, String) {
+    // variables in order of first appearance, the i-th one is renamed to VAR_NAMES[i]
+    let mut renaming: Vec<String> = Vec::new();
+    let mut convert = |expr: &str| {
+        let spaced = expr.replace('(', "( ").replace(')', " )");
+        let tokens: Vec<String> = spaced
+            .split_whitespace()
+            .map(|token| {
+                if !VAR_NAMES[..vars].contains(&token) {
+                    return token.to_string();
+                }
+                let index = renaming.iter().position(|var| var == token).unwrap_or_else(|| {
+                    renaming.push(token.to_string());
+                    renaming.len() - 1
+                });
+                format!("?{}", VAR_NAMES[index])
+            })
+            .collect();
+        tokens.join(" ").replace("( ", "(").replace(" )", ")")
+    };
+    (convert(lhs), convert(rhs))
+}
+
+fn term_vars(expr: &str, vars: usize) -> Vec<&'static str> {
+    let spaced = expr.replace(['(', ')'], " ");
+    let mut used: Vec<&'static str> = VAR_NAMES[..vars].iter().copied().filter(|var| spaced.split_whitespace().any(|token| token == *var)).collect();
+    used.sort_unstable();
+    used
+}
+
+// Synthesizes a small set of sound rules that prove every equality between the enumerated terms
+pub fn synthesize(config: &SynthConfig) -> Result<Vec<RuleSpec>, String> {
+    if config.vars == 0 || config.vars > VAR_NAMES.len() {
+        return Err(format!("the number of variables must be between 1 and {}", VAR_NAMES.len()));
+    }
+    if let Some(op) = config.ops.iter().find(|op| arity(op).is_none()) {
+        return Err(format!("unknown operator `{}`, expected one of ! * + ^ xnor mux maj", op));
+    }
+    let (terms, reps) = enumerate(config);
+
+    // candidate `term => representative` for every non-representative term
+    let mut candidates: Vec<(usize, usize)> = terms
+        .iter()
+        .enumerate()
+        .filter(|(index, term)| reps[&term.table] != *index)
+        .map(|(index, term)| (index, reps[&term.table]))
+        .filter(|(lhs, rhs)| {
+            let (lhs_vars, rhs_vars) = (term_vars(&terms[*lhs].expr, config.vars), term_vars(&terms[*rhs].expr, config.vars));
+            terms[*lhs].size > 1 && rhs_vars.iter().all(|var| lhs_vars.contains(var))
+        })
+        .collect();
+    candidates.sort_by_key(|(lhs, rhs)| (terms[*lhs].size + terms[*rhs].size, terms[*lhs].size, *lhs));
+
+    let mut egraph = SynthEGraph::default();
+    let ids: Vec<Id> = terms
+        .iter()
+        .map(|term| egraph.add_expr(&term.expr.parse::<RecExpr<Prop>>().expect("enumerated terms are well-formed")))
+        .collect();
+    egraph.rebuild();
+
+    let mut rules: Vec<RuleSpec> = Vec::new();
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    let mut pending = false;
+    for (lhs, rhs) in candidates {
+        // a candidate that only renames the variables of an earlier one adds nothing
+        let (lhs_pattern, rhs_pattern) = canonical(&terms[lhs].expr, &terms[rhs].expr, config.vars);
+        if !seen.insert((lhs_pattern.clone(), rhs_pattern.clone())) || egraph.find(ids[lhs]) == egraph.find(ids[rhs]) {
+            continue;
+        }
+        // prove what the rules kept so far imply before keeping another one
+        if pending {
+            let rewrites = build_rewrites::<()>(&rules).map_err(|err| err.to_string())?;
+            let runner: Runner<Prop, ()> = Runner::default()
+                .with_egraph(egraph)
+                .with_iter_limit(config.iter_limit)
+                .with_node_limit(config.node_limit)
+                .run(&rewrites);
+            egraph = runner.egraph;
+            pending = false;
+            if egraph.find(ids[lhs]) == egraph.find(ids[rhs]) {
+                continue;
+            }
+        }
+        // rules that are also valid right to left (same variables, no bare variable) are bidirectional
+        let reversible = terms[rhs].size > 1 && term_vars(&terms[lhs].expr, config.vars) == term_vars(&terms[rhs].expr, config.vars);
+        rules.push(RuleSpec {
+            name: format!("synth-{}", rules.len() + 1),
+            lhs: lhs_pattern,
+            rhs: rhs_pattern,
+            direction: if reversible { Direction::Both } else { Direction::Forward },
+            enabled: true,
+        });
+        egraph.union(ids[lhs], ids[rhs]);
+        egraph.rebuild();
+        pending = true;
+    }
+    minimize(rules, config)
+}
+
+// Whether `rule` follows from `others`: both sides, with their variables read as symbols, are
+// rewritten with `others` until they are in one class or the limits are reached
+pub fn derivable(rule: &RuleSpec, others: &[RuleSpec], config: &SynthConfig) -> Result<bool, String> {
+    let rewrites = build_rewrites::<()>(others).map_err(|err| err.to_string())?;
+    let term = |pattern: &str| pattern.replace('?', "").parse::<RecExpr<Prop>>().map_err(|err| format!("{}: {}", rule.name, err));
+    let mut egraph = SynthEGraph::default();
+    let lhs = egraph.add_expr(&term(&rule.lhs)?);
+    let rhs = egraph.add_expr(&term(&rule.rhs)?);
+    let runner: Runner<Prop, ()> = Runner::default()
+        .with_egraph(egraph)
+        .with_iter_limit(config.iter_limit)
+        .with_node_limit(config.node_limit)
+        .with_hook(move |runner| if runner.egraph.find(lhs) == runner.egraph.find(rhs) { Err("proven".to_string()) } else { Ok(()) })
+        .run(&rewrites);
+    Ok(runner.egraph.find(lhs) == runner.egraph.find(rhs))
+}
+
+// Drops every rule the other rules prove, the largest first so the general ones stay.
+// A rule that is not derivable from a set is not derivable from any subset of it either,
+// so one pass leaves a set where no rule follows from the others.
+fn minimize(mut rules: Vec<RuleSpec>, config: &SynthConfig) -> Result<Vec<RuleSpec>, String> {
+    for index in (0..rules.len()).rev() {
+        let others: Vec<RuleSpec> = rules.iter().enumerate().filter(|(other, _)| *other != index).map(|(_, rule)| rule.clone()).collect();
+        if derivable(&rules[index], &others, config)? {
+            rules.remove(index);
+        }
+    }
+    for (index, rule) in rules.iter_mut().enumerate() {
+        rule.name = format!("synth-{}", index + 1);
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::soundness::{check_rules, Verdict};
+
+    fn has_rule(rules: &[RuleSpec], lhs: &str, rhs: &str) -> bool {
+        rules.iter().any(|rule| rule.lhs == lhs && rule.rhs == rhs)
+    }
+
+    #[test]
+    fn synthesized_rules_are_sound_and_minimal() {
+        let config = SynthConfig::default();
+        let rules = synthesize(&config).unwrap();
+        for check in check_rules(&build_rewrites::<()>(&rules).unwrap()) {
+            assert_eq!(check.verdict, Verdict::Sound, "{}", check);
+        }
+        for (index, rule) in rules.iter().enumerate() {
+            let others: Vec<RuleSpec> = rules.iter().enumerate().filter(|(other, _)| *other != index).map(|(_, rule)| rule.clone()).collect();
+            assert!(!derivable(rule, &others, &config).unwrap(), "{} follows from the other rules", rule);
+        }
+    }
+
+    #[test]
+    fn null_elements_are_kept_and_mirrored_rules_are_not() {
+        let rules = synthesize(&SynthConfig::default()).unwrap();
+        assert!(has_rule(&rules, "(* ?a 0)", "0") || has_rule(&rules, "(* 0 ?a)", "0"));
+        assert!(has_rule(&rules, "(+ ?a 1)", "1") || has_rule(&rules, "(+ 1 ?a)", "1"));
+        assert!(!(has_rule(&rules, "(* ?a 1)", "?a") && has_rule(&rules, "(* 1 ?a)", "?a")));
+        assert!(!(has_rule(&rules, "(+ ?a 0)", "?a") && has_rule(&rules, "(+ 0 ?a)", "?a")));
+    }
+
+    #[test]
+    fn rules_implied_by_the_others_are_dropped() {
+        let config = SynthConfig::default();
+        let rule = |name: &str, lhs: &str, rhs: &str, direction| RuleSpec { name: name.to_string(), lhs: lhs.to_string(), rhs: rhs.to_string(), direction, enabled: true };
+        let rules = vec![
+            rule("identity", "(* ?a 1)", "?a", Direction::Forward),
+            rule("identity-left", "(* 1 ?a)", "?a", Direction::Forward),
+            rule("comm", "(* ?a ?b)", "(* ?b ?a)", Direction::Both),
+            rule("assoc", "(* ?a (* ?b ?c))", "(* (* ?a ?b) ?c)", Direction::Both),
+            rule("swap", "(* ?a (* ?b ?c))", "(* ?b (* ?a ?c))", Direction::Both),
+        ];
+        let kept = minimize(rules, &config).unwrap();
+        assert_eq!(kept.len(), 3, "{:?}", kept);
+        assert!(has_rule(&kept, "(* ?a 1)", "?a"));
+        assert!(has_rule(&kept, "(* ?a ?b)", "(* ?b ?a)"));
+    }
+}
@@ -0,0 +1,297 @@
+// And-inverter graphs in the AIGER format, used to hand circuits to ABC without going
+// through eqn text.
+//
+// Variables are numbered the canonical way: inputs first, then latches, then and gates
+// in topological order, so every graph can be written in the binary `aig` format.
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fs;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AigLatch {
+    pub next: u32,
+    // `None` when the latch is uninitialized
+    pub init: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Aig {
+    pub num_inputs: u32,
+    pub latches: Vec<AigLatch>,
+    pub outputs: Vec<u32>,
+    // right-hand sides of the and gates, gate `i` is variable `num_inputs + latches.len() + i + 1`
+    pub ands: Vec<(u32, u32)>,
+    pub input_names: Vec<String>,
+    pub latch_names: Vec<String>,
+    pub output_names: Vec<String>,
+}
+
+impl Aig {
+    pub fn max_var(&self) -> u32 {
+        self.num_inputs + self.latches.len() as u32 + self.ands.len() as u32
+    }
+
+    /// Literal of input `index`
+    pub fn input(&self, index: u32) -> u32 {
+        2 * (index + 1)
+    }
+
+    /// Literal of latch `index`
+    pub fn latch(&self, index: u32) -> u32 {
+        2 * (self.num_inputs + index + 1)
+    }
+
+    /// Appends an and gate and returns its literal. Operands must already exist.
+    pub fn and(&mut self, a: u32, b: u32) -> u32 {
+        self.ands.push((a.max(b), a.min(b)));
+        2 * self.max_var()
+    }
+
+    fn header(&self, format: &str) -> String {
+        format!(
+            "{} {} {} {} {} {}\n",
+            format,
+            self.max_var(),
+            self.num_inputs,
+            self.latches.len(),
+            self.outputs.len(),
+            self.ands.len()
+        )
+    }
+
+    fn latch_line(&self, index: usize, binary: bool) -> String {
+        let latch = &self.latches[index];
+        let lit = self.latch(index as u32);
+        let init = match latch.init {
+            Some(false) => String::new(),
+            Some(true) => " 1".to_string(),
+            None => format!(" {}", lit),
+        };
+        if binary {
+            format!("{}{}\n", latch.next, init)
+        } else {
+            format!("{} {}{}\n", lit, latch.next, init)
+        }
+    }
+
+    fn symbols(&self) -> String {
+        let mut symbols = String::new();
+        for (kind, names) in [('i', &self.input_names), ('l', &self.latch_names), ('o', &self.output_names)] {
+            for (index, name) in names.iter().enumerate() {
+                symbols.push_str(&format!("{}{} {}\n", kind, index, name));
+            }
+        }
+        symbols
+    }
+
+    /// The graph in the ASCII `aag` format
+    pub fn to_ascii(&self) -> Vec<u8> {
+        let mut out = self.header("aag");
+        for index in 0..self.num_inputs {
+            out.push_str(&format!("{}\n", self.input(index)));
+        }
+        for index in 0..self.latches.len() {
+            out.push_str(&self.latch_line(index, false));
+        }
+        for output in &self.outputs {
+            out.push_str(&format!("{}\n", output));
+        }
+        let first = self.num_inputs + self.latches.len() as u32 + 1;
+        for (index, (rhs0, rhs1)) in self.ands.iter().enumerate() {
+            out.push_str(&format!("{} {} {}\n", 2 * (first + index as u32), rhs0, rhs1));
+        }
+        out.push_str(&self.symbols());
+        out.into_bytes()
+    }
+
+    /// The graph in the binary `aig` format
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut text = self.header("aig");
+        for index in 0..self.latches.len() {
+            text.push_str(&self.latch_line(index, true));
+        }
+        for output in &self.outputs {
+            text.push_str(&format!("{}\n", output));
+        }
+        let mut out = text.into_bytes();
+        let first = self.num_inputs + self.latches.len() as u32 + 1;
+        for (index, (rhs0, rhs1)) in self.ands.iter().enumerate() {
+            let lhs = 2 * (first + index as u32);
+            push_varint(&mut out, lhs - rhs0);
+            push_varint(&mut out, rhs0 - rhs1);
+        }
+        out.extend(self.symbols().into_bytes());
+        out
+    }
+
+    /// Writes the graph, in the binary format unless the path ends in `.aag`
+    pub fn to_file(&self, path: &str) {
+        let bytes = if path.ends_with(".aag") { self.to_ascii() } else { self.to_binary() };
+        fs::write(path, bytes).expect("Unable to write AIGER file");
+    }
+
+    /// Reads an AIGER file written by ABC (or any other canonical writer)
+    pub fn from_file(path: &str) -> Aig {
+        let bytes = fs::read(path).expect("Unable to read AIGER file");
+        Aig::parse(&bytes).expect("AIGER file was not well-formatted")
+    }
+
+    /// Parses a canonical AIGER file, ASCII or binary
+    pub fn parse(bytes: &[u8]) -> Result<Aig, Box<dyn StdError>> {
+        let mut offset = 0;
+        let next_line = |offset: &mut usize| -> Result<String, Box<dyn StdError>> {
+            let rest = bytes.get(*offset..).filter(|rest| !rest.is_empty()).ok_or("unexpected end of AIGER file")?;
+            let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            *offset += (len + 1).min(rest.len());
+            Ok(std::str::from_utf8(&rest[..len])?.trim_end_matches('\r').to_string())
+        };
+        let numbers = |line: &str| -> Result<Vec<u32>, Box<dyn StdError>> {
+            Ok(line.split_whitespace().map(str::parse).collect::<Result<Vec<u32>, _>>()?)
+        };
+
+        let header = next_line(&mut offset)?;
+        let binary = header.starts_with("aig ");
+        if !binary && !header.starts_with("aag ") {
+            return Err("expected an `aag` or `aig` header".into());
+        }
+        let counts = numbers(&header[4..])?;
+        if counts.len() < 5 || counts.iter().skip(5).any(|&count| count != 0) {
+            return Err(format!("unsupported AIGER header `{}`", header).into());
+        }
+        let (num_inputs, num_latches, num_outputs, num_ands) = (counts[1], counts[2], counts[3], counts[4]);
+        if counts[0] != num_inputs + num_latches + num_ands {
+            return Err("only canonical AIGER files (M = I + L + A) are supported".into());
+        }
+
+        let mut aig = Aig {
+            num_inputs,
+            ..Default::default()
+        };
+        // binary files have no input lines
+        for index in 0..if binary { 0 } else { num_inputs } {
+            if numbers(&next_line(&mut offset)?)? != [aig.input(index)] {
+                return Err(format!("input {} is not canonical", index).into());
+            }
+        }
+        for index in 0..num_latches {
+            let fields = numbers(&next_line(&mut offset)?)?;
+            let fields = if binary { fields } else { fields.get(1..).unwrap_or_default().to_vec() };
+            if fields.is_empty() || fields.len() > 2 {
+                return Err(format!("malformed latch {}", index).into());
+            }
+            let lit = aig.latch(index);
+            let init = match fields.get(1).copied() {
+                None | Some(0) => Some(false),
+                Some(1) => Some(true),
+                Some(init) if init == lit => None,
+                Some(init) => return Err(format!("invalid latch initial value {}", init).into()),
+            };
+            aig.latches.push(AigLatch { next: fields[0], init });
+        }
+        for _ in 0..num_outputs {
+            let output = numbers(&next_line(&mut offset)?)?.first().copied().ok_or("malformed output")?;
+            aig.outputs.push(output);
+        }
+        for index in 0..num_ands {
+            let lhs = 2 * (num_inputs + num_latches + index + 1);
+            if binary {
+                let delta0 = read_varint(bytes, &mut offset)?;
+                let delta1 = read_varint(bytes, &mut offset)?;
+                let rhs0 = lhs.checked_sub(delta0).ok_or("invalid and gate delta")?;
+                let rhs1 = rhs0.checked_sub(delta1).ok_or("invalid and gate delta")?;
+                aig.ands.push((rhs0, rhs1));
+            } else {
+                let fields = numbers(&next_line(&mut offset)?)?;
+                if fields.len() != 3 || fields[0] != lhs {
+                    return Err(format!("and gate {} is not canonical", lhs).into());
+                }
+                aig.ands.push((fields[1], fields[2]));
+            }
+        }
+
+        while let Ok(line) = next_line(&mut offset) {
+            if line == "c" {
+                break;
+            }
+            let Some((key, name)) = line.split_once(' ') else { continue };
+            let Ok(index) = key[1..].parse::<usize>() else { continue };
+            let names = match key.chars().next() {
+                Some('i') => &mut aig.input_names,
+                Some('l') => &mut aig.latch_names,
+                Some('o') => &mut aig.output_names,
+                _ => continue,
+            };
+            if names.len() <= index {
+                names.resize(index + 1, String::new());
+            }
+            names[index] = name.to_string();
+        }
+        Ok(aig)
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], offset: &mut usize) -> Result<u32, Box<dyn StdError>> {
+    let mut value: u32 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes.get(*offset).ok_or("unexpected end of AIGER file")?;
+        *offset += 1;
+        value |= ((byte & 0x7f) as u32).checked_shl(shift).ok_or("invalid and gate delta")?;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Structural hashing for building an `Aig`: equal and gates are only added once
+#[derive(Default)]
+pub struct AigBuilder {
+    pub aig: Aig,
+    strash: HashMap<(u32, u32), u32>,
+}
+
+impl AigBuilder {
+    pub fn and(&mut self, a: u32, b: u32) -> u32 {
+        let key = (a.max(b), a.min(b));
+        match key {
+            (_, 0) => 0,
+            (x, 1) => x,
+            (x, y) if x == y => x,
+            (x, y) if x == y ^ 1 => 0,
+            _ => *self.strash.entry(key).or_insert_with(|| self.aig.and(key.0, key.1)),
+        }
+    }
+
+    pub fn or(&mut self, a: u32, b: u32) -> u32 {
+        self.and(a ^ 1, b ^ 1) ^ 1
+    }
+
+    pub fn xor(&mut self, a: u32, b: u32) -> u32 {
+        let lhs = self.and(a, b ^ 1);
+        let rhs = self.and(a ^ 1, b);
+        self.or(lhs, rhs)
+    }
+
+    /// `a` if `s` is true, `b` otherwise
+    pub fn mux(&mut self, s: u32, a: u32, b: u32) -> u32 {
+        let lhs = self.and(s, a);
+        let rhs = self.and(s ^ 1, b);
+        self.or(lhs, rhs)
+    }
+
+    pub fn maj(&mut self, a: u32, b: u32, c: u32) -> u32 {
+        let ab = self.and(a, b);
+        let a_or_b = self.or(a, b);
+        let rest = self.and(c, a_or_b);
+        self.or(ab, rest)
+    }
+}
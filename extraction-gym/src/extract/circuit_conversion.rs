@@ -6,8 +6,9 @@ use rustc_hash::FxHashMap;
 use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::Read;
 use rayon::prelude::*;
+use crate::extract::aiger::{Aig, AigBuilder, AigLatch};
 
 //==================================================
 //Data Structures
@@ -17,6 +18,22 @@ use rayon::prelude::*;
 struct Graph {
     nodes: FxHashMap<String, Node>,
     root_eclasses: Vec<String>,
+    // names of the primary outputs, `root_names[i]` is rooted at `root_eclasses[i]`
+    #[serde(default)]
+    root_names: Vec<String>,
+    // names of the primary inputs in declaration order
+    #[serde(default)]
+    input_names: Vec<String>,
+    // latches cut into pseudo inputs (their outputs) and pseudo outputs (their inputs)
+    #[serde(default)]
+    latches: Vec<Latch>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Latch {
+    input: String,
+    output: String,
+    init: Option<bool>,
 }
 
 
@@ -83,13 +100,15 @@ fn process_json_simplify_keys(input_json: &str) -> Result<String, Box<dyn StdErr
 // Step 3: Update Root Eclasses
 // ==================================================
 
-/// Updates root eclasses in the target JSON
+/// Updates root eclasses (and the output/input names and latches) in the target JSON
 fn update_root_eclasses(graph_json: &str, target_json: &str) -> Result<String, Box<dyn StdError>> {
     let source_data: Value = serde_json::from_str(graph_json)?;
     let mut target_data: Value = serde_json::from_str(target_json)?;
 
-    let root_eclasses = source_data["root_eclasses"].as_array().unwrap_or(&Vec::new()).to_owned();
-    target_data["root_eclasses"] = serde_json::json!(root_eclasses);
+    for key in ["root_eclasses", "root_names", "input_names", "latches"] {
+        let values = source_data[key].as_array().unwrap_or(&Vec::new()).to_owned();
+        target_data[key] = serde_json::json!(values);
+    }
 
     Ok(serde_json::to_string_pretty(&target_data)?)
 }
@@ -106,31 +125,235 @@ fn json_to_eqn(json_str: &str, prefix_mapping_path: &str, is_large: bool) -> Res
         return Err("The graph is cyclic.".into());
     }
 
-    let prefix_mapping = read_prefix_mapping(prefix_mapping_path);
-    //println!("prefix mapping: {:?}", prefix_mapping);
-    let mut final_content = String::with_capacity(graph.root_eclasses.len() * 1000);
+    // outputs share the visited map so logic used by several outputs is only emitted once
+    let mut visited = FxHashMap::default();
+    let mut visit_count = FxHashMap::default();
+    let mut parts: Vec<String> = Vec::with_capacity(graph.root_eclasses.len());
 
     for root in &graph.root_eclasses {
-        let mut visited = FxHashMap::default();
-        let mut visit_count = FxHashMap::default();
-
         let equation = dag_to_equations(&graph.nodes, root, &mut visited, &mut visit_count, is_large);
+        // graphs written before multi-output roots chain all outputs with `&`
+        parts.extend(equation.split('&').map(str::trim).map(String::from));
+    }
 
-        let variables: Vec<String> = graph.nodes.values()
-            .filter(|node| node.children.is_empty() && node.op != "1" && node.op != "0")
-            .map(|node| node.op.clone())
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
+    let variables = circuit_inputs(&graph);
+    let output_names = circuit_outputs(&graph, parts.len(), Some(prefix_mapping_path))?;
 
-        let parts: Vec<String> = equation.split('&').map(str::trim).map(String::from).collect();
+    Ok(generate_eqn_content(&variables, &output_names, parts, visited))
+}
 
-        let content = generate_eqn_content(&variables, parts, "p", visited, &prefix_mapping);
-        final_content.push_str(&content);
-        final_content.push('\n');
+/// Primary inputs of the circuit, in declaration order when the graph records them
+fn circuit_inputs(graph: &Graph) -> Vec<String> {
+    if !graph.input_names.is_empty() {
+        return graph.input_names.clone();
     }
+    graph.nodes.values()
+        .filter(|node| node.children.is_empty() && node.op != "1" && node.op != "0")
+        .map(|node| node.op.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect()
+}
 
-    Ok(final_content)
+/// Names of the primary outputs, read from the OUTORDER of the original circuit
+/// when the graph does not record them
+fn circuit_outputs(graph: &Graph, num_outputs: usize, prefix_mapping_path: Option<&str>) -> Result<Vec<String>, Box<dyn StdError>> {
+    if graph.root_names.len() == num_outputs {
+        return Ok(graph.root_names.clone());
+    }
+    let prefix_mapping_path = prefix_mapping_path.ok_or("the e-graph records no output names and no original circuit was given")?;
+    let prefix_mapping = read_prefix_mapping(prefix_mapping_path)?;
+    Ok((0..num_outputs)
+        .map(|i| format!("p[{}]", i))
+        .map(|f_number| prefix_mapping.get(&f_number).cloned().unwrap_or(f_number))
+        .collect())
+}
+
+// ==================================================
+// Step 4 (BLIF): Convert JSON to BLIF Format
+// ==================================================
+
+/// Converts JSON representation to BLIF, one `.names` per extracted node.
+/// Cut latches are restored as `.latch` lines.
+fn json_to_blif(json_str: &str, prefix_mapping_path: &str) -> Result<String, Box<dyn StdError>> {
+    let graph: Graph = serde_json::from_str(json_str)?;
+
+    if is_cyclic_graph(&graph.nodes) {
+        return Err("The graph is cyclic.".into());
+    }
+
+    // graphs written before multi-output roots chain all outputs with `&`
+    let mut roots = Vec::with_capacity(graph.root_eclasses.len());
+    for root in &graph.root_eclasses {
+        flatten_concat(&graph.nodes, root, &mut roots)?;
+    }
+
+    let inputs = circuit_inputs(&graph);
+    let output_names = circuit_outputs(&graph, roots.len(), Some(prefix_mapping_path))?;
+    let latch_outputs: HashSet<&str> = graph.latches.iter().map(|latch| latch.output.as_str()).collect();
+    let latch_inputs: HashSet<&str> = graph.latches.iter().map(|latch| latch.input.as_str()).collect();
+
+    let mut content = String::from(".model circuit\n");
+    let primary_inputs: Vec<&str> = inputs.iter().map(String::as_str).filter(|name| !latch_outputs.contains(name)).collect();
+    let primary_outputs: Vec<&str> = output_names.iter().map(String::as_str).filter(|name| !latch_inputs.contains(name)).collect();
+    content.push_str(&format!(".inputs {}\n", primary_inputs.join(" ")));
+    content.push_str(&format!(".outputs {}\n", primary_outputs.join(" ")));
+    for latch in &graph.latches {
+        let init = match latch.init {
+            Some(false) => "0",
+            Some(true) => "1",
+            None => "3",
+        };
+        content.push_str(&format!(".latch {} {} {}\n", latch.input, latch.output, init));
+    }
+
+    // iterative dfs over the extracted dag, every node is written once
+    let mut written: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&str> = roots.iter().map(String::as_str).collect();
+    while let Some(class) = stack.pop() {
+        if !written.insert(class) {
+            continue;
+        }
+        let node = graph.nodes.get(class).ok_or_else(|| format!("e-class {} was not extracted", class))?;
+        stack.extend(node.children.iter().map(String::as_str));
+        let fanins: Vec<String> = node.children.iter().map(|child| blif_signal(&graph.nodes, child)).collect();
+        let cover = match (node.op.as_str(), fanins.len()) {
+            ("0", 0) => "",
+            ("1", 0) => "1\n",
+            // primary inputs are referenced by name
+            (_, 0) => continue,
+            ("!", 1) => "0 1\n",
+            ("*", 2) => "11 1\n",
+            ("+", 2) => "1- 1\n-1 1\n",
+            ("->", 2) => "0- 1\n-1 1\n",
+            ("^", 2) => "10 1\n01 1\n",
+            ("xnor", 2) => "11 1\n00 1\n",
+            ("mux", 3) => "11- 1\n0-1 1\n",
+            ("maj", 3) => "11- 1\n1-1 1\n-11 1\n",
+            (op, _) => return Err(format!("operator `{}` cannot be written as BLIF", op).into()),
+        };
+        let mut signals = fanins;
+        signals.push(blif_signal(&graph.nodes, class));
+        content.push_str(&format!(".names {}\n{}", signals.join(" "), cover));
+    }
+
+    // outputs are buffers of the signal they are rooted at
+    for (name, root) in output_names.iter().zip(&roots) {
+        let signal = blif_signal(&graph.nodes, root);
+        if signal != *name {
+            content.push_str(&format!(".names {} {}\n1 1\n", signal, name));
+        }
+    }
+
+    content.push_str(".end\n");
+    Ok(content)
+}
+
+/// Name of the BLIF signal driven by an e-class: inputs keep their name,
+/// every other class gets a `new_n_` wire
+fn blif_signal(nodes: &FxHashMap<String, Node>, class: &str) -> String {
+    match nodes.get(class) {
+        Some(node) if node.children.is_empty() && node.op != "0" && node.op != "1" => node.op.clone(),
+        _ => format!("new_n_{}", class),
+    }
+}
+
+/// Collects the outputs of a (possibly `&`-chained) root in order
+fn flatten_concat(nodes: &FxHashMap<String, Node>, root: &str, roots: &mut Vec<String>) -> Result<(), Box<dyn StdError>> {
+    let mut stack = vec![root];
+    while let Some(class) = stack.pop() {
+        let node = nodes.get(class).ok_or_else(|| format!("e-class {} was not extracted", class))?;
+        if node.op == "&" {
+            stack.extend(node.children.iter().rev().map(String::as_str));
+        } else {
+            roots.push(class.to_string());
+        }
+    }
+    Ok(())
+}
+
+// ==================================================
+// Step 4 (AIGER): Convert JSON to an And-Inverter Graph
+// ==================================================
+
+/// Converts JSON representation to an and-inverter graph.
+/// Cut latches are restored as AIGER latches.
+fn json_to_aig(json_str: &str, prefix_mapping_path: Option<&str>) -> Result<Aig, Box<dyn StdError>> {
+    let graph: Graph = serde_json::from_str(json_str)?;
+
+    if is_cyclic_graph(&graph.nodes) {
+        return Err("The graph is cyclic.".into());
+    }
+
+    let mut roots = Vec::with_capacity(graph.root_eclasses.len());
+    for root in &graph.root_eclasses {
+        flatten_concat(&graph.nodes, root, &mut roots)?;
+    }
+    let inputs = circuit_inputs(&graph);
+    let output_names = circuit_outputs(&graph, roots.len(), prefix_mapping_path)?;
+    let latch_outputs: HashSet<&str> = graph.latches.iter().map(|latch| latch.output.as_str()).collect();
+    let latch_inputs: HashSet<&str> = graph.latches.iter().map(|latch| latch.input.as_str()).collect();
+
+    // inputs and latches take the first variables, the and gates follow
+    let mut builder = AigBuilder::default();
+    let mut signals: HashMap<&str, u32> = HashMap::new();
+    for name in inputs.iter().filter(|name| !latch_outputs.contains(name.as_str())) {
+        let lit = builder.aig.input(builder.aig.num_inputs);
+        builder.aig.num_inputs += 1;
+        builder.aig.input_names.push(name.clone());
+        signals.insert(name, lit);
+    }
+    for (index, latch) in graph.latches.iter().enumerate() {
+        builder.aig.latches.push(AigLatch { next: 0, init: latch.init });
+        builder.aig.latch_names.push(latch.output.clone());
+        signals.insert(&latch.output, builder.aig.latch(index as u32));
+    }
+
+    // iterative post-order dfs, a class is built once all of its children are
+    let mut lits: HashMap<&str, u32> = HashMap::new();
+    let mut stack: Vec<(&str, bool)> = roots.iter().map(|root| (root.as_str(), false)).collect();
+    while let Some((class, expanded)) = stack.pop() {
+        if lits.contains_key(class) {
+            continue;
+        }
+        let node = graph.nodes.get(class).ok_or_else(|| format!("e-class {} was not extracted", class))?;
+        if !expanded {
+            stack.push((class, true));
+            stack.extend(node.children.iter().map(|child| (child.as_str(), false)));
+            continue;
+        }
+        let children: Vec<u32> = node.children.iter().map(|child| lits[child.as_str()]).collect();
+        let lit = match (node.op.as_str(), children.as_slice()) {
+            ("0", []) => 0,
+            ("1", []) => 1,
+            (name, []) => *signals.get(name).ok_or_else(|| format!("`{}` is not a primary input", name))?,
+            ("!", [a]) => a ^ 1,
+            ("*", [a, b]) => builder.and(*a, *b),
+            ("+", [a, b]) => builder.or(*a, *b),
+            ("->", [a, b]) => builder.or(a ^ 1, *b),
+            ("^", [a, b]) => builder.xor(*a, *b),
+            ("xnor", [a, b]) => builder.xor(*a, *b) ^ 1,
+            ("mux", [s, a, b]) => builder.mux(*s, *a, *b),
+            ("maj", [a, b, c]) => builder.maj(*a, *b, *c),
+            (op, _) => return Err(format!("operator `{}` cannot be written as AIGER", op).into()),
+        };
+        lits.insert(class, lit);
+    }
+
+    for (name, root) in output_names.iter().zip(&roots) {
+        let lit = lits[root.as_str()];
+        if latch_inputs.contains(name.as_str()) {
+            for (latch, aig_latch) in graph.latches.iter().zip(builder.aig.latches.iter_mut()) {
+                if latch.input == *name {
+                    aig_latch.next = lit;
+                }
+            }
+        } else {
+            builder.aig.outputs.push(lit);
+            builder.aig.output_names.push(name.clone());
+        }
+    }
+    Ok(builder.aig)
 }
 
 // ===================================================
@@ -221,70 +444,109 @@ fn dag_to_equations(
         _ => {
             let operands: Vec<String> = node.children
                 .iter()
-                .map(|child_id| dag_to_equations(nodes, child_id, visited, visit_count, is_large))
-                .collect();
-
-            match operands.len() {
-                0 => node.op.clone(),
-                1 => format!("{}({})", node.op, operands[0]),
-                2 => {
-                    if is_large {
-                        let (lhs, rhs) = (operands[0].clone(), operands[1].clone());
-                        let (lhs_id, rhs_id) = (
-                            if lhs.len() > 50 { Some(string_to_unique_id(&lhs)) } else { None },
-                            if rhs.len() > 50 { Some(string_to_unique_id(&rhs)) } else { None },
-                        );
-                        if let Some(id) = lhs_id { visited.insert(id.to_string(), lhs.clone()); }
-                        if let Some(id) = rhs_id { visited.insert(id.to_string(), rhs.clone()); }
-                        format!(
-                            "({} {} {})",
-                            lhs_id.map_or_else(|| lhs, |id| format!("new_n_{}", id)),
-                            node.op,
-                            rhs_id.map_or_else(|| rhs, |id| format!("new_n_{}", id))
-                        )
+                .map(|child_id| {
+                    let operand = dag_to_equations(nodes, child_id, visited, visit_count, is_large);
+                    // in large mode operands longer than 50 characters get their own equation
+                    if is_large && operand.len() > 50 {
+                        named_operand(&operand, visited)
                     } else {
-                        format!("({} {} {})", operands[0], node.op, operands[1])
+                        operand
                     }
+                })
+                .collect();
+
+            if let Some(expression) = lower_to_and_or(&node.op, &operands, visited) {
+                expression
+            } else {
+                match operands.len() {
+                    0 => node.op.clone(),
+                    1 => format!("{}({})", node.op, operands[0]),
+                    2 => format!("({} {} {})", operands[0], node.op, operands[1]),
+                    _ => unreachable!(),
                 }
-                _ => unreachable!(),
             }
         }
     };
 
+    remember_shared(node_id, expression, visited, visit_count)
+}
+
+/// Records the expression of a node that is used more than once, so it is written as its own equation
+fn remember_shared(
+    node_id: &str,
+    expression: String,
+    visited: &mut FxHashMap<String, String>,
+    visit_count: &FxHashMap<String, usize>,
+) -> String {
     if visit_count[node_id] > 1 && (expression.contains(' ') || expression.contains('(')) {
         visited.insert(node_id.to_string(), expression.clone());
     }
-
     expression
 }
 
+/// Writes an operand that is not a plain signal as its own new_n_ equation, named by its hash
+fn named_operand(operand: &str, visited: &mut FxHashMap<String, String>) -> String {
+    if !operand.contains(' ') && !operand.contains('(') {
+        return operand.to_string();
+    }
+    let id = string_to_unique_id(operand);
+    visited.insert(id.to_string(), operand.to_string());
+    format!("new_n_{}", id)
+}
+
+/// eqn only has `!`, `*` and `+`, the other gates are written in their and/or form.
+/// Operands the and/or form uses twice are named, so chains of these gates stay linear in size.
+fn lower_to_and_or(op: &str, operands: &[String], visited: &mut FxHashMap<String, String>) -> Option<String> {
+    let mut named = |operand: &String| named_operand(operand, visited);
+    Some(match (op, operands) {
+        ("^", [a, b]) => {
+            let (a, b) = (named(a), named(b));
+            format!("(({} * !({})) + (!({}) * {}))", a, b, a, b)
+        }
+        ("xnor", [a, b]) => {
+            let (a, b) = (named(a), named(b));
+            format!("(({} * {}) + (!({}) * !({})))", a, b, a, b)
+        }
+        ("mux", [s, a, b]) => {
+            let s = named(s);
+            format!("(({} * {}) + (!({}) * {}))", s, a, s, b)
+        }
+        ("maj", [a, b, c]) => {
+            let (a, b) = (named(a), named(b));
+            format!("(({} * {}) + ({} * ({} + {})))", a, b, c, a, b)
+        }
+        _ => return None,
+    })
+}
+
 // ===================================================
 // Helper functions (in json2eqn): Read Prefix Mapping
 // ===================================================
 
 /// Reads the prefix mapping from a file
-fn read_prefix_mapping(file_path: &str) -> FxHashMap<String, String> {
-    //println!("file path: {}", file_path);
-    let file = File::open(file_path).expect("Unable to open file");
-    let reader = BufReader::new(file);
+fn read_prefix_mapping(file_path: &str) -> Result<FxHashMap<String, String>, Box<dyn StdError>> {
+    let mut content = String::new();
+    File::open(file_path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| format!("{}: {}", file_path, e))?;
     let mut mapping = FxHashMap::default();
 
-    for line in reader.lines() {
-        let line = line.expect("Unable to read line");
-        if line.starts_with("OUTORDER = ") {
-            let parts: Vec<&str> = line
-                .trim_start_matches("OUTORDER = ")
-                .trim_end_matches(';')
-                .split_whitespace()
-                .collect();
-            for (index, part) in parts.iter().enumerate() {
-                mapping.insert(format!("p[{}]", index), part.to_string());
-            }
-            break;
+    // OUTORDER may be wrapped over several lines, it ends at the first `;`
+    let statement = content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Some(start) = statement.find("OUTORDER") {
+        let outorder = &statement[start..];
+        let outorder = &outorder[..outorder.find(';').unwrap_or(outorder.len())];
+        let names = outorder.splitn(2, '=').nth(1).unwrap_or("");
+        for (index, part) in names.split_whitespace().enumerate() {
+            mapping.insert(format!("p[{}]", index), part.to_string());
         }
     }
 
-    mapping
+    Ok(mapping)
 }
 
 
@@ -295,26 +557,17 @@ fn read_prefix_mapping(file_path: &str) -> FxHashMap<String, String> {
 /// Generates the content for the equation file
 fn generate_eqn_content(
     variables: &[String],
+    output_names: &[String],
     parts: Vec<String>,
-    f_prefix: &str,
     visited: FxHashMap<String, String>,
-    prefix_mapping: &FxHashMap<String, String>,
 ) -> String {
     let mut content = format!("INORDER = {};\n", variables.join(" "));
+    content.push_str(&format!("OUTORDER = {};\n", output_names.join(" ")));
 
-    for (index, part) in parts.iter().enumerate() {
-        let f_number = format!("{}[{}]", f_prefix, index);
-        let mapped_prefix = prefix_mapping.get(&f_number).unwrap_or(&f_number);
-        content.push_str(&format!("{} = {};\n", mapped_prefix, part));
+    for (name, part) in output_names.iter().zip(parts.iter()) {
+        content.push_str(&format!("{} = {};\n", name, part));
     }
 
-    let outorder: Vec<String> = (0..parts.len())
-        .map(|i| format!("{}[{}]", f_prefix, i))
-        .map(|f_number| prefix_mapping.get(&f_number).unwrap_or(&f_number).to_string())
-        .collect();
-
-    content.push_str(&format!("OUTORDER = {};\n", outorder.join(" ")));
-
     for (node_id, expr) in visited {
         content.push_str(&format!("new_n_{} = {};\n", node_id, expr));
     }
@@ -339,6 +592,30 @@ pub fn process_circuit_conversion(
     extraction_result_to_eqn(dag_cost_json, saturated_graph_json, prefix_mapping_path, is_large)
 }
 
+/// Processes the circuit conversion to BLIF
+pub fn process_circuit_conversion_blif(
+    extraction_result: &crate::ExtractionResult,
+    saturated_graph_json: &str,
+    prefix_mapping_path: &str,
+) -> Result<String, Box<dyn StdError>> {
+    let dag_cost_json = extraction_result.dag_cost_json.as_ref()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "DAG cost JSON not found"))?;
+
+    extraction_result_to_blif(dag_cost_json, saturated_graph_json, prefix_mapping_path)
+}
+
+/// Processes the circuit conversion to an and-inverter graph
+pub fn process_circuit_conversion_aig(
+    extraction_result: &crate::ExtractionResult,
+    saturated_graph_json: &str,
+    prefix_mapping_path: &str,
+) -> Result<Aig, Box<dyn StdError>> {
+    let dag_cost_json = extraction_result.dag_cost_json.as_ref()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "DAG cost JSON not found"))?;
+
+    extraction_result_to_aig(dag_cost_json, saturated_graph_json, Some(prefix_mapping_path))
+}
+
 /// Converts extraction result to equation format
 pub fn extraction_result_to_eqn(
     dag_cost_json: &str,
@@ -352,3 +629,112 @@ pub fn extraction_result_to_eqn(
     json_to_eqn(&final_json, prefix_mapping_path, is_large)
 }
 
+
+/// Converts extraction result to BLIF
+pub fn extraction_result_to_blif(
+    dag_cost_json: &str,
+    saturated_graph_json: &str,
+    prefix_mapping_path: &str,
+) -> Result<String, Box<dyn StdError>> {
+    let processed_json = process_json_with_choices(dag_cost_json, saturated_graph_json)?;
+    let simplified_json = process_json_simplify_keys(&processed_json)?;
+    let final_json = update_root_eclasses(saturated_graph_json, &simplified_json)?;
+    json_to_blif(&final_json, prefix_mapping_path)
+}
+
+/// Converts extraction result to an and-inverter graph.
+/// The output names come from the e-graph, or from the OUTORDER of `prefix_mapping_path` if it records none.
+pub fn extraction_result_to_aig(
+    dag_cost_json: &str,
+    saturated_graph_json: &str,
+    prefix_mapping_path: Option<&str>,
+) -> Result<Aig, Box<dyn StdError>> {
+    let processed_json = process_json_with_choices(dag_cost_json, saturated_graph_json)?;
+    let simplified_json = process_json_simplify_keys(&processed_json)?;
+    let final_json = update_root_eclasses(saturated_graph_json, &simplified_json)?;
+    json_to_aig(&final_json, prefix_mapping_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a0 ^ a1 ^ ... ^ a{depth} as an extracted graph, every xor used once
+    fn xor_chain_json(depth: usize) -> String {
+        let mut nodes = serde_json::Map::new();
+        let mut last = "a0".to_string();
+        nodes.insert(last.clone(), serde_json::json!({ "op": "a0", "children": [], "eclass": "a0", "cost": 1.0 }));
+        for i in 1..=depth {
+            let input = format!("a{}", i);
+            nodes.insert(input.clone(), serde_json::json!({ "op": input, "children": [], "eclass": input, "cost": 1.0 }));
+            let class = format!("x{}", i);
+            nodes.insert(class.clone(), serde_json::json!({ "op": "^", "children": [last, input], "eclass": class, "cost": 1.0 }));
+            last = class;
+        }
+        let inputs: Vec<String> = (0..=depth).map(|i| format!("a{}", i)).collect();
+        serde_json::json!({ "nodes": nodes, "root_eclasses": [last], "root_names": ["f"], "input_names": inputs }).to_string()
+    }
+
+    #[test]
+    fn xor_chains_stay_linear_in_size() {
+        let depth = 24;
+        for is_large in [false, true] {
+            let eqn = json_to_eqn(&xor_chain_json(depth), "", is_large).unwrap();
+            assert!(eqn.len() < depth * 200, "{} bytes for a chain of {} xors (large: {})", eqn.len(), depth, is_large);
+            // every named operand is defined
+            for reference in eqn.split("new_n_").skip(1) {
+                let id: String = reference.chars().take_while(char::is_ascii_alphanumeric).collect();
+                assert!(eqn.contains(&format!("new_n_{} = ", id)), "new_n_{} is not defined (large: {})", id, is_large);
+            }
+        }
+    }
+
+    // f = a * b + c and g = !(a * b), with an unchosen alternative for f
+    fn saturated_graph(root_names: &[&str]) -> String {
+        let node = |op: &str, children: &[&str], eclass: &str| serde_json::json!({ "op": op, "children": children, "eclass": eclass, "cost": 1.0 });
+        serde_json::json!({
+            "nodes": {
+                "a.0": node("a", &[], "a"),
+                "b.0": node("b", &[], "b"),
+                "c.0": node("c", &[], "c"),
+                "ab.0": node("*", &["a.0", "b.0"], "ab"),
+                "f.0": node("+", &["ab.0", "c.0"], "f"),
+                "f.1": node("->", &["ab.0", "c.0"], "f"),
+                "g.0": node("!", &["ab.0"], "g"),
+            },
+            "root_eclasses": ["f", "g"],
+            "root_names": root_names,
+            "input_names": ["a", "b", "c"],
+        })
+        .to_string()
+    }
+
+    fn extraction_result() -> String {
+        serde_json::json!({ "choices": { "a": "a.0", "b": "b.0", "c": "c.0", "ab": "ab.0", "f": "f.0", "g": "g.0" } }).to_string()
+    }
+
+    #[test]
+    fn aiger_written_from_an_extraction_reads_back_to_the_same_circuit() {
+        let aig = extraction_result_to_aig(&extraction_result(), &saturated_graph(&["f", "g"]), None).unwrap();
+        let path = std::env::temp_dir().join(format!("circuit-conversion-{}.aag", std::process::id()));
+        let path = path.to_str().unwrap();
+        aig.to_file(path);
+        let read = cec::aiger_parser::parse_aiger(&std::fs::read(path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let names = |signals: &[(String, cec::netlist::Pos)]| signals.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&read.inputs), ["a", "b", "c"]);
+        assert_eq!(names(&read.outputs), ["f", "g"]);
+        let expected = cec::eqn_parser::parse_eqn("INORDER = a b c;\nOUTORDER = f g;\nf = a * b + c;\ng = !(a * b);\n").unwrap();
+        let report = cec::check::check_equivalence(&expected, &read, &cec::check::CecConfig::default()).unwrap();
+        assert!(report.equivalent(), "{:?}", report.outputs);
+    }
+
+    #[test]
+    fn output_names_need_the_egraph_or_the_original_circuit() {
+        let err = extraction_result_to_aig(&extraction_result(), &saturated_graph(&[]), None).unwrap_err();
+        assert!(err.to_string().contains("no output names"), "{}", err);
+        let missing = std::env::temp_dir().join("circuit-conversion-missing.eqn");
+        assert!(extraction_result_to_aig(&extraction_result(), &saturated_graph(&[]), missing.to_str()).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use super::*;
-use crate::extract::circuit_conversion::process_circuit_conversion;
+use crate::extract::aiger::Aig;
+use crate::extract::circuit_conversion::{process_circuit_conversion, process_circuit_conversion_aig, process_circuit_conversion_blif};
 use rand::prelude::*;
 use rayon::prelude::*;
 use rustc_hash::{FxHashMap, FxHashSet};
@@ -52,6 +53,18 @@ fn call_abc(eqn_content: &str) -> Result<f32, Box<dyn std::error::Error>> {
 
     //println!("Reading equation file...");
     abc.execute_command(&format!("read_eqn {}", temp_path));
+    abc_delay(&mut abc)
+}
+
+// Same as `call_abc`, but hands the circuit to ABC as an AIG instead of eqn text
+fn call_abc_aig(aig: &Aig) -> Result<f32, Box<dyn std::error::Error>> {
+    let mut abc = Abc::new();
+    abc.read_aig(aig);
+    abc_delay(&mut abc)
+}
+
+// Maps the circuit currently loaded in ABC and returns its delay
+fn abc_delay(abc: &mut Abc) -> Result<f32, Box<dyn std::error::Error>> {
     //println!("Reading library...");
     abc.execute_command(&format!("read_lib ../abc/asap7_clean.lib"));
     //println!("Performing structural hashing...");
@@ -694,28 +707,51 @@ fn calculate_abc_cost_or_dump(
     prefix_mapping_path: &str,
     dump_to_file: bool,
 ) -> f64 {
-    let eqn_content = match process_circuit_conversion(
-        result,
-        saturated_graph_json,
-        prefix_mapping_path,
-        false,
-    ) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("Error in circuit conversion: {}", e);
-            return f64::INFINITY;
-        }
-    };
     // dump file mode
     if dump_to_file {
+        let eqn_content = match process_circuit_conversion(
+            result,
+            saturated_graph_json,
+            prefix_mapping_path,
+            false,
+        ) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Error in circuit conversion: {}", e);
+                return f64::INFINITY;
+            }
+        };
         if let Err(e) = std::fs::write("src/extract/tmp/best_result.eqn", &eqn_content) {
             eprintln!("Error writing to file: {}", e);
             // Handle the error appropriately
         }
+        match process_circuit_conversion_blif(result, saturated_graph_json, prefix_mapping_path) {
+            Ok(blif_content) => {
+                if let Err(e) = std::fs::write("src/extract/tmp/best_result.blif", &blif_content) {
+                    eprintln!("Error writing to file: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Error in BLIF conversion: {}", e),
+        }
+        match process_circuit_conversion_aig(result, saturated_graph_json, prefix_mapping_path) {
+            Ok(aig) => {
+                if let Err(e) = std::fs::write("src/extract/tmp/best_result.aig", aig.to_binary()) {
+                    eprintln!("Error writing to file: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Error in AIGER conversion: {}", e),
+        }
         return f64::INFINITY;
-    // abc cost calculation mode
+    // abc cost calculation mode, the circuit goes to ABC as an AIG without the eqn detour
     } else {
-        match call_abc(&eqn_content) {
+        let aig = match process_circuit_conversion_aig(result, saturated_graph_json, prefix_mapping_path) {
+            Ok(aig) => aig,
+            Err(e) => {
+                eprintln!("Error in circuit conversion: {}", e);
+                return f64::INFINITY;
+            }
+        };
+        match call_abc_aig(&aig) {
             Ok(delay) => delay as f64,
             Err(e) => {
                 eprintln!("Error in ABC processing: {}", e);
@@ -1,5 +1,4 @@
-//use aiger_rs::aig;
-//use aig::Aig;
+use crate::extract::aiger::Aig;
 use std::ffi::{c_void, CString};
 use std::sync::Mutex;
 use std::cell::RefCell;
@@ -64,23 +63,23 @@ impl Abc {
     //     println!("{}", output_str);
     // }
 
-    // pub fn read_aig(&mut self, aig: &Aig) {
-    //     let tmpfile = tempfile::NamedTempFile::new().unwrap();
-    //     let path = tmpfile.path().as_os_str().to_str().unwrap();
-    //     aig.to_file(path);
-    //     let command = format!("read_aiger {};", path);
-    //     let command = CString::new(command).unwrap();
-    //     let res = unsafe { Cmd_CommandExecute(self.ptr, command.as_ptr()) };
-    //     assert!(res == 0, "abc read aig failed");
-    // }
+    pub fn read_aig(&mut self, aig: &Aig) {
+        let tmpfile = tempfile::Builder::new().suffix(".aig").tempfile().unwrap();
+        let path = tmpfile.path().as_os_str().to_str().unwrap();
+        aig.to_file(path);
+        let command = format!("read_aiger {};", path);
+        let command = CString::new(command).unwrap();
+        let res = unsafe { Cmd_CommandExecute(self.ptr, command.as_ptr()) };
+        assert!(res == 0, "abc read aig failed");
+    }
 
-    // pub fn write_aig(&mut self) -> Aig {
-    //     let tmpfile = tempfile::NamedTempFile::new().unwrap();
-    //     let path = tmpfile.path().as_os_str().to_str().unwrap();
-    //     let command = format!("write_aiger {};", path);
-    //     let command = CString::new(command).unwrap();
-    //     let res = unsafe { Cmd_CommandExecute(self.ptr, command.as_ptr()) };
-    //     assert!(res == 0, "abc write aig failed");
-    //     Aig::from_file(path)
-    // }
+    pub fn write_aig(&mut self) -> Aig {
+        let tmpfile = tempfile::Builder::new().suffix(".aig").tempfile().unwrap();
+        let path = tmpfile.path().as_os_str().to_str().unwrap();
+        let command = format!("write_aiger {};", path);
+        let command = CString::new(command).unwrap();
+        let res = unsafe { Cmd_CommandExecute(self.ptr, command.as_ptr()) };
+        assert!(res == 0, "abc write aig failed");
+        Aig::from_file(path)
+    }
 }
\ No newline at end of file
@@ -255,6 +255,82 @@ fn write_output_file(
     .unwrap();
 }
 
+// Function to get the optional AIGER output filename from the command-line arguments
+// Input: A mutable reference to the `pico_args::Arguments` instance
+// Returns: The AIGER filename, if the extracted circuit should also be written as AIGER
+fn get_aig_output_filename(args: &mut pico_args::Arguments) -> Option<String> {
+    args.opt_value_from_str("--out-aig").unwrap()
+}
+
+// Function to get the optional original circuit from the command-line arguments
+// Input: A mutable reference to the `pico_args::Arguments` instance
+// Returns: The eqn file whose OUTORDER names the outputs of e-graphs that do not record them
+fn get_prefix_mapping_filename(args: &mut pico_args::Arguments) -> Option<String> {
+    args.opt_value_from_str("--prefix-mapping").unwrap()
+}
+
+// Function to write the extracted circuit as AIGER (binary, or ASCII for `.aag` files)
+// Input:
+//   - `aig_filename`: The AIGER file to write
+//   - `egraph_filename`: The serialized e-graph the result was extracted from
+//   - `prefix_mapping_filename`: The original circuit, only read if the e-graph records no output names
+//   - `dag_cost_extraction_result`: The extraction result
+fn write_aig_result(aig_filename: &str, egraph_filename: &str, prefix_mapping_filename: Option<&str>, dag_cost_extraction_result: &ExtractionResult) {
+    let saturated_graph_json = fs::read_to_string(egraph_filename).unwrap();
+    let dag_cost_json = to_string_pretty(dag_cost_extraction_result).unwrap();
+    match extract::circuit_conversion::extraction_result_to_aig(&dag_cost_json, &saturated_graph_json, prefix_mapping_filename) {
+        Ok(aig) => aig.to_file(aig_filename),
+        Err(e) => eprintln!("Error in AIGER conversion: {}", e),
+    }
+}
+
+// Function to get the optional reference netlist the extracted circuit is checked against
+// Input: A mutable reference to the `pico_args::Arguments` instance
+// Returns: The netlist file (eqn, BLIF, AIGER or Verilog), usually the circuit before rewriting
+fn get_cec_reference(args: &mut pico_args::Arguments) -> Option<String> {
+    args.opt_value_from_str("--cec").unwrap()
+}
+
+// Function to check the extracted circuit against a reference netlist with the SAT-based equivalence checker
+// Input:
+//   - `reference`: The reference netlist file
+//   - `egraph_filename`: The serialized e-graph the result was extracted from
+//   - `dag_cost_extraction_result`: The extraction result
+// Returns: Whether every output was proven equivalent
+fn check_extraction_result(reference: &str, egraph_filename: &str, dag_cost_extraction_result: &ExtractionResult) -> bool {
+    let saturated_graph_json = fs::read_to_string(egraph_filename).unwrap();
+    let dag_cost_json = to_string_pretty(dag_cost_extraction_result).unwrap();
+    let extracted = match extract::circuit_conversion::extraction_result_to_eqn(&dag_cost_json, &saturated_graph_json, reference, false) {
+        Ok(eqn) => cec::eqn_parser::parse_eqn(&eqn).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let report = extracted
+        .and_then(|extracted| cec::read_netlist(reference).map(|reference| (extracted, reference)).map_err(|e| format!("{}: {}", reference, e)))
+        .and_then(|(extracted, reference)| cec::check::check_equivalence(&reference, &extracted, &cec::check::CecConfig::default()).map_err(|e| e.to_string()));
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error in equivalence checking: {}", e);
+            return false;
+        }
+    };
+    let mut shown = false;
+    for (output, verdict) in &report.outputs {
+        match verdict {
+            cec::check::Verdict::Equivalent(_) => {}
+            // the input assignment is printed for the first difference only
+            cec::check::Verdict::Different(_) if shown => println!("CEC {}: different", output),
+            _ => {
+                println!("CEC {}: {}", output, verdict);
+                shown = true;
+            }
+        }
+    }
+    let proven = report.count(|verdict| matches!(verdict, cec::check::Verdict::Equivalent(_)));
+    println!("CEC against {}: {} of {} outputs proven equivalent ({} SAT checks)", reference, proven, report.outputs.len(), report.sat_checks);
+    report.equivalent()
+}
+
 fn get_iteration(args: &mut pico_args::Arguments) -> u32 {
     args.opt_value_from_str("--iteration")
         .unwrap()
@@ -318,6 +394,9 @@ fn main() {
     let modified_filename_for_dag_cost = modify_filename(&filename, "input/", "out_dag_json/");
 
     let (num_samples, random_prob) = get_random_sampling_settings(&mut args);
+    let aig_filename = get_aig_output_filename(&mut args);
+    let prefix_mapping_filename = get_prefix_mapping_filename(&mut args);
+    let cec_reference = get_cec_reference(&mut args);
     // Check for any remaining arguments
     let rest = args.finish();
     if !rest.is_empty() {
@@ -392,6 +471,12 @@ fn main() {
         // Write the JSON result to files
         write_json_result(&modified_name_for_tree_cost, &tree_cost_extraction_result);
         write_json_result(&modified_name_for_dag_cost, &dag_cost_extraction_result);
+        if let Some(aig_filename) = &aig_filename {
+            write_aig_result(aig_filename, &filename, prefix_mapping_filename.as_deref(), &dag_cost_extraction_result);
+        }
+        let equivalent = cec_reference
+            .as_deref()
+            .map_or(true, |reference| check_extraction_result(reference, &filename, &dag_cost_extraction_result));
 
         // Log the result
         log_result(&filename, &extractor_name, dag_cost, us);
@@ -410,6 +495,9 @@ fn main() {
             "Time consumption of tree-based extraction: {} seconds",
             us as f64 / 1000000.0
         );
+        if !equivalent {
+            std::process::exit(1);
+        }
     } else { // extractor is random-based-faster-bottom-up
         // if the extractor is random
         let extractor: Arc<dyn Extractor + Send + Sync> = Arc::new(FasterBottomUpExtractorRandom);
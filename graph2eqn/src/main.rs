@@ -2,7 +2,7 @@ use serde::Deserialize;
 use rustc_hash::FxHashMap;
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{Read, Write};
 use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
 
@@ -19,6 +19,22 @@ struct Node {
 struct Graph {
     nodes: FxHashMap<String, Node>,
     root_eclasses: Vec<String>,
+    // names of the primary outputs, `root_names[i]` is rooted at `root_eclasses[i]`
+    #[serde(default)]
+    root_names: Vec<String>,
+    // names of the primary inputs in declaration order
+    #[serde(default)]
+    input_names: Vec<String>,
+    // latches cut into pseudo inputs (their outputs) and pseudo outputs (their inputs)
+    #[serde(default)]
+    latches: Vec<Latch>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Latch {
+    input: String,
+    output: String,
+    init: Option<bool>,
 }
 
 fn is_cyclic_graph(nodes: &FxHashMap<String, Node>) -> bool {
@@ -81,6 +97,50 @@ fn string_to_unique_id(s: &str) -> u64 {
     hasher.finish()
 }
 
+// an operand that is not a plain signal gets its own new_n_ equation, named by its hash
+fn named_operand(operand: &str, visited: &mut FxHashMap<String, String>) -> String {
+    if !operand.contains(' ') && !operand.contains('(') {
+        return operand.to_string();
+    }
+    let id = string_to_unique_id(operand);
+    visited.insert(id.to_string(), operand.to_string());
+    format!("new_n_{}", id)
+}
+
+// in large mode operands longer than 50 characters get their own equation
+fn long_operand(operand: String, visited: &mut FxHashMap<String, String>) -> String {
+    if operand.len() > 50 {
+        named_operand(&operand, visited)
+    } else {
+        operand
+    }
+}
+
+// eqn only has `!`, `*` and `+`, the other gates are written in their and/or form.
+// Operands the and/or form uses twice are named, so chains of these gates stay linear in size.
+fn lower_to_and_or(op: &str, operands: &[String], visited: &mut FxHashMap<String, String>) -> Option<String> {
+    let mut named = |operand: &String| named_operand(operand, visited);
+    Some(match (op, operands) {
+        ("^", [a, b]) => {
+            let (a, b) = (named(a), named(b));
+            format!("(({} * !({})) + (!({}) * {}))", a, b, a, b)
+        }
+        ("xnor", [a, b]) => {
+            let (a, b) = (named(a), named(b));
+            format!("(({} * {}) + (!({}) * !({})))", a, b, a, b)
+        }
+        ("mux", [s, a, b]) => {
+            let s = named(s);
+            format!("(({} * {}) + (!({}) * {}))", s, a, s, b)
+        }
+        ("maj", [a, b, c]) => {
+            let (a, b) = (named(a), named(b));
+            format!("(({} * {}) + ({} * ({} + {})))", a, b, c, a, b)
+        }
+        _ => return None,
+    })
+}
+
 fn dag_to_equations_small(
     nodes: &FxHashMap<String, Node>,
     node_id: &str,
@@ -109,7 +169,9 @@ fn dag_to_equations_small(
                 .iter()
                 .map(|child_id| dag_to_equations_small(nodes, child_id, visited, visit_count))
                 .collect();
-            if operands.is_empty() {
+            if let Some(expression) = lower_to_and_or(&node.op, &operands, visited) {
+                expression
+            } else if operands.is_empty() {
                 node.op.clone()
             } else if operands.len() == 1 {
                 format!("{}({})", node.op, operands[0])
@@ -119,11 +181,7 @@ fn dag_to_equations_small(
         }
     };
 
-    if visit_count[node_id] > 1 && (expression.contains(" ") || expression.contains("(")) {
-        visited.insert(node_id.to_string(), expression.clone());
-    }
-
-    expression
+    remember_shared(node_id, expression, visited, visit_count)
 }
 
 fn dag_to_equations_large(
@@ -151,84 +209,61 @@ fn dag_to_equations_large(
             let operands: Vec<String> = node
                 .children
                 .iter()
-                .map(|child_id| dag_to_equations_large(nodes, child_id, visited, visit_count))
+                .map(|child_id| {
+                    let operand = dag_to_equations_large(nodes, child_id, visited, visit_count);
+                    long_operand(operand, visited)
+                })
                 .collect();
-    
-            match operands.len() {
-                0 => node.op.clone(),
-                1 => {
-                    let operand = &operands[0];
-                    if operand.len() > 50 {
-                        let new_node_id = string_to_unique_id(operand);
-                        visited.insert(new_node_id.to_string(), operand.clone());
-                        format!("{}(new_n_{})", node.op, new_node_id)
-                    } else {
-                        format!("{}({})", node.op, operand)
-                    }
-                }
-                2 => {
-                    let lhs = &operands[0];
-                    let rhs = &operands[1];
-                    let (lhs_id, rhs_id) = (
-                        if lhs.len() > 50 {
-                            let id = string_to_unique_id(lhs);
-                            visited.insert(id.to_string(), lhs.clone());
-                            Some(id)
-                        } else {
-                            None
-                        },
-                        if rhs.len() > 50 {
-                            let id = string_to_unique_id(rhs);
-                            visited.insert(id.to_string(), rhs.clone());
-                            Some(id)
-                        } else {
-                            None
-                        },
-                    );
-    
-                    format!(
-                        "({} {} {})",
-                        lhs_id.map_or_else(|| lhs.clone(), |id| format!("new_n_{}", id)),
-                        node.op,
-                        rhs_id.map_or_else(|| rhs.clone(), |id| format!("new_n_{}", id))
-                    )
+
+            if let Some(expression) = lower_to_and_or(&node.op, &operands, visited) {
+                expression
+            } else {
+                match operands.len() {
+                    0 => node.op.clone(),
+                    1 => format!("{}({})", node.op, operands[0]),
+                    2 => format!("({} {} {})", operands[0], node.op, operands[1]),
+                    _ => unreachable!(),
                 }
-                _ => unreachable!(),
             }
         }
     };
 
+    remember_shared(node_id, expression, visited, visit_count)
+}
+
+// a node used more than once gets its own equation
+fn remember_shared(
+    node_id: &str,
+    expression: String,
+    visited: &mut FxHashMap<String, String>,
+    visit_count: &FxHashMap<String, usize>,
+) -> String {
     if visit_count[node_id] > 1 && (expression.contains(" ") || expression.contains("(")) {
         visited.insert(node_id.to_string(), expression.clone());
     }
-
     expression
 }
 
-fn format_synopsys_single(equation: &str) -> Vec<String> {
-    equation
-        .split('&')
-        .map(|part| part.trim().to_string())
-        .collect()
-}
-
 fn read_prefix_mapping(file_path: &str) -> FxHashMap<String, String> {
-    let file = File::open(file_path).expect("Unable to open file");
-    let reader = BufReader::new(file);
+    let mut content = String::new();
+    File::open(file_path)
+        .expect("Unable to open file")
+        .read_to_string(&mut content)
+        .expect("Unable to read file");
     let mut mapping = FxHashMap::default();
 
-    for line in reader.lines() {
-        let line = line.expect("Unable to read line");
-        if line.starts_with("OUTORDER = ") {
-            let parts: Vec<&str> = line
-                .trim_start_matches("OUTORDER = ")
-                .trim_end_matches(';')
-                .split_whitespace()
-                .collect();
-            for (index, part) in parts.iter().enumerate() {
-                mapping.insert(format!("p[{}]", index), part.to_string());
-            }
-            break;
+    // OUTORDER may be wrapped over several lines, it ends at the first `;`
+    let statement = content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Some(start) = statement.find("OUTORDER") {
+        let outorder = &statement[start..];
+        let outorder = &outorder[..outorder.find(';').unwrap_or(outorder.len())];
+        let names = outorder.split_once('=').map_or("", |(_, names)| names);
+        for (index, part) in names.split_whitespace().enumerate() {
+            mapping.insert(format!("p[{}]", index), part.to_string());
         }
     }
 
@@ -237,44 +272,181 @@ fn read_prefix_mapping(file_path: &str) -> FxHashMap<String, String> {
 
 fn write_to_file(
     variables: &Vec<String>,
+    output_names: &[String],
     parts: Vec<String>,
     file_name: &str,
-    f_prefix: &str,
     visited: FxHashMap<String, String>,
-    prefix_mapping: &FxHashMap<String, String>,
 ) {
     let mut file = File::create(file_name).expect("Unable to create file");
 
     writeln!(file, "INORDER = {};", variables.join(" ")).expect("Unable to write to file");
+    writeln!(file, "OUTORDER = {};", output_names.join(" ")).expect("Unable to write to file");
 
-    for (index, part) in parts.iter().enumerate() {
-        let f_number = format!("{}[{}]", f_prefix, index);
-        let mapped_prefix = prefix_mapping.get(&f_number).unwrap_or(&f_number);
-        writeln!(file, "{} = {};", mapped_prefix, part).expect("Unable to write to file");
+    for (name, part) in output_names.iter().zip(parts.iter()) {
+        writeln!(file, "{} = {};", name, part).expect("Unable to write to file");
     }
 
-    let outorder: Vec<String> = (0..parts.len())
-        .map(|i| format!("{}[{}]", f_prefix, i))
-        .map(|f_number| {
-            prefix_mapping
-                .get(&f_number)
-                .unwrap_or(&f_number)
-                .to_string()
-        })
-        .collect();
-
-    writeln!(file, "OUTORDER = {};", outorder.join(" ")).expect("Unable to write to file");
-
     for (node_id, expr) in visited.iter() {
         writeln!(file, "new_n_{} = {};", node_id, expr).expect("Unable to write to file");
     }
 }
 
+// Signal driven by an e-class: inputs keep their name, every other class is a new_n_ wire
+fn signal_name(nodes: &FxHashMap<String, Node>, class: &str) -> String {
+    match nodes.get(class) {
+        Some(node) if node.children.is_empty() && node.op != "0" && node.op != "1" => node.op.clone(),
+        _ => format!("new_n_{}", class),
+    }
+}
+
+// graphs written before multi-output roots chain all outputs with `&`
+fn flatten_concat(nodes: &FxHashMap<String, Node>, root: &str, roots: &mut Vec<String>) {
+    let mut stack = vec![root];
+    while let Some(class) = stack.pop() {
+        let node = nodes.get(class).expect("root e-class was not extracted");
+        if node.op == "&" {
+            stack.extend(node.children.iter().rev().map(String::as_str));
+        } else {
+            roots.push(class.to_string());
+        }
+    }
+}
+
+// Writes the extracted dag as BLIF, one .names per node, and restores the cut latches
+fn write_blif(graph: &Graph, variables: &[String], output_names: &[String], roots: &[String], file_name: &str) {
+    let latch_outputs: Vec<&str> = graph.latches.iter().map(|latch| latch.output.as_str()).collect();
+    let latch_inputs: Vec<&str> = graph.latches.iter().map(|latch| latch.input.as_str()).collect();
+    let primary_inputs: Vec<&str> = variables.iter().map(String::as_str).filter(|name| !latch_outputs.contains(name)).collect();
+    let primary_outputs: Vec<&str> = output_names.iter().map(String::as_str).filter(|name| !latch_inputs.contains(name)).collect();
+
+    let mut file = File::create(file_name).expect("Unable to create file");
+    writeln!(file, ".model circuit").expect("Unable to write to file");
+    writeln!(file, ".inputs {}", primary_inputs.join(" ")).expect("Unable to write to file");
+    writeln!(file, ".outputs {}", primary_outputs.join(" ")).expect("Unable to write to file");
+    for latch in &graph.latches {
+        let init = match latch.init {
+            Some(false) => "0",
+            Some(true) => "1",
+            None => "3",
+        };
+        writeln!(file, ".latch {} {} {}", latch.input, latch.output, init).expect("Unable to write to file");
+    }
+
+    let mut written = std::collections::HashSet::new();
+    let mut stack: Vec<&str> = roots.iter().map(String::as_str).collect();
+    while let Some(class) = stack.pop() {
+        if !written.insert(class) {
+            continue;
+        }
+        let node = graph.nodes.get(class).expect("e-class was not extracted");
+        stack.extend(node.children.iter().map(String::as_str));
+        let mut signals: Vec<String> = node.children.iter().map(|child| signal_name(&graph.nodes, child)).collect();
+        let cover = match (node.op.as_str(), signals.len()) {
+            ("0", 0) => "",
+            ("1", 0) => "1\n",
+            (_, 0) => continue,
+            ("!", 1) => "0 1\n",
+            ("*", 2) => "11 1\n",
+            ("+", 2) => "1- 1\n-1 1\n",
+            ("->", 2) => "0- 1\n-1 1\n",
+            ("^", 2) => "10 1\n01 1\n",
+            ("xnor", 2) => "11 1\n00 1\n",
+            ("mux", 3) => "11- 1\n0-1 1\n",
+            ("maj", 3) => "11- 1\n1-1 1\n-11 1\n",
+            (op, _) => panic!("operator `{}` cannot be written as BLIF", op),
+        };
+        signals.push(signal_name(&graph.nodes, class));
+        write!(file, ".names {}\n{}", signals.join(" "), cover).expect("Unable to write to file");
+    }
+
+    for (name, root) in output_names.iter().zip(roots) {
+        let signal = signal_name(&graph.nodes, root);
+        if signal != *name {
+            writeln!(file, ".names {} {}\n1 1", signal, name).expect("Unable to write to file");
+        }
+    }
+    writeln!(file, ".end").expect("Unable to write to file");
+}
+
+// Verilog identifier for a signal, names such as `a[0]` become escaped identifiers
+fn verilog_name(name: &str) -> String {
+    let simple = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    let keyword = matches!(
+        name,
+        "module" | "endmodule" | "input" | "output" | "wire" | "assign" | "and" | "or" | "not" | "nand" | "nor" | "xor" | "xnor" | "buf"
+    );
+    if simple && !keyword {
+        name.to_string()
+    } else {
+        format!("\\{} ", name)
+    }
+}
+
+// Writes the extracted dag as structural Verilog with one gate primitive per node.
+// Cut latches stay pseudo inputs and outputs, like in the eqn output.
+fn write_verilog(graph: &Graph, variables: &[String], output_names: &[String], roots: &[String], file_name: &str) {
+    let mut file = File::create(file_name).expect("Unable to create file");
+    let ports: Vec<String> = variables.iter().chain(output_names).map(|name| verilog_name(name)).collect();
+    writeln!(file, "module circuit ({});", ports.join(", ")).expect("Unable to write to file");
+    for name in variables {
+        writeln!(file, "  input {};", verilog_name(name)).expect("Unable to write to file");
+    }
+    for name in output_names {
+        writeln!(file, "  output {};", verilog_name(name)).expect("Unable to write to file");
+    }
+
+    let mut gates = Vec::new();
+    let mut wires = Vec::new();
+    let mut written = std::collections::HashSet::new();
+    let mut stack: Vec<&str> = roots.iter().map(String::as_str).collect();
+    while let Some(class) = stack.pop() {
+        if !written.insert(class) {
+            continue;
+        }
+        let node = graph.nodes.get(class).expect("e-class was not extracted");
+        stack.extend(node.children.iter().map(String::as_str));
+        let output = verilog_name(&signal_name(&graph.nodes, class));
+        let inputs: Vec<String> = node.children.iter().map(|child| verilog_name(&signal_name(&graph.nodes, child))).collect();
+        let gate = match (node.op.as_str(), inputs.as_slice()) {
+            ("0", []) => format!("assign {} = 1'b0;", output),
+            ("1", []) => format!("assign {} = 1'b1;", output),
+            (_, []) => continue,
+            ("!", [a]) => format!("not g{} ({}, {});", class, output, a),
+            ("*", [a, b]) => format!("and g{} ({}, {}, {});", class, output, a, b),
+            ("+", [a, b]) => format!("or g{} ({}, {}, {});", class, output, a, b),
+            ("->", [a, b]) => format!("assign {} = ~{} | {};", output, a, b),
+            ("^", [a, b]) => format!("xor g{} ({}, {}, {});", class, output, a, b),
+            ("xnor", [a, b]) => format!("xnor g{} ({}, {}, {});", class, output, a, b),
+            ("mux", [s, a, b]) => format!("assign {} = {} ? {} : {};", output, s, a, b),
+            ("maj", [a, b, c]) => format!("assign {} = ({} & {}) | ({} & {}) | ({} & {});", output, a, b, a, c, b, c),
+            (op, _) => panic!("operator `{}` cannot be written as Verilog", op),
+        };
+        wires.push(output);
+        gates.push(gate);
+    }
+
+    if !wires.is_empty() {
+        writeln!(file, "  wire {};", wires.join(", ")).expect("Unable to write to file");
+    }
+    for gate in gates {
+        writeln!(file, "  {}", gate).expect("Unable to write to file");
+    }
+    for (name, root) in output_names.iter().zip(roots) {
+        let signal = signal_name(&graph.nodes, root);
+        if signal != *name {
+            writeln!(file, "  assign {} = {};", verilog_name(name), verilog_name(&signal)).expect("Unable to write to file");
+        }
+    }
+    writeln!(file, "endmodule").expect("Unable to write to file");
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 3 {
         println!("Usage: <program> <path_to_input_json_file> <path_to_output_circuit_file> [mode] [check_cyclic]");
         println!("Mode can be 'small' or 'large'. If not specified, 'small' is used as default.");
+        println!("The circuit is written as BLIF if the output file ends in .blif, as structural Verilog for .v, as eqn otherwise.");
         std::process::exit(1);
     }
     
@@ -297,16 +469,56 @@ fn main() {
         }
     }
 
-    let root_nodes = &graph.root_eclasses;
-    let prefix_mapping = read_prefix_mapping("../e-rewriter/circuit0_opt.eqn");
-    //println!("prefix mapping: {:?}", prefix_mapping);
-    for (i, root) in root_nodes.iter().enumerate() {
-        let mut visited = FxHashMap::default();
-        let mut visit_count = FxHashMap::default();
+    //print the mode
+    println!("Mode: {}", mode);
 
-        //print the mode
-        println!("Mode: {}", mode);
-        
+    // graphs written before multi-output roots chain all outputs with `&`
+    let mut roots = Vec::with_capacity(graph.root_eclasses.len());
+    for root in &graph.root_eclasses {
+        flatten_concat(&graph.nodes, root, &mut roots);
+    }
+
+    let variables = if !graph.input_names.is_empty() {
+        graph.input_names.clone()
+    } else {
+        let mut variables = vec![];
+        for node in graph.nodes.values() {
+            if node.children.is_empty() && !variables.contains(&node.op) && node.op != "1" && node.op != "0" {
+                variables.push(node.op.clone());
+            }
+        }
+        variables
+    };
+
+    // output names come from the graph, older graphs fall back to the OUTORDER of the input circuit
+    let output_names: Vec<String> = if graph.root_names.len() == roots.len() {
+        graph.root_names.clone()
+    } else {
+        let prefix_mapping = read_prefix_mapping("../e-rewriter/circuit0_opt.eqn");
+        (0..roots.len())
+            .map(|i| format!("p[{}]", i))
+            .map(|f_number| prefix_mapping.get(&f_number).cloned().unwrap_or(f_number))
+            .collect()
+    };
+
+    if output_path.ends_with(".v") {
+        write_verilog(&graph, &variables, &output_names, &roots, output_path);
+        println!("Finished graph to Verilog conversion for {} outputs", output_names.len());
+        return;
+    }
+
+    if output_path.ends_with(".blif") {
+        write_blif(&graph, &variables, &output_names, &roots, output_path);
+        println!("Finished graph to BLIF conversion for {} outputs", output_names.len());
+        return;
+    }
+
+    // every root is a primary output; the outputs share the visited map so logic
+    // used by several outputs is only emitted once
+    let mut visited = FxHashMap::default();
+    let mut visit_count = FxHashMap::default();
+    let mut parts = Vec::with_capacity(roots.len());
+    for root in &roots {
         let equation = match mode {
             "small" => dag_to_equations_small(&graph.nodes, root, &mut visited, &mut visit_count),
             "large" => dag_to_equations_large(&graph.nodes, root, &mut visited, &mut visit_count),
@@ -315,25 +527,79 @@ fn main() {
                 dag_to_equations_small(&graph.nodes, root, &mut visited, &mut visit_count)
             }
         };
+        parts.push(equation);
+    }
 
-        let mut variables = vec![];
-        for node in graph.nodes.values() {
-            if node.children.is_empty() && !variables.contains(&node.op) && node.op != "1" && node.op != "0" {
-                variables.push(node.op.clone());
+    write_to_file(&variables, &output_names, parts, output_path, visited);
+
+    println!("Finished graph to equation conversion for {} outputs using {} mode", output_names.len(), mode);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a0 ^ a1 ^ ... ^ a{depth}, every xor used once
+    fn xor_chain(depth: usize) -> (FxHashMap<String, Node>, String) {
+        let mut nodes = FxHashMap::default();
+        let leaf = |name: String| Node { op: name.clone(), children: vec![], eclass: name, cost: 1.0 };
+        nodes.insert("a0".to_string(), leaf("a0".to_string()));
+        let mut last = "a0".to_string();
+        for i in 1..=depth {
+            nodes.insert(format!("a{}", i), leaf(format!("a{}", i)));
+            let class = format!("x{}", i);
+            nodes.insert(class.clone(), Node { op: "^".to_string(), children: vec![last, format!("a{}", i)], eclass: class.clone(), cost: 1.0 });
+            last = class;
+        }
+        (nodes, last)
+    }
+
+    #[test]
+    fn xor_chains_stay_linear_in_size() {
+        let depth = 24;
+        let (nodes, root) = xor_chain(depth);
+        for large in [false, true] {
+            let mut visited = FxHashMap::default();
+            let mut visit_count = FxHashMap::default();
+            let output = if large {
+                dag_to_equations_large(&nodes, &root, &mut visited, &mut visit_count)
+            } else {
+                dag_to_equations_small(&nodes, &root, &mut visited, &mut visit_count)
+            };
+            let size = output.len() + visited.iter().map(|(id, expr)| id.len() + expr.len()).sum::<usize>();
+            assert!(size < depth * 200, "{} bytes for a chain of {} xors (large: {})", size, depth, large);
+            // every named operand is defined
+            for expr in visited.values().chain([&output]) {
+                for reference in expr.split("new_n_").skip(1) {
+                    let id: String = reference.chars().take_while(char::is_ascii_alphanumeric).collect();
+                    assert!(visited.contains_key(&id), "new_n_{} is not defined", id);
+                }
             }
         }
+    }
 
-        let parts = format_synopsys_single(&equation);
-
-        write_to_file(
-            &variables,
-            parts,
-            &format!("{}", output_path),
-            "p",
-            visited,
-            &prefix_mapping,
-        );
-        
-        println!("Finished graph to equation conversion for circuit {} using {} mode", i + 1, mode);
+    #[test]
+    fn verilog_names_that_are_not_identifiers_are_escaped() {
+        let leaf = |name: &str| Node { op: name.to_string(), children: vec![], eclass: name.to_string(), cost: 1.0 };
+        let mut nodes = FxHashMap::default();
+        nodes.insert("a[0]".to_string(), leaf("a[0]"));
+        nodes.insert("module".to_string(), leaf("module"));
+        nodes.insert("b_1".to_string(), leaf("b_1"));
+        nodes.insert("5".to_string(), Node { op: "*".to_string(), children: vec!["a[0]".to_string(), "module".to_string()], eclass: "5".to_string(), cost: 1.0 });
+        nodes.insert("6".to_string(), Node { op: "+".to_string(), children: vec!["5".to_string(), "b_1".to_string()], eclass: "6".to_string(), cost: 1.0 });
+        let graph = Graph { nodes, root_eclasses: vec!["6".to_string()], root_names: vec![], input_names: vec![], latches: vec![] };
+        let variables = ["a[0]", "module", "b_1"].map(String::from);
+        let outputs = ["f[1]".to_string()];
+
+        let path = env::temp_dir().join(format!("graph2eqn-{}.v", std::process::id()));
+        write_verilog(&graph, &variables, &outputs, &graph.root_eclasses, path.to_str().unwrap());
+        let verilog = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(verilog.starts_with("module circuit (\\a[0] , \\module , b_1, \\f[1] );\n"), "{}", verilog);
+        assert!(verilog.contains("  input \\a[0] ;\n  input \\module ;\n  input b_1;\n  output \\f[1] ;\n"), "{}", verilog);
+        assert!(verilog.contains("and g5 (new_n_5, \\a[0] , \\module );"), "{}", verilog);
+        assert!(verilog.contains("or g6 (new_n_6, new_n_5, b_1);"), "{}", verilog);
+        assert!(verilog.contains("assign \\f[1]  = new_n_6;"), "{}", verilog);
     }
-}
\ No newline at end of file
+}
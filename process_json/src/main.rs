@@ -118,7 +118,7 @@ fn process_json_simplify_keys(
     Ok(())
 }
 
-// Function to update root eclasses in the output file
+// Function to update root eclasses (and output/input names) in the output file
 // Input:
 //   - graph_file: Path to the graph file containing the root eclasses
 //   - output_file: Path to the output file to update
@@ -134,12 +134,11 @@ fn update_root_eclasses(graph_file: &Path, output_file: &Path) {
         .unwrap();
     let source_data: Value = serde_json::from_str(&source_data).unwrap();
 
-    // Extract the root eclasses from the graph data
-    let root_eclasses = if let Some(root_eclasses) = source_data["root_eclasses"].as_array() {
-        root_eclasses.to_owned()
-    } else {
-        vec![]
-    };
+    // Extract the root eclasses (and the output/input names and latches) from the graph data
+    let metadata: Vec<(&str, Vec<Value>)> = ["root_eclasses", "root_names", "input_names", "latches"]
+        .into_iter()
+        .map(|key| (key, source_data[key].as_array().cloned().unwrap_or_default()))
+        .collect();
 
     // Read the target file data
     let mut target_data = String::new();
@@ -150,7 +149,9 @@ fn update_root_eclasses(graph_file: &Path, output_file: &Path) {
     let mut target_data: Value = serde_json::from_str(&target_data).unwrap();
 
     // Update the root eclasses in the target data
-    target_data["root_eclasses"] = json!(root_eclasses);
+    for (key, values) in metadata {
+        target_data[key] = json!(values);
+    }
 
     // Write the updated data back to the target file
     File::create(&output_file)
@@ -42,6 +42,47 @@ setup_directories() {
     echo -e "${GREEN}Setup complete.${RESET}\n"
 }
 
+usage() {
+    echo "Usage: $0 [-i iterations] [-c area|delay] [-p pattern] [-n samplings] [-r probability] [-e \"e-rewriter options\"]"
+    echo "Without options the settings are read interactively."
+    echo "  -e passes extra options to e-rewriter, e.g. -e \"--time-limit 60 --scheduler simple\" (see e-rewriter --help)"
+}
+
+# Function to parse the command line, returns 1 if no option was given
+parse_args() {
+    rewriter_args=""
+    if [ $# -eq 0 ]; then
+        return 1
+    fi
+    while getopts "i:c:p:n:r:e:h" opt; do
+        case $opt in
+            i) iteration_times=$OPTARG ;;
+            c) cost_function=$OPTARG ;;
+            p) pattern=$OPTARG ;;
+            n) num_samplings=$OPTARG ;;
+            r) prob_randomization=$OPTARG ;;
+            e) rewriter_args=$OPTARG ;;
+            h) usage; exit 0 ;;
+            *) usage; exit 1 ;;
+        esac
+    done
+    iteration_times=${iteration_times:-30}
+    cost_function=${cost_function:-"area"}
+    pattern=${pattern:-"faster-bottom-up"}
+    num_samplings=${num_samplings:-30}
+    prob_randomization=${prob_randomization:-0.1}
+    map_cost_function
+}
+
+# if cost_function is 'area', replace it with 'node_sum_cost', if it is 'delay', replace it with 'node_depth_cost'
+map_cost_function() {
+    if [ "$cost_function" == "area" ]; then
+        cost_function="node_sum_cost"
+    elif [ "$cost_function" == "delay" ]; then
+        cost_function="node_depth_cost"
+    fi
+}
+
 # Function to get user input
 get_user_input() {
     read -p "Enter the number of iteration times (optional, default: 1): " iteration_times
@@ -62,12 +103,7 @@ get_user_input() {
         prob_randomization=${prob_randomization:-0.1}
     fi
 
-    # if cost_function is 'area', replace it with 'node_sum_cost', if it is 'delay', replace it with 'node_depth_cost'
-    if [ "$cost_function" == "area" ]; then
-        cost_function="node_sum_cost"
-    elif [ "$cost_function" == "delay" ]; then
-        cost_function="node_depth_cost"
-    fi
+    map_cost_function
 }
 
 # Function to rewrite the circuit
@@ -87,7 +123,7 @@ rewrite_circuit() {
     copy_file "circuit0_opt.eqn" "../e-rewriter/circuit0_opt.eqn"
     change_dir "../e-rewriter"
     start_time_process_rw=$(date +%s.%N)
-    execute_command "$feature_cmd circuit0_opt.eqn $iteration_times"
+    execute_command "$feature_cmd circuit0_opt.eqn --iter-limit $iteration_times --output-dir rewritten_circuit $rewriter_args"
     change_dir ".."
     copy_file "e-rewriter/rewritten_circuit/rewritten_egraph_with_weight_cost_serd.json" "extraction-gym/input/"
 
@@ -296,7 +332,7 @@ feature_cmd="./target/x86_64-unknown-linux-musl/release/e-rewriter"
 echo -e "${YELLOW}Using feature label: ${feature}${RESET}"
 
 setup_directories
-get_user_input 
+parse_args "$@" || get_user_input
 rewrite_circuit # eqn2egraph, rewrite
 extract_dag # extract from saturated egraph, extract dag
 process_json # extract from saturated egraph, process json